@@ -0,0 +1,64 @@
+//! Measures how fast frames are drawn, run with: cargo bench --features bench
+use chip_8_emulator::assembler::assemble;
+use chip_8_emulator::chip8::CHIP8;
+use chip_8_emulator::error::Error;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+const MAX_CYCLES: u64 = 10_000;
+
+/// Covers the screen in 8x15 sprites, row by row, forever
+const FILL_SCREEN: &str = "
+    LD I, 0x50
+    start:
+    LD V0, 0
+    LD V1, 0
+    draw:
+    DRW V0, V1, 15
+    ADD V0, 8
+    SE V0, 64
+    JP draw
+    LD V0, 0
+    ADD V1, 15
+    SE V1, 45
+    JP draw
+    JP start
+";
+
+/// Toggles one sprite every instruction pair, so every frame differs from the last and is presented
+const TOGGLE: &str = "
+    LD I, 0x50
+    loop:
+    DRW V0, V1, 15
+    JP loop
+";
+
+/// Runs the program headless until it reaches MAX_CYCLES
+fn run(program: &[u8], cycles_per_frame: usize) {
+    let mut chip8 = CHIP8::headless();
+    chip8.deterministic = true;
+    chip8.cycles_per_frame = cycles_per_frame;
+    chip8.max_cycles = Some(MAX_CYCLES);
+    chip8.load_bytes(program).unwrap();
+    let result = chip8.run();
+    assert_eq!(
+        result,
+        Err(Error::InstructionLimitReached { limit: MAX_CYCLES })
+    );
+}
+
+fn draw_sprites(c: &mut Criterion) {
+    let program = assemble(FILL_SCREEN).unwrap();
+    c.bench_function("draw full screen of sprites", |b| {
+        b.iter(|| run(black_box(&program), 100))
+    });
+}
+
+fn draw_graphics(c: &mut Criterion) {
+    let program = assemble(TOGGLE).unwrap();
+    c.bench_function("present a frame every 2 instructions", |b| {
+        b.iter(|| run(black_box(&program), 2))
+    });
+}
+
+criterion_group!(benches, draw_sprites, draw_graphics);
+criterion_main!(benches);
@@ -0,0 +1,26 @@
+; Bounces a smiley face around the screen, run with: chip_8_emulator load --demo
+; Rebuild demo.chip8 after changing it with: chip_8_emulator assemble examples/demo.asm examples/demo.chip8
+        LD V0, 0            ; x
+        LD V1, 0            ; y
+        LD V2, 1            ; x speed, 255 moves left
+        LD V3, 1            ; y speed, 255 moves up
+        LD I, face
+loop:   DRW V0, V1, 5
+        LD V4, 2            ; Waits 2 frames
+        LD DT, V4
+wait:   LD V4, DT
+        SE V4, 0
+        JP wait
+        DRW V0, V1, 5       ; Drawing it again erases it
+        ADD V0, V2
+        ADD V1, V3
+        SNE V0, 0           ; Turns around at each edge
+        LD V2, 1
+        SNE V0, 56
+        LD V2, 255
+        SNE V1, 0
+        LD V3, 1
+        SNE V1, 27
+        LD V3, 255
+        JP loop
+face:   DB 0x24, 0x24, 0x00, 0x81, 0x7E
@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+use std::fmt;
+
+const ORIGIN: u16 = 0x200; // Where programs are loaded, so labels point at the right address
+
+/// A line of assembly that couldn't be assembled
+#[derive(Debug, PartialEq)]
+pub struct AssembleError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AssembleError {}
+
+#[derive(Debug, PartialEq)]
+enum Operand {
+    V(u16),
+    I,
+    IndirectI,
+    DT,
+    ST,
+    K,
+    F,
+    B,
+    Value(String),
+}
+
+/// An instruction or DB directive waiting for its labels to be resolved
+struct Statement {
+    line: usize,
+    mnemonic: String,
+    operands: Vec<Operand>,
+}
+
+/// Assembles a program written with the same mnemonics the disassembler prints, e.g. `LD V0, 0x05`
+/// Labels are written as `name:` and can be used in place of any address
+/// Comments start with `;` and `DB 0x12, 0x34` includes raw bytes
+pub fn assemble(source: &str) -> Result<Vec<u8>, AssembleError> {
+    // First pass: find where every label points
+    let mut labels = HashMap::new();
+    let mut statements = Vec::new();
+    let mut address = ORIGIN;
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let error = |message| AssembleError { line, message };
+        let mut text = text.split(';').next().unwrap_or("").trim();
+        if let Some((label, rest)) = split_label(text) {
+            if labels.insert(label.to_string(), address).is_some() {
+                return Err(error(format!("label {} is defined twice", label)));
+            }
+            text = rest;
+        }
+        if text.is_empty() {
+            continue;
+        }
+        let (mnemonic, rest) = match text.find(char::is_whitespace) {
+            Some(pos) => (&text[..pos], text[pos..].trim()),
+            None => (text, ""),
+        };
+        let mnemonic = mnemonic.to_uppercase();
+        let operands: Vec<Operand> = if rest.is_empty() {
+            Vec::new()
+        } else {
+            rest.split(',')
+                .map(|operand| parse_operand(operand.trim()))
+                .collect()
+        };
+        address += if mnemonic == "DB" {
+            operands.len() as u16
+        } else {
+            2
+        };
+        statements.push(Statement {
+            line,
+            mnemonic,
+            operands,
+        });
+    }
+
+    // Second pass: encode now that every label is known
+    let mut program = Vec::new();
+    for statement in statements {
+        let bytes = encode(&statement, &labels).map_err(|message| AssembleError {
+            line: statement.line,
+            message,
+        })?;
+        program.extend(bytes);
+    }
+    Ok(program)
+}
+
+/// Splits `name: rest` into the label and the rest of the line
+fn split_label(text: &str) -> Option<(&str, &str)> {
+    let pos = text.find(':')?;
+    let label = text[..pos].trim();
+    if label.is_empty() || !label.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return None;
+    }
+    Some((label, text[pos + 1..].trim()))
+}
+
+fn parse_operand(operand: &str) -> Operand {
+    match operand.to_uppercase().as_str() {
+        "I" => Operand::I,
+        "[I]" => Operand::IndirectI,
+        "DT" => Operand::DT,
+        "ST" => Operand::ST,
+        "K" => Operand::K,
+        "F" => Operand::F,
+        "B" => Operand::B,
+        upper => match upper
+            .strip_prefix('V')
+            .map(|reg| u16::from_str_radix(reg, 16))
+        {
+            Some(Ok(reg)) if upper.len() == 2 => Operand::V(reg),
+            _ => Operand::Value(operand.to_string()),
+        },
+    }
+}
+
+/// A number in hex (0x2A6) or decimal, or the address of a label, no bigger than max
+fn value(token: &str, labels: &HashMap<String, u16>, max: u16) -> Result<u16, String> {
+    let parsed = match token
+        .strip_prefix("0x")
+        .or_else(|| token.strip_prefix("0X"))
+    {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => token.parse().ok(),
+    };
+    let value = match parsed.or_else(|| labels.get(token).copied()) {
+        Some(value) => value,
+        None if token.starts_with(|c: char| c.is_ascii_digit()) => {
+            return Err(format!("invalid number {}", token))
+        }
+        None => return Err(format!("unknown label {}", token)),
+    };
+    if value > max {
+        return Err(format!(
+            "{} is too big, the most it can be is 0x{:x}",
+            token, max
+        ));
+    }
+    Ok(value)
+}
+
+fn encode(statement: &Statement, labels: &HashMap<String, u16>) -> Result<Vec<u8>, String> {
+    use Operand::*;
+    let addr = |token: &String| value(token, labels, 0xFFF);
+    let byte = |token: &String| value(token, labels, 0xFF);
+    let nibble = |token: &String| value(token, labels, 0xF);
+    let xy = |x: &u16, y: &u16| x << 8 | y << 4;
+
+    let mnemonic = statement.mnemonic.as_str();
+    let opcode = match (mnemonic, statement.operands.as_slice()) {
+        ("DB", bytes) if !bytes.is_empty() => {
+            return bytes
+                .iter()
+                .map(|operand| match operand {
+                    Value(token) => byte(token).map(|value| value as u8),
+                    _ => Err("DB only takes numbers".to_string()),
+                })
+                .collect();
+        }
+        ("HALT", []) => 0x0000,
+        ("CLS", []) => 0x00E0,
+        ("RET", []) => 0x00EE,
+        ("SYS", [Value(nnn)]) => addr(nnn)?,
+        ("JP", [Value(nnn)]) => 0x1000 | addr(nnn)?,
+        ("JP", [V(0), Value(nnn)]) => 0xB000 | addr(nnn)?,
+        ("CALL", [Value(nnn)]) => 0x2000 | addr(nnn)?,
+        ("SE", [V(x), Value(nn)]) => 0x3000 | x << 8 | byte(nn)?,
+        ("SNE", [V(x), Value(nn)]) => 0x4000 | x << 8 | byte(nn)?,
+        ("SE", [V(x), V(y)]) => 0x5000 | xy(x, y),
+        ("SAVE", [V(x), V(y)]) => 0x5002 | xy(x, y),
+        ("LOAD", [V(x), V(y)]) => 0x5003 | xy(x, y),
+        ("LD", [V(x), Value(nn)]) => 0x6000 | x << 8 | byte(nn)?,
+        ("ADD", [V(x), Value(nn)]) => 0x7000 | x << 8 | byte(nn)?,
+        ("LD", [V(x), V(y)]) => 0x8000 | xy(x, y),
+        ("OR", [V(x), V(y)]) => 0x8001 | xy(x, y),
+        ("AND", [V(x), V(y)]) => 0x8002 | xy(x, y),
+        ("XOR", [V(x), V(y)]) => 0x8003 | xy(x, y),
+        ("ADD", [V(x), V(y)]) => 0x8004 | xy(x, y),
+        ("SUB", [V(x), V(y)]) => 0x8005 | xy(x, y),
+        ("SHR", [V(x)]) => 0x8006 | x << 8,
+        ("SUBN", [V(x), V(y)]) => 0x8007 | xy(x, y),
+        ("SHL", [V(x)]) => 0x800E | x << 8,
+        ("SNE", [V(x), V(y)]) => 0x9000 | xy(x, y),
+        ("LD", [I, Value(nnn)]) => 0xA000 | addr(nnn)?,
+        ("RND", [V(x), Value(nn)]) => 0xC000 | x << 8 | byte(nn)?,
+        ("DRW", [V(x), V(y), Value(n)]) => 0xD000 | xy(x, y) | nibble(n)?,
+        ("SKP", [V(x)]) => 0xE09E | x << 8,
+        ("SKNP", [V(x)]) => 0xE0A1 | x << 8,
+        ("AUDIO", []) => 0xF002,
+        ("LD", [V(x), DT]) => 0xF007 | x << 8,
+        ("LD", [V(x), K]) => 0xF00A | x << 8,
+        ("LD", [DT, V(x)]) => 0xF015 | x << 8,
+        ("LD", [ST, V(x)]) => 0xF018 | x << 8,
+        ("ADD", [I, V(x)]) => 0xF01E | x << 8,
+        ("LD", [F, V(x)]) => 0xF029 | x << 8,
+        ("LD", [B, V(x)]) => 0xF033 | x << 8,
+        ("PITCH", [V(x)]) => 0xF03A | x << 8,
+        ("LD", [IndirectI, V(x)]) => 0xF055 | x << 8,
+        ("LD", [V(x), IndirectI]) => 0xF065 | x << 8,
+        (
+            "DB" | "HALT" | "CLS" | "RET" | "SYS" | "JP" | "CALL" | "SE" | "SNE" | "LD" | "ADD"
+            | "OR" | "AND" | "XOR" | "SUB" | "SHR" | "SUBN" | "SHL" | "RND" | "DRW" | "SKP"
+            | "SKNP" | "AUDIO" | "PITCH" | "SAVE" | "LOAD",
+            _,
+        ) => return Err(format!("invalid operands for {}", mnemonic)),
+        _ => return Err(format!("unknown instruction {}", mnemonic)),
+    };
+    Ok(opcode.to_be_bytes().to_vec())
+}
+
+#[test]
+fn test_assemble() {
+    let source = "
+        CLS
+        LD V0, 0x05
+        ld v1, 10      ; Lower case and decimal work too
+        ADD V0, V1
+        DRW V0, V1, 5
+        LD VA, [I]
+        HALT
+    ";
+    assert_eq!(
+        assemble(source),
+        Ok(vec![
+            0x00, 0xE0, 0x60, 0x05, 0x61, 0x0A, 0x80, 0x14, 0xD0, 0x15, 0xFA, 0x65, 0x00, 0x00
+        ])
+    );
+}
+
+#[test]
+fn test_assemble_labels() {
+    let source = "
+        start: CALL draw
+        JP start
+        draw:
+        LD I, sprite
+        RET
+        sprite: DB 0x3C, 0xC3, 255
+    ";
+    assert_eq!(
+        assemble(source),
+        Ok(vec![
+            0x22, 0x04, 0x12, 0x00, 0xA2, 0x08, 0x00, 0xEE, 0x3C, 0xC3, 0xFF
+        ])
+    );
+}
+
+#[test]
+fn test_assemble_errors() {
+    let error = |line, message: &str| {
+        Err(AssembleError {
+            line,
+            message: message.to_string(),
+        })
+    };
+    assert_eq!(
+        assemble("CLS\nJMP 0x200"),
+        error(2, "unknown instruction JMP")
+    );
+    assert_eq!(assemble("DRW V0, V1"), error(1, "invalid operands for DRW"));
+    assert_eq!(
+        assemble("LD V0, 0x100"),
+        error(1, "0x100 is too big, the most it can be is 0xff")
+    );
+    assert_eq!(assemble("JP nowhere"), error(1, "unknown label nowhere"));
+    assert_eq!(
+        assemble("a: CLS\na: CLS"),
+        error(2, "label a is defined twice")
+    );
+}
@@ -0,0 +1,146 @@
+use std::f32::consts::PI;
+
+/// The shape of the tone played while the sound timer is running
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Waveform {
+    Square,
+    Sine,
+    Triangle,
+}
+
+impl std::str::FromStr for Waveform {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "square" => Ok(Waveform::Square),
+            "sine" => Ok(Waveform::Sine),
+            "triangle" => Ok(Waveform::Triangle),
+            _ => Err(format!(
+                "unknown waveform {}, expected square, sine, or triangle",
+                s
+            )),
+        }
+    }
+}
+
+/// How the beeper sounds, defaults to a quiet square wave
+pub struct Audio {
+    /// From 0 (silent) to 100
+    pub volume: u8,
+    pub waveform: Waveform,
+    /// The pitch of the tone
+    pub beep_hz: f32,
+    /// XO-CHIP's 128 bit pattern, played a bit at a time instead of the beep
+    pub pattern: [u8; 16],
+    /// XO-CHIP's playback rate for the pattern, 64 plays 4000 bits a second
+    pub pitch: u8,
+    phase: f32,
+}
+
+impl Default for Audio {
+    fn default() -> Self {
+        Audio {
+            volume: 20,
+            waveform: Waveform::Square,
+            beep_hz: 440.0,
+            pattern: [0; 16],
+            pitch: 64,
+            phase: 0.0,
+        }
+    }
+}
+
+impl Audio {
+    /// The value of the waveform at a point through a cycle, scaled by the volume
+    pub fn sample(&self, phase: f32) -> f32 {
+        let value = match self.waveform {
+            Waveform::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Sine => (phase * 2.0 * PI).sin(),
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+        };
+        value * self.volume.min(100) as f32 / 100.0
+    }
+
+    /// Fills the buffer with the tone, carrying on where the last buffer left off
+    pub fn fill(&mut self, buffer: &mut [f32], sample_rate: u32) {
+        let step = self.beep_hz / sample_rate as f32;
+        for sample in buffer.iter_mut() {
+            *sample = self.sample(self.phase);
+            self.phase = (self.phase + step).fract();
+        }
+    }
+
+    /// Fills the buffer with the XO-CHIP pattern, looping over its 128 bits
+    /// A set bit plays at full volume and a clear bit at the opposite
+    pub fn fill_pattern(&mut self, buffer: &mut [f32], sample_rate: u32) {
+        let bits_per_second = 4000.0 * 2f32.powf((self.pitch as f32 - 64.0) / 48.0);
+        let step = bits_per_second / sample_rate as f32;
+        let volume = self.volume.min(100) as f32 / 100.0;
+        for sample in buffer.iter_mut() {
+            let bit = self.phase as usize % 128;
+            let set = self.pattern[bit / 8] & (0x80 >> (bit % 8)) != 0;
+            *sample = if set { volume } else { -volume };
+            self.phase = (self.phase + step) % 128.0;
+        }
+    }
+}
+
+#[test]
+fn test_waveform_from_str() {
+    assert_eq!("square".parse(), Ok(Waveform::Square));
+    assert_eq!("sine".parse(), Ok(Waveform::Sine));
+    assert_eq!("triangle".parse(), Ok(Waveform::Triangle));
+    assert!("sawtooth".parse::<Waveform>().is_err());
+}
+
+#[test]
+fn test_sample() {
+    let mut audio = Audio {
+        volume: 50,
+        ..Audio::default()
+    };
+    assert_eq!(audio.sample(0.25), 0.5);
+    assert_eq!(audio.sample(0.75), -0.5);
+    audio.waveform = Waveform::Triangle;
+    assert_eq!(audio.sample(0.0), -0.5);
+    assert_eq!(audio.sample(0.5), 0.5);
+    audio.waveform = Waveform::Sine;
+    assert!((audio.sample(0.25) - 0.5).abs() < 1e-6);
+    audio.volume = 0;
+    assert_eq!(audio.sample(0.25), 0.0);
+}
+
+#[test]
+fn test_fill_follows_beep_hz() {
+    let mut audio = Audio {
+        volume: 100,
+        beep_hz: 2000.0,
+        ..Audio::default()
+    };
+    // Two samples per half cycle at 8000Hz, whatever the waveform
+    let mut buffer = [0.0; 8];
+    audio.fill(&mut buffer, 8000);
+    assert_eq!(buffer, [1.0, 1.0, -1.0, -1.0, 1.0, 1.0, -1.0, -1.0]);
+    audio.waveform = Waveform::Triangle;
+    audio.fill(&mut buffer, 8000);
+    assert_eq!(buffer, [-1.0, 0.0, 1.0, 0.0, -1.0, 0.0, 1.0, 0.0]);
+}
+
+#[test]
+fn test_fill_pattern() {
+    let mut audio = Audio {
+        volume: 100,
+        ..Audio::default()
+    };
+    audio.pattern[0] = 0b1010_0000;
+    // Pitch 64 plays 4000 bits a second, one bit per sample at 4000Hz
+    let mut buffer = [0.0; 4];
+    audio.fill_pattern(&mut buffer, 4000);
+    assert_eq!(buffer, [1.0, -1.0, 1.0, -1.0]);
+}
@@ -0,0 +1,98 @@
+use crate::chip8::CHIP8;
+use crate::error::Error;
+use std::path::Path;
+
+/// How running one ROM went
+pub struct RomResult {
+    pub name: String,
+    pub result: Result<(), Error>,
+}
+
+/// Runs every .chip8 file in the directory headless, stopping any that run for more than max_cycles
+/// Keys are never pressed, so programs waiting on one run until the limit
+pub fn run_directory(dir: &Path, max_cycles: u64) -> std::io::Result<Vec<RomResult>> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) == Some("chip8") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut results = Vec::new();
+    for path in paths {
+        let rom = std::fs::read(&path)?;
+        let mut chip8 = CHIP8::headless();
+        chip8.deterministic = true;
+        chip8.non_blocking_keys = true;
+        chip8.max_cycles = Some(max_cycles);
+        let result = chip8.load_bytes(&rom).and_then(|_| chip8.run());
+        results.push(RomResult {
+            name: path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+            result,
+        });
+    }
+    Ok(results)
+}
+
+/// A table of each ROM and whether it halted, followed by the totals
+pub fn summary(results: &[RomResult]) -> String {
+    let width = results.iter().map(|rom| rom.name.len()).max().unwrap_or(0);
+    let mut summary = String::new();
+    for rom in results {
+        let outcome = match &rom.result {
+            Ok(()) => "halted".to_string(),
+            Err(err) => format!("error: {}", err),
+        };
+        summary.push_str(&format!(
+            "{:<width$}  {}\n",
+            rom.name,
+            outcome,
+            width = width
+        ));
+    }
+    let failed = results.iter().filter(|rom| rom.result.is_err()).count();
+    summary.push_str(&format!(
+        "{} halted, {} failed\n",
+        results.len() - failed,
+        failed
+    ));
+    summary
+}
+
+#[test]
+fn test_run_directory() {
+    let results = run_directory(Path::new("testbin"), 10_000).unwrap();
+    let result = |name: &str| &results.iter().find(|rom| rom.name == name).unwrap().result;
+    let limit_reached = Err(Error::InstructionLimitReached { limit: 10_000 });
+    assert_eq!(*result("add_xnn.chip8"), Ok(()));
+    assert_eq!(*result("infinite_loop.chip8"), limit_reached);
+    assert_eq!(*result("set_x_to_key_press.chip8"), limit_reached);
+    assert_eq!(
+        *result("stack_overflow.chip8"),
+        Err(Error::StackOverflow { pc: 0x200 })
+    );
+    assert!(matches!(
+        result("unknown_opcode.chip8"),
+        Err(Error::UnknownOpcode { .. })
+    ));
+    assert!(results.iter().all(|rom| rom.name.ends_with(".chip8")));
+
+    let summary = summary(&results);
+    let line = summary
+        .lines()
+        .find(|line| line.starts_with("add_xnn.chip8 "))
+        .unwrap();
+    assert!(line.ends_with("  halted"));
+    let failed = results.iter().filter(|rom| rom.result.is_err()).count();
+    assert!(summary.ends_with(&format!(
+        "{} halted, {} failed\n",
+        results.len() - failed,
+        failed
+    )));
+}
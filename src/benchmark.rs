@@ -0,0 +1,79 @@
+use crate::chip8::{StepResult, CHIP8};
+use crate::error::Error;
+use crate::instruction::{cycle_cost, decode};
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// The COSMAC VIP's CPU takes 8 clock pulses for each machine cycle
+const CLOCKS_PER_MACHINE_CYCLE: u64 = 8;
+
+/// How many instructions ran, what they'd cost on the COSMAC VIP, and how long they took
+pub struct BenchmarkResult {
+    pub instructions: u64,
+    /// The COSMAC VIP machine cycles the instructions add up to, by cycle_cost
+    pub machine_cycles: u64,
+    pub elapsed: Duration,
+}
+
+impl BenchmarkResult {
+    pub fn instructions_per_second(&self) -> f64 {
+        self.instructions as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// The clock speed in MHz a COSMAC VIP would need to run the instructions as fast, where the
+    /// real one runs at 1.76MHz
+    pub fn emulated_mhz(&self) -> f64 {
+        let clocks = self.machine_cycles * CLOCKS_PER_MACHINE_CYCLE;
+        clocks as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON) / 1_000_000.0
+    }
+}
+
+/// Runs the ROM headless for the number of instructions, or until it halts, timing only the CPU
+/// Instructions are stepped one at a time, so nothing is drawn, the timers don't count down, and
+/// keys are never read
+pub fn run(rom: &[u8], instructions: u64) -> Result<BenchmarkResult, Error> {
+    let mut chip8 = CHIP8::headless();
+    chip8.non_blocking_keys = true;
+    chip8.load_bytes(rom)?;
+    let machine_cycles = Rc::new(Cell::new(0));
+    let counter = machine_cycles.clone();
+    chip8.set_instruction_hook(Box::new(move |_, opcode| {
+        counter.set(counter.get() + cycle_cost(decode(opcode)) as u64);
+    }));
+    let start = Instant::now();
+    for _ in 0..instructions {
+        if let StepResult::Halted(_) = chip8.step()? {
+            break;
+        }
+    }
+    Ok(BenchmarkResult {
+        instructions: chip8.cycle_count(),
+        machine_cycles: machine_cycles.get(),
+        elapsed: start.elapsed(),
+    })
+}
+
+#[test]
+fn test_benchmark() {
+    let rom = std::fs::read("testbin/infinite_loop.chip8").unwrap();
+    let result = run(&rom, 10_000).unwrap();
+    assert_eq!(result.instructions, 10_000);
+    assert!(result.instructions_per_second() > 0.0);
+    assert!(result.emulated_mhz() > 0.0);
+    // Each jump is 12 machine cycles on top of fetching and decoding
+    assert_eq!(result.machine_cycles, 10_000 * 52);
+
+    // Clocks per instruction, which goes up with instructions that cost more, like drawing
+    let clocks_per_instruction =
+        |result: BenchmarkResult| result.emulated_mhz() * 1e6 / result.instructions_per_second();
+    let jumps = clocks_per_instruction(result);
+    assert!((jumps - 52.0 * 8.0).abs() < 1e-6);
+    let rom = crate::assembler::assemble("loop: DRW V0, V1, 15\nJP loop").unwrap();
+    let draws = clocks_per_instruction(run(&rom, 10_000).unwrap());
+    assert!(draws > jumps * 5.0);
+
+    // Programs that halt early count the instructions they ran
+    let rom = std::fs::read("testbin/add_xnn.chip8").unwrap();
+    assert_eq!(run(&rom, 10_000).unwrap().instructions, 3);
+}
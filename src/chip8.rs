@@ -1,703 +1,3824 @@
-use crate::color::Color;
-use minifb::{Key, KeyRepeat, Scale, Window, WindowOptions};
+use crate::audio::Audio;
+use crate::color::{blend, scale, Color, Palette};
+use crate::disassembler::{disassemble, opcode_class, OPCODE_CLASSES};
+use crate::error::Error;
+#[cfg(feature = "minifb")]
+use crate::frontend::MinifbFrontend;
+use crate::frontend::{DebugCommand, Frontend, NullFrontend};
+use crate::grid::Grid;
+#[cfg(test)]
+use crate::input_log::KeyChange;
+use crate::input_log::{self, InputEvent, InputRecorder, InputReplay};
+use crate::instruction::{cycle_cost, decode, Instruction, TYPICAL_CYCLE_COST};
+use crate::options::Options;
+use crate::overlay::Overlay;
+use crate::png;
+use crate::quirks::Quirks;
+use crate::rom_info::{identify_rom, sha1_hex};
+use crate::save_state;
+use log::{error, info, trace, warn};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
 use std::fs::File;
 use std::io::Read;
+use std::time::{Duration, Instant};
 
-const WIDTH: usize = 64;
-const HEIGHT: usize = 32;
-const OFF: u32 = 0x000000; // Black
+pub const WIDTH: usize = 64; // Default display size, SCHIP hires is 128x64
+pub const HEIGHT: usize = 32;
 const VF: usize = 0x0f;
+const ADDRESS_MASK: usize = 0x0fff; // Addresses are 12 bits
+const MEMORY_SIZE: usize = 4096;
+const FRAME_DURATION: Duration = Duration::from_micros(16_667); // 60Hz
+const PERSISTENCE_FADE: u8 = 64; // Unlit pixels fade out over 4 frames
+#[cfg(feature = "zip")]
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const DUMP_LEN: usize = 64; // Bytes shown by the debug memory dump
+const MEMORY_DUMP_FILE: &str = "memory.bin";
+const HEX_DUMP_FILE: &str = "memory.txt";
+const AUTOSAVE_SLOTS: usize = 3; // Autosaves kept, in case the newest was cut off mid-write
+/// Bounces a face around the screen, run when no program is given, see examples/demo.asm
+pub const DEMO_ROM: &[u8] = include_bytes!("../examples/demo.chip8");
+
+const FONTS: [u8; 80] = [
+    0xf0, 0x90, 0x90, 0x90, 0xf0, 0x20, 0x60, 0x20, 0x20, 0x70, 0xf0, 0x10, 0xf0, 0x80, 0xf0, 0xf0,
+    0x10, 0xf0, 0x10, 0xf0, 0x90, 0x90, 0xf0, 0x10, 0x10, 0xf0, 0x80, 0xf0, 0x10, 0xf0, 0xf0, 0x80,
+    0xf0, 0x90, 0xf0, 0xf0, 0x10, 0x20, 0x40, 0x40, 0xf0, 0x90, 0xf0, 0x90, 0xf0, 0xf0, 0x90, 0xf0,
+    0x10, 0xf0, 0xf0, 0x90, 0xf0, 0x90, 0x90, 0xe0, 0x90, 0xe0, 0x90, 0xe0, 0xf0, 0x80, 0x80, 0x80,
+    0xf0, 0xe0, 0x90, 0x90, 0x90, 0xe0, 0xf0, 0x80, 0xf0, 0x80, 0xf0, 0xf0, 0x80, 0xf0, 0x80, 0x80,
+];
+
+/// The outcome of successfully executing a single instruction
+#[derive(Debug, PartialEq)]
+pub enum StepResult {
+    Continue,
+    Halted(HaltReason),
+}
+
+/// Why a 0x0000 halted the program
+#[derive(Debug, PartialEq)]
+pub enum HaltReason {
+    /// The program has a 0x0000 in it to stop itself
+    HaltOpcode,
+    /// Execution ran past the end of the loaded program into empty memory
+    EndOfProgram,
+    /// A 1NNN jumped to its own address, which would loop forever, with detect_idle on
+    IdleLoop,
+}
+
+impl fmt::Display for HaltReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HaltReason::HaltOpcode => write!(f, "halted by a 0x0000 in the program"),
+            HaltReason::EndOfProgram => write!(f, "ran past the end of the program"),
+            HaltReason::IdleLoop => write!(f, "jumped to itself in an idle loop"),
+        }
+    }
+}
+
+/// Who counts the delay and sound timers down
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimerMode {
+    /// The emulator counts them down once a frame
+    Auto,
+    /// The host counts them down by calling tick_timers at 60Hz, for embedders running their own loop
+    Manual,
+}
+
+/// Formats bytes as lines of 16 hex bytes followed by their ASCII characters, numbered from addr
+fn hex_dump(bytes: &[u8], addr: usize) -> String {
+    let mut dump = String::new();
+    for (line, bytes) in bytes.chunks(16).enumerate() {
+        let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let ascii: String = bytes
+            .iter()
+            .map(|&byte| {
+                if (0x20..0x7f).contains(&byte) {
+                    byte as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        dump.push_str(&format!(
+            "{:03x}: {:<47}  |{}|\n",
+            addr + line * 16,
+            hex.join(" "),
+            ascii
+        ));
+    }
+    dump
+}
+
+/// The registers from Vx to Vy in the order XO-CHIP's 5xy2 and 5xy3 go through them
+fn register_range(x: u8, y: u8) -> Vec<usize> {
+    let (x, y) = (x as usize, y as usize);
+    if x <= y {
+        (x..=y).collect()
+    } else {
+        (y..=x).rev().collect()
+    }
+}
+
+/// How a pixel is shown in text dumps
+fn pixel_char(on: bool) -> char {
+    if on {
+        '#'
+    } else {
+        '.'
+    }
+}
+
+/// Draws sprite bytes as rows of 8 pixels, the highest bit on the left like Dxyn
+fn sprite_text(bytes: &[u8]) -> String {
+    let mut text = String::new();
+    for byte in bytes {
+        text.extend((0..8).rev().map(|bit| pixel_char(byte >> bit & 1 == 1)));
+        text.push('\n');
+    }
+    text
+}
+
+/// Called with the display each time a new frame is drawn
+pub type FrameCallback = Box<dyn FnMut(&Grid<bool>)>;
+
+/// The machine state before an instruction executed, used to rewind a step in debug mode
+/// Only the display pixels and memory bytes the instruction changed are kept
+struct RewindState {
+    registers: [u8; 16],
+    i: u16,
+    position_in_memory: usize,
+    stack: [u16; 16],
+    stack_pointer: usize,
+    delay_timer: u8,
+    sound_timer: u8,
+    display_diff: Vec<(usize, usize)>,
+    memory_diff: Vec<(usize, u8)>,
+}
+
+/// A copy of the whole machine, for comparing in tests
+#[derive(Clone, Debug, PartialEq)]
+pub struct State {
+    pub registers: [u8; 16],
+    pub i: u16,
+    pub pc: usize,
+    pub stack: [u16; 16],
+    pub stack_pointer: usize,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub keys: [bool; 16],
+    pub display: Grid<bool>,
+    pub memory: Vec<u8>,
+}
+
+impl State {
+    /// Describes every difference from other, e.g. `V3: 05 -> 07`, for readable test failures
+    pub fn diff(&self, other: &State) -> Vec<String> {
+        let mut diffs = Vec::new();
+        for (reg, (old, new)) in self.registers.iter().zip(&other.registers).enumerate() {
+            if old != new {
+                diffs.push(format!("V{:X}: {:02x} -> {:02x}", reg, old, new));
+            }
+        }
+        if self.i != other.i {
+            diffs.push(format!("I: {:03x} -> {:03x}", self.i, other.i));
+        }
+        if self.pc != other.pc {
+            diffs.push(format!("PC: {:03x} -> {:03x}", self.pc, other.pc));
+        }
+        for (level, (old, new)) in self.stack.iter().zip(&other.stack).enumerate() {
+            if old != new {
+                diffs.push(format!("stack[{}]: {:03x} -> {:03x}", level, old, new));
+            }
+        }
+        if self.stack_pointer != other.stack_pointer {
+            diffs.push(format!(
+                "SP: {} -> {}",
+                self.stack_pointer, other.stack_pointer
+            ));
+        }
+        if self.delay_timer != other.delay_timer {
+            diffs.push(format!("DT: {} -> {}", self.delay_timer, other.delay_timer));
+        }
+        if self.sound_timer != other.sound_timer {
+            diffs.push(format!("ST: {} -> {}", self.sound_timer, other.sound_timer));
+        }
+        for (key, (old, new)) in self.keys.iter().zip(&other.keys).enumerate() {
+            if old != new {
+                diffs.push(format!("key {:X}: {} -> {}", key, old, new));
+            }
+        }
+        if self.display != other.display {
+            let changed = self.display.cells().iter().zip(other.display.cells());
+            let count = changed.filter(|(old, new)| old != new).count();
+            diffs.push(format!("display: {} pixels changed", count));
+        }
+        for (addr, (old, new)) in self.memory.iter().zip(&other.memory).enumerate() {
+            if old != new {
+                diffs.push(format!("memory {:03x}: {:02x} -> {:02x}", addr, old, new));
+            }
+        }
+        if self.memory.len() != other.memory.len() {
+            diffs.push(format!(
+                "memory size: {} -> {}",
+                self.memory.len(),
+                other.memory.len()
+            ));
+        }
+        diffs
+    }
+
+    /// A SHA-1 of the dump, to check a run ended in exactly the same state
+    pub fn hash(&self) -> String {
+        sha1_hex(self.dump().as_bytes())
+    }
+
+    /// Formats the registers, timers, keys, display, and a hex dump of memory as text
+    pub fn dump(&self) -> String {
+        let mut dump = String::new();
+        for (reg, value) in self.registers.iter().enumerate() {
+            dump.push_str(&format!("V{:X}: {:02x}\n", reg, value));
+        }
+        dump.push_str(&format!(
+            "I: {:03x}\nPC: {:03x}\nSP: {}\n",
+            self.i, self.pc, self.stack_pointer
+        ));
+        let stack: Vec<String> = self
+            .stack
+            .iter()
+            .map(|addr| format!("{:03x}", addr))
+            .collect();
+        dump.push_str(&format!("Stack: {}\n", stack.join(" ")));
+        dump.push_str(&format!(
+            "DT: {}\nST: {}\n",
+            self.delay_timer, self.sound_timer
+        ));
+        let keys: Vec<String> = (0..16)
+            .filter(|&key| self.keys[key])
+            .map(|key| format!("{:X}", key))
+            .collect();
+        dump.push_str(&format!("Keys held: {}\n", keys.join(" ")));
+        dump.push_str("\nDisplay:\n");
+        for row in self.display.rows() {
+            let line: String = row.iter().map(|&on| pixel_char(on)).collect();
+            dump.push_str(&line);
+            dump.push('\n');
+        }
+        dump.push_str("\nMemory:\n");
+        dump.push_str(&hex_dump(&self.memory, 0));
+        dump
+    }
+}
 
 pub struct CHIP8 {
     registers: [u8; 16],
     i: u16,
     position_in_memory: usize,
-    memory: [u8; 4096],
+    memory: Vec<u8>,
+    fonts: [u8; 80],
     stack: [u16; 16],
     stack_pointer: usize,
     keys: [bool; 16],
+    /// Keys pressed with set_key that Fx0A hasn't read yet, oldest first
+    key_presses: VecDeque<usize>,
     delay_timer: u8,
     sound_timer: u8,
-    display: [[bool; WIDTH]; HEIGHT],
-    window: Window,
+    timer_mode: TimerMode,
+    display: Grid<bool>,
+    intensity: Grid<u8>,
+    pub persistence: bool,
+    last_fade: Instant,
+    frontend: Box<dyn Frontend>,
+    last_presented: Vec<u32>,
+    /// The display as of the last frame_diff
+    diffed_display: Grid<bool>,
+    /// Reused each frame so drawing doesn't allocate
+    frame_buffer: Vec<u32>,
     draw_flag: bool,
+    vblank: bool,
+    pub cycles_per_frame: usize,
+    pub max_cycles: Option<u64>,
+    cycle_count: u64,
+    pub deterministic: bool,
+    rng: StdRng,
+    pub start_address: usize,
+    program_end: usize,
+    last_frame: Instant,
     pub debug: bool,
+    pub breakpoints: HashSet<usize>,
+    pub watch_addr: Option<usize>,
+    pub dump_addr: Option<usize>,
+    history: VecDeque<RewindState>,
+    pub rewind_depth: usize,
+    memory_log: Option<Vec<(usize, u8)>>,
+    stop_at_breakpoints: bool,
+    pub trace: bool,
+    pub profile: bool,
+    /// Counts the opcode classes run like profile, for coverage_report
+    pub coverage: bool,
+    opcode_counts: HashMap<&'static str, u64>,
+    instruction_hook: Option<Box<dyn FnMut(usize, u16)>>,
+    sound_callback: Option<Box<dyn FnMut(bool)>>,
+    frame_callback: Option<FrameCallback>,
     pub color: Color,
+    /// Replaces the palette made from color
+    pub palette: Option<Palette>,
+    /// Multiplies the brightness of lit pixels, from 0.0 to 2.0
+    pub brightness: f32,
+    /// Swaps the lit and unlit colors, toggled with the I key while running
+    pub inverted: bool,
+    /// Lines drawn over the presented frame, which is scaled up to make room for them
+    pub overlay: Overlay,
+    pub quirks: Quirks,
+    pub audio: Audio,
+    pub xo_chip: bool,
+    /// Fx0A runs again on the next step instead of blocking until a key is pressed
+    pub non_blocking_keys: bool,
+    /// Fx0A takes a key that's already held down instead of waiting for a new press
+    pub key_repeat: bool,
+    /// Frames end once their instructions add up to cycles_per_frame typical instructions' worth
+    /// of COSMAC VIP machine cycles, instead of after cycles_per_frame instructions
+    pub cycle_costs: bool,
+    /// Stops running while the display doesn't have focus
+    pub pause_on_blur: bool,
+    /// Shows the instructions and frames per second in the title
+    pub show_stats: bool,
+    /// Errors instead of wrapping the PC around to 0x000 when it runs past the end of memory
+    pub halt_on_pc_wrap: bool,
+    /// Prints a summary of the loaded program and settings before running it
+    pub verbose: bool,
+    /// Halts when a 1NNN jumps to its own address instead of spinning there forever
+    pub detect_idle: bool,
+    /// Warns about instructions that are allowed but probably mistakes, like writing to VF directly
+    pub strict: bool,
+    /// Writes the final state and why it stopped to this file when run returns
+    pub dump_on_exit: Option<String>,
+    /// Writes the display to this PNG file when the program halts or hits max_cycles
+    pub screenshot_on_halt: Option<String>,
+    /// How many pixels wide and high each CHIP8 pixel is in screenshots
+    pub screenshot_scale: usize,
+    /// Writes every key pressed and released, with the cycle it happened on, to this file when run returns
+    pub record_input: Option<String>,
+    /// Saves the state every this many frames, to AUTOSAVE_SLOTS files that take turns
+    pub autosave: Option<u64>,
+    /// Autosaves go to this path with .0.state to .2.state added, .0 being the newest
+    pub autosave_path: String,
+    /// Restores this save state once the first program in run_sequence loads, to carry on from
+    /// where an autosave left off
+    pub resume_from: Option<String>,
+    input_recorder: InputRecorder,
+    input_replay: Option<InputReplay>,
+    strict_warnings: Vec<String>,
+}
+
+#[cfg(feature = "minifb")]
+impl Default for CHIP8 {
+    fn default() -> Self {
+        CHIP8::new()
+    }
 }
 
 impl CHIP8 {
+    #[cfg(feature = "minifb")]
     pub fn new() -> CHIP8 {
+        CHIP8::with_options(Options::default())
+    }
+
+    /// Creates a CHIP8 set up with the options, drawing to a window unless it's headless
+    pub fn with_options(options: Options) -> CHIP8 {
+        #[cfg(feature = "minifb")]
+        let frontend: Box<dyn Frontend> = if options.headless {
+            Box::new(NullFrontend)
+        } else {
+            Box::new(MinifbFrontend::new(WIDTH, HEIGHT, options.scale))
+        };
+        #[cfg(not(feature = "minifb"))]
+        let frontend: Box<dyn Frontend> = Box::new(NullFrontend);
+        CHIP8::with_frontend_options(frontend, options)
+    }
+
+    /// Creates a CHIP8 set up with the options that draws to and reads keys from the given frontend
+    /// The headless and scale options are ignored since the frontend is already picked
+    pub fn with_frontend_options(frontend: Box<dyn Frontend>, options: Options) -> CHIP8 {
+        let mut chip8 = CHIP8::with_frontend(frontend).with_memory_size(options.memory_size);
+        chip8.color = options.color;
+        chip8.palette = options.palette;
+        chip8.brightness = options.brightness;
+        chip8.quirks = options.quirks;
+        chip8.cycles_per_frame = options.cycles_per_frame;
+        if let Some(seed) = options.seed {
+            chip8.set_seed(seed);
+        }
+        chip8.debug = options.debug;
+        chip8.start_address = options.start_address;
+        chip8.trace = options.trace;
+        chip8.profile = options.profile;
+        chip8.coverage = options.coverage;
+        chip8.strict = options.strict;
+        chip8.key_repeat = options.key_repeat;
+        chip8.cycle_costs = options.cycle_costs;
+        chip8.max_cycles = options.max_cycles;
+        chip8.deterministic = options.deterministic;
+        chip8.xo_chip = options.xo_chip;
+        chip8.audio = options.audio;
+        chip8
+    }
+
+    /// Creates a CHIP8 without a display or keyboard
+    pub fn headless() -> CHIP8 {
+        CHIP8::with_frontend(Box::new(NullFrontend))
+    }
+
+    /// Creates a CHIP8 that draws to and reads keys from the given frontend
+    pub fn with_frontend(frontend: Box<dyn Frontend>) -> CHIP8 {
+        CHIP8::with_display_size(frontend, WIDTH, HEIGHT)
+    }
+
+    /// Swaps the usual 4096 bytes of memory for size bytes, for variants with more or less RAM
+    /// The memory has to have room for the font at 0x50 to 0x9F
+    pub fn with_memory_size(mut self, size: usize) -> CHIP8 {
+        assert!(size >= 0xA0, "{} bytes of memory can't hold the font", size);
+        self.memory = vec![0; size];
+        self
+    }
+
+    /// How many bytes of memory there are, 4096 unless with_memory_size changed it
+    pub fn memory_size(&self) -> usize {
+        self.memory.len()
+    }
+
+    /// Creates a CHIP8 with a width x height display instead of the usual 64x32
+    pub fn with_display_size(frontend: Box<dyn Frontend>, width: usize, height: usize) -> CHIP8 {
         CHIP8 {
             registers: [0; 16],
             i: 0,
-            memory: [0; 4096],
+            memory: vec![0; MEMORY_SIZE],
+            fonts: FONTS,
             position_in_memory: 0x200, // We start reading at 0x200 on the COSMAC VIP though, other variants started at other memory locations apparently
             stack: [0; 16],
             stack_pointer: 0,
             keys: [false; 16],
+            key_presses: VecDeque::new(),
             delay_timer: 0,
             sound_timer: 0,
-            display: [[false; 64]; 32],
-            window: Window::new(
-                "CHIP8",
-                WIDTH,
-                HEIGHT,
-                WindowOptions {
-                    scale: Scale::X32, // Change this value to X16, X8 to make the pixels and window smaller
-                    ..WindowOptions::default()
-                },
-            )
-            .unwrap_or_else(|e| {
-                panic!("Error creating window: {}", e);
-            }),
+            timer_mode: TimerMode::Auto,
+            display: Grid::new(width, height),
+            intensity: Grid::new(width, height),
+            persistence: false,
+            last_fade: Instant::now(),
+            frontend,
+            last_presented: Vec::new(),
+            diffed_display: Grid::new(width, height),
+            frame_buffer: Vec::new(),
             draw_flag: false,
+            vblank: false,
+            cycles_per_frame: 10,
+            max_cycles: None,
+            cycle_count: 0,
+            deterministic: false,
+            rng: StdRng::from_entropy(),
+            start_address: 0x200, // ETI-660 programs start at 0x600
+            program_end: 0x200,
+            last_frame: Instant::now(),
             debug: false,
+            breakpoints: HashSet::new(),
+            watch_addr: None,
+            dump_addr: None,
+            history: VecDeque::new(),
+            rewind_depth: 100,
+            memory_log: None,
+            stop_at_breakpoints: true,
+            trace: false,
+            profile: false,
+            opcode_counts: HashMap::new(),
+            coverage: false,
+            instruction_hook: None,
+            sound_callback: None,
+            frame_callback: None,
             color: Color::Purple,
+            palette: None,
+            brightness: 1.0,
+            inverted: false,
+            overlay: Overlay::None,
+            quirks: Quirks::default(),
+            audio: Audio::default(),
+            xo_chip: false,
+            non_blocking_keys: false,
+            key_repeat: false,
+            cycle_costs: false,
+            pause_on_blur: false,
+            show_stats: false,
+            halt_on_pc_wrap: false,
+            verbose: false,
+            detect_idle: false,
+            strict: false,
+            strict_warnings: Vec::new(),
+            dump_on_exit: None,
+            screenshot_on_halt: None,
+            screenshot_scale: 8,
+            record_input: None,
+            autosave: None,
+            autosave_path: "autosave".to_string(),
+            resume_from: None,
+            input_recorder: InputRecorder::default(),
+            input_replay: None,
         }
     }
 
-    /// The main run loop: Executes instructions, draws if the draw flag is set, and sets the keys on each loop
-    pub fn run(&mut self) {
-        loop {
-            if self.debug {
-                self.wait_on_debug_input();
+    /// The main run loop: Executes a frame's budget of instructions (see frame_budget), then ends
+    /// the frame by drawing, counting down the timers, setting the keys, and sleeping until the
+    /// next 60Hz frame
+    pub fn run(&mut self) -> Result<(), Error> {
+        self.run_until_stopped().map(|_| ())
+    }
+
+    /// Runs the program like run, returning why it halted, or None if the user quit
+    fn run_until_stopped(&mut self) -> Result<Option<HaltReason>, Error> {
+        let result = self.run_frames();
+        if let Some(path) = self.dump_on_exit.clone() {
+            let reason = match &result {
+                Ok(Some(reason)) => reason.to_string(),
+                Ok(None) => "quit".to_string(),
+                Err(err) => format!("error: {}", err),
+            };
+            let dump = format!("Exit: {}\n\n{}", reason, self.snapshot().dump());
+            if let Err(err) = std::fs::write(&path, dump) {
+                error!("Error writing the state to {}: {}", path, err);
             }
-            if self.emulate_cycle() {
-                break;
+        }
+        let stopped = matches!(
+            result,
+            Ok(Some(_)) | Err(Error::InstructionLimitReached { .. })
+        );
+        if let Some(path) = self.screenshot_on_halt.clone().filter(|_| stopped) {
+            if let Err(err) = std::fs::write(&path, self.screenshot()) {
+                error!("Error writing the screenshot to {}: {}", path, err);
             }
-            if self.draw_flag {
-                self.draw_graphics();
+        }
+        if let Some(path) = self.record_input.clone() {
+            if let Err(err) = std::fs::write(&path, input_log::format(self.recorded_input())) {
+                error!("Error writing the recorded input to {}: {}", path, err);
             }
-            self.set_keys();
         }
+        if let Ok(None) = result {
+            // Nothing counts the sound timer down once the user quits, even in the middle of Fx0A
+            self.set_sound_timer(0);
+        }
+        result
     }
 
-    /// Loop until a valid key is pressed
-    fn wait_on_debug_input(&mut self) {
-        let mut key_pressed = false;
-        while !key_pressed {
-            self.window.update();
-            self.window.get_keys_pressed(KeyRepeat::No).iter().for_each(|key|
-                match key {
-                    Key::Enter => { key_pressed = true },
-                    Key::Escape => { std::process::exit(0) },
-                    Key::Delete => {
-                        key_pressed = true;
-                        self.debug = false;
-                    },
-                    _ => {}
-                }
-            );
-        }
+    /// Runs the programs one after another, resetting the machine before loading each
+    /// Files that can't be loaded are skipped, returning LoadFailed for the first of them once the
+    /// rest have run, and the rest are skipped once a program stops with an error or the user quits
+    pub fn run_sequence(&mut self, files: &[String]) -> Result<(), Error> {
+        self.run_sequence_with(files, |_, _| {})
     }
 
-    /// Loads an operation from memory and executes the operation
-    /// returns true when it loads a 0x0000 or exit operation
-    fn emulate_cycle(&mut self) -> bool {
-        let op_byte1 = self.memory[self.position_in_memory] as u16;
-        let op_byte2 = self.memory[self.position_in_memory + 1] as u16;
-        let opcode = op_byte1 << 8 | op_byte2;
-
-        let x = ((opcode & 0x0F00) >> 8) as u8;
-        let y = ((opcode & 0x00F0) >> 4) as u8;
-        let nn = (opcode & 0x00FF) as u8;
-        let n = (opcode & 0x000F) as u8;
-        let nnn = opcode & 0x0FFF;
-
-        self.position_in_memory += 2;
-
-        let mut execution_finished = false;
-        match opcode {
-            0x0000 => execution_finished = true,
-            0x00E0 => self.clear_screen(),
-            0x00EE => self.ret(),
-            0x1000..=0x1FFF => self.goto(nnn),
-            0x2000..=0x2FFF => self.call(nnn),
-            0x3000..=0x3FFF => self.skip_if_equal(x, nn),
-            0x4000..=0x4FFF => self.skip_if_not_equal(x, nn),
-            0x5000..=0x5FF0 => self.skip_xy_equal(x, y),
-            0x6000..=0x6FFF => self.set_xnn(x, nn),
-            0x7000..=0x7FFF => self.add_xnn(x, nn),
-            0x8000..=0x8FFF => match n {
-                0 => self.assign_xy(x, y),
-                1 => self.or_xy(x, y),
-                2 => self.and_xy(x, y),
-                3 => self.xor_xy(x, y),
-                4 => self.add_xy(x, y),
-                5 => self.sub_xy(x, y),
-                6 => self.shift_right(x),
-                7 => self.sub_yx(x, y),
-                14 => self.shift_left(x),
-                _ => unimplemented!("opcode {:04x}", opcode),
-            },
-            0x9000..=0x9FF0 => self.skip_xy_not_equal(x, y),
-            0xA000..=0xAFFF => self.set_16bit_register(nnn),
-            0xB000..=0xBFFF => self.jump_nnn_plus_v0(nnn),
-            0xC000..=0xCFFF => self.rand(x, nn),
-            0xD000..=0xDFFF => self.draw(x, y, n),
-            0xE000..=0xEFFF => match nn {
-                0x9E => self.skip_if_key_pressed(x),
-                0xA1 => self.skip_if_key_not_pressed(x),
-                _ => unimplemented!("opcode {:04x}", opcode),
-            },
-            0xF000..=0xFFFF => match nn {
-                0x07 => self.set_x_to_delay_timer(x),
-                0x0A => self.set_x_to_keypress(x),
-                0x15 => self.set_delay_timer_to_x(x),
-                0x18 => self.set_sound_timer_to_x(x),
-                0x1E => self.add_ix(x),
-                0x29 => self.set_i_sprite_addr_x(x),
-                0x33 => self.set_bcd(x),
-                0x55 => self.reg_dump(x),
-                0x65 => self.reg_load(x),
-                _ => unimplemented!("opcode {:04x}", opcode),
-            },
-            _ => unimplemented!("opcode: {:04x}", opcode),
+    /// Runs the programs like run_sequence, calling finished with each program that ran once it
+    /// stops, before the reset clears its profile and coverage counts
+    pub fn run_sequence_with(
+        &mut self,
+        files: &[String],
+        mut finished: impl FnMut(&CHIP8, &str),
+    ) -> Result<(), Error> {
+        let mut unloaded = None;
+        for file in files {
+            self.reset();
+            if !self.load_into_memory(file) {
+                unloaded.get_or_insert_with(|| file.clone());
+                continue;
+            }
+            if let Some(path) = self.resume_from.take() {
+                self.load_state(&path)
+                    .map_err(|reason| Error::InvalidSaveState { file: path, reason })?;
+            }
+            if self.verbose {
+                print!("{}", self.banner(file));
+            }
+            let stopped = self.run_until_stopped();
+            finished(self, file);
+            if stopped?.is_none() {
+                break;
+            }
+        }
+        match unloaded {
+            Some(file) => Err(Error::LoadFailed { file }),
+            None => Ok(()),
         }
-        execution_finished
     }
 
-    /// Update the window
-    fn draw_graphics(&mut self) {
-        let mut buf = Vec::new();
-        for i in 0..self.display.len() {
-            for j in 0..self.display[0].len() {
-                if self.display[i][j] {
-                    buf.push(self.color.hex_color())
+    /// Clears the registers, stack, timers, keys, display, and memory, as if just powered on
+    /// The profile and coverage counts and recorded input start over too, but settings like the
+    /// quirks, colors, speed, and input being replayed are kept
+    pub fn reset(&mut self) {
+        self.registers = [0; 16];
+        self.i = 0;
+        self.position_in_memory = self.wrap_address(self.start_address);
+        self.stack = [0; 16];
+        self.stack_pointer = 0;
+        self.keys = [false; 16];
+        self.key_presses.clear();
+        self.delay_timer = 0;
+        self.set_sound_timer(0);
+        self.display.clear();
+        self.intensity.clear();
+        self.draw_flag = false;
+        self.vblank = false;
+        self.history.clear();
+        self.memory.iter_mut().for_each(|byte| *byte = 0);
+        self.cycle_count = 0;
+        self.opcode_counts.clear();
+        self.input_recorder = InputRecorder::default();
+    }
+
+    /// Runs frames until the program halts, returning why
+    /// Returns None when the user quits or closes the window
+    fn run_frames(&mut self) -> Result<Option<HaltReason>, Error> {
+        let mut cycles = 0;
+        let mut stats = Stats::new();
+        // With cycle_costs, the machine cycles the last instruction of a frame ran past its budget
+        let mut overspent = 0;
+        let mut frames: u64 = 0;
+        loop {
+            if !self.frontend.is_open() {
+                return Ok(None);
+            }
+            if self.pause_on_blur {
+                self.wait_for_focus();
+            }
+            let budget = self.frame_budget();
+            let mut spent = overspent;
+            while spent < budget {
+                if let Some(limit) = self.max_cycles.filter(|&limit| cycles >= limit) {
+                    return Err(Error::InstructionLimitReached { limit });
+                }
+                cycles += 1;
+                spent += self.next_cost();
+                stats.instructions += 1;
+                self.hit_breakpoint();
+                let result = if self.debug {
+                    if !self.wait_on_debug_input() {
+                        return Ok(None);
+                    }
+                    self.step_with_history()
                 } else {
-                    buf.push(OFF)
+                    self.emulate_cycle()
+                };
+                match result {
+                    Ok(StepResult::Halted(reason)) => {
+                        if self.take_draw_flag() {
+                            self.draw_graphics();
+                        }
+                        return Ok(Some(reason));
+                    }
+                    Ok(StepResult::Continue) => {}
+                    // Stay in the step prompt so the state leading up to the error can be inspected
+                    Err(err) if self.debug => eprintln!("Error: {}", err),
+                    Err(err) => return Err(err),
+                }
+                // Show every step while debugging instead of waiting for the end of the frame
+                if self.debug && self.take_draw_flag() {
+                    self.draw_graphics();
+                }
+                if std::mem::replace(&mut self.vblank, false) {
+                    break;
+                }
+            }
+            overspent = spent.saturating_sub(budget);
+            self.end_frame();
+            stats.frames += 1;
+            frames += 1;
+            if self
+                .autosave
+                .is_some_and(|every| every > 0 && frames % every == 0)
+            {
+                self.autosave();
+            }
+            if self.frontend.take_invert_toggle() {
+                self.inverted = !self.inverted;
+                self.draw_graphics();
+            }
+            if self.show_stats {
+                if let Some(title) = stats.title() {
+                    self.frontend.set_title(&title);
                 }
             }
         }
-        self.window.update_with_buffer(&buf, WIDTH, HEIGHT).unwrap();
     }
 
-    /// disp_clear()
-    fn clear_screen(&mut self) {
-        self.display = [[false; 64]; 32];
+    /// How much each frame can spend on instructions: cycles_per_frame instructions, or with
+    /// cycle_costs, that many typical instructions' worth of machine cycles
+    fn frame_budget(&self) -> u64 {
+        let cost = if self.cycle_costs {
+            TYPICAL_CYCLE_COST
+        } else {
+            1
+        };
+        self.cycles_per_frame as u64 * cost as u64
     }
 
-    /// goto NNN;
-    fn goto(&mut self, addr: u16) {
-        self.position_in_memory = addr as usize;
+    /// What running the instruction at the PC takes out of the frame's budget
+    fn next_cost(&self) -> u64 {
+        if self.cycle_costs {
+            cycle_cost(decode(self.opcode_at(self.position_in_memory))) as u64
+        } else {
+            1
+        }
     }
 
-    /// *(0xNNN)()
-    fn call(&mut self, addr: u16) {
-        let sp = self.stack_pointer;
-        let stack = &mut self.stack;
+    /// Draws, counts down the timers, and records the keys pressed, then waits for the next frame
+    fn end_frame(&mut self) {
+        if self.take_draw_flag() || self.persistence {
+            self.draw_graphics();
+        }
+        if self.timer_mode == TimerMode::Auto {
+            self.tick_timers();
+        }
+        self.set_keys();
+        self.wait_for_vblank();
+    }
+
+    /// Counts both timers down by one, stopping at zero
+    /// Called at 60Hz, by the emulator in TimerMode::Auto or by the host in TimerMode::Manual
+    pub fn tick_timers(&mut self) {
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.set_sound_timer(self.sound_timer.saturating_sub(1));
+    }
 
-        if sp > stack.len() {
-            panic!("Stack overflow!")
+    /// Sets the sound timer, telling the sound callback when the beep starts or stops
+    fn set_sound_timer(&mut self, value: u8) {
+        let was_playing = self.sound_timer > 0;
+        self.sound_timer = value;
+        if was_playing != (value > 0) {
+            if let Some(callback) = self.sound_callback.as_mut() {
+                callback(value > 0);
+            }
         }
+    }
 
-        stack[sp] = self.position_in_memory as u16;
-        self.stack_pointer += 1;
-        self.position_in_memory = addr as usize;
+    /// Scans memory from the start address for opcodes the emulator can't execute
+    /// Data mixed in with the program is read as opcodes too, so not everything listed will run
+    pub fn validate_rom(&self) -> Vec<(usize, u16)> {
+        let start = self.wrap_address(self.start_address);
+        (start..self.memory.len() - 1)
+            .step_by(2)
+            .map(|addr| {
+                (
+                    addr,
+                    (self.memory[addr] as u16) << 8 | self.memory[addr + 1] as u16,
+                )
+            })
+            .filter(|&(_, opcode)| match decode(opcode) {
+                Instruction::Unknown(_) => true,
+                Instruction::StoreRange(..)
+                | Instruction::LoadRange(..)
+                | Instruction::LoadAudioPattern
+                | Instruction::SetPitch(_) => !self.xo_chip,
+                _ => false,
+            })
+            .collect()
     }
 
-    /// return;
-    fn ret(&mut self) {
-        if self.stack_pointer == 0 {
-            panic!("Stack underflow!");
+    /// Picks whether the emulator counts the timers down itself or leaves it to tick_timers
+    pub fn set_timer_mode(&mut self, mode: TimerMode) {
+        self.timer_mode = mode;
+    }
+
+    /// Copies the registers, timers, keys, display, and memory, to compare or diff against later
+    pub fn snapshot(&self) -> State {
+        State {
+            registers: self.registers,
+            i: self.i,
+            pc: self.position_in_memory,
+            stack: self.stack,
+            stack_pointer: self.stack_pointer,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            keys: self.keys,
+            display: self.display.clone(),
+            memory: self.memory.clone(),
         }
+    }
 
-        self.stack_pointer -= 1;
-        self.position_in_memory = self.stack[self.stack_pointer] as usize;
+    /// Writes the state to a file that load_state can restore
+    pub fn save_state(&self, path: &str) -> std::io::Result<()> {
+        std::fs::write(path, save_state::encode(&self.snapshot()))
     }
 
-    /// if(Vx==NN)
-    fn skip_if_equal(&mut self, x: u8, nn: u8) {
-        if self.registers[x as usize] == nn {
-            self.position_in_memory += 2;
+    /// Restores a state written by save_state, by this machine or one with the same memory and
+    /// display sizes
+    pub fn load_state(&mut self, path: &str) -> Result<(), String> {
+        let bytes = std::fs::read(path).map_err(|err| err.to_string())?;
+        self.restore(save_state::decode(&bytes)?)
+    }
+
+    /// Puts the machine back in a state taken by snapshot
+    pub fn restore(&mut self, state: State) -> Result<(), String> {
+        if state.memory.len() != self.memory.len() {
+            let sizes = (state.memory.len(), self.memory.len());
+            return Err(format!(
+                "the state has {} bytes of memory instead of {}",
+                sizes.0, sizes.1
+            ));
+        }
+        let (width, height) = (state.display.width(), state.display.height());
+        if (width, height) != (self.display.width(), self.display.height()) {
+            let ours = (self.display.width(), self.display.height());
+            return Err(format!(
+                "the state's display is {}x{} instead of {}x{}",
+                width, height, ours.0, ours.1
+            ));
         }
+        self.registers = state.registers;
+        self.i = state.i;
+        self.position_in_memory = state.pc;
+        self.stack = state.stack;
+        self.stack_pointer = state.stack_pointer;
+        self.delay_timer = state.delay_timer;
+        self.set_sound_timer(state.sound_timer);
+        self.keys = state.keys;
+        self.display = state.display;
+        self.memory = state.memory;
+        self.history.clear();
+        self.draw_flag = true;
+        Ok(())
     }
 
-    /// if(Vx!=NN)
-    fn skip_if_not_equal(&mut self, x: u8, nn: u8) {
-        if self.registers[x as usize] != nn {
-            self.position_in_memory += 2;
+    /// Saves to the newest autosave file, first moving the older ones down a slot so a bad write
+    /// never loses more than the one save
+    fn autosave(&mut self) {
+        let slot = |n: usize| format!("{}.{}.state", self.autosave_path, n);
+        for n in (1..AUTOSAVE_SLOTS).rev() {
+            // The older files won't be there yet for the first few saves
+            let _ = std::fs::rename(slot(n - 1), slot(n));
+        }
+        if let Err(err) = self.save_state(&slot(0)) {
+            error!("Error autosaving to {}: {}", slot(0), err);
         }
     }
 
-    /// if(Vx==Vy)
-    fn skip_xy_equal(&mut self, x: u8, y: u8) {
-        if self.registers[x as usize] == self.registers[y as usize] {
-            self.position_in_memory += 2;
+    /// Seeds the random number generator used by Cxnn, so runs can be repeated
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// Calls the hook with the PC and opcode before each instruction executes
+    /// For building tracers, coverage tools, and profilers on top of the emulator
+    pub fn set_instruction_hook(&mut self, hook: Box<dyn FnMut(usize, u16)>) {
+        self.instruction_hook = Some(hook);
+    }
+
+    /// Calls the callback with true when the beep should start and false when it should stop
+    /// For hosts that play the sound themselves instead of pulling samples from fill_audio
+    pub fn set_sound_callback(&mut self, callback: Box<dyn FnMut(bool)>) {
+        self.sound_callback = Some(callback);
+    }
+
+    /// Calls the callback with the display each time a new frame is drawn
+    /// For recording, streaming, or rendering frames without a frontend
+    pub fn set_frame_callback(&mut self, callback: FrameCallback) {
+        self.frame_callback = Some(callback);
+    }
+
+    /// Executes a single instruction, for hosts that drive the emulator from their own loop
+    pub fn step(&mut self) -> Result<StepResult, Error> {
+        self.emulate_cycle()
+    }
+
+    /// The value of register Vi
+    pub fn register(&self, i: usize) -> u8 {
+        self.registers[i]
+    }
+
+    pub fn set_register(&mut self, i: usize, value: u8) {
+        self.registers[i] = value;
+    }
+
+    /// The address register I
+    pub fn index(&self) -> u16 {
+        self.i
+    }
+
+    pub fn set_index(&mut self, value: u16) {
+        self.i = value;
+    }
+
+    /// The address of the next instruction
+    pub fn pc(&self) -> usize {
+        self.position_in_memory
+    }
+
+    pub fn set_pc(&mut self, pc: usize) {
+        self.position_in_memory = pc;
+    }
+
+    pub fn delay(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn set_delay(&mut self, value: u8) {
+        self.delay_timer = value;
+    }
+
+    pub fn sound(&self) -> u8 {
+        self.sound_timer
+    }
+
+    pub fn set_sound(&mut self, value: u8) {
+        self.set_sound_timer(value);
+    }
+
+    /// The current state of the display, for hosts that do their own rendering
+    pub fn framebuffer(&self) -> &Grid<bool> {
+        &self.display
+    }
+
+    /// The display flattened row by row
+    pub fn framebuffer_flat(&self) -> Vec<bool> {
+        self.display.cells().to_vec()
+    }
+
+    /// The (row, col, new value) of every pixel that changed since the last call, or since the
+    /// display was blank for the first, so hosts can send or record only what changed
+    pub fn frame_diff(&mut self) -> Vec<(usize, usize, bool)> {
+        let changed = self.diffed_display.changed(&self.display);
+        self.diffed_display = self.display.clone();
+        changed
+            .into_iter()
+            .map(|(row, col)| (row, col, self.display[row][col]))
+            .collect()
+    }
+
+    /// Fills the buffer with the beep while the sound timer is running and silence otherwise
+    /// Audio hosts call this from their output callback
+    pub fn fill_audio(&mut self, buffer: &mut [f32], sample_rate: u32) {
+        if self.sound_timer > 0 && self.xo_chip {
+            self.audio.fill_pattern(buffer, sample_rate);
+        } else if self.sound_timer > 0 {
+            self.audio.fill(buffer, sample_rate);
+        } else {
+            buffer.iter_mut().for_each(|sample| *sample = 0.0);
         }
     }
 
-    /// Vx = NN
-    fn set_xnn(&mut self, x: u8, nn: u8) {
-        self.registers[x as usize] = nn;
+    /// Whether anything was drawn since the last call, so hosts know when to repaint
+    pub fn take_draw_flag(&mut self) -> bool {
+        std::mem::replace(&mut self.draw_flag, false)
     }
 
-    /// Vx += NN
-    fn add_xnn(&mut self, x: u8, nn: u8) {
-        self.registers[x as usize] = (self.registers[x as usize] as u16 + nn as u16) as u8;
+    /// Presses or releases a key, for hosts that handle their own input
+    /// Each press is read once by Fx0A, holding the key down doesn't satisfy the next one
+    pub fn set_key(&mut self, idx: usize, pressed: bool) -> Result<(), Error> {
+        match self.keys.get_mut(idx) {
+            Some(key) => {
+                if pressed && !*key {
+                    self.key_presses.push_back(idx);
+                }
+                *key = pressed;
+                Ok(())
+            }
+            None => Err(Error::InvalidKey { key: idx }),
+        }
     }
 
-    /// Vx=Vy
-    fn assign_xy(&mut self, x: u8, y: u8) {
-        self.registers[x as usize] = self.registers[y as usize];
+    /// Releases a key pressed with set_key
+    pub fn release_key(&mut self, idx: usize) -> Result<(), Error> {
+        self.set_key(idx, false)
     }
 
-    /// Vx=Vx|Vy
-    fn or_xy(&mut self, x: u8, y: u8) {
-        self.registers[x as usize] |= self.registers[y as usize];
+    /// Which of the 16 keys are pressed, for drawing a keypad
+    pub fn pressed_keys(&self) -> [bool; 16] {
+        self.keys
     }
 
-    /// Vx=Vx&Vy
-    fn and_xy(&mut self, x: u8, y: u8) {
-        self.registers[x as usize] &= self.registers[y as usize];
+    /// Switches to debug mode when the next instruction is at a breakpoint
+    /// returns true when a breakpoint was hit
+    fn hit_breakpoint(&mut self) -> bool {
+        if self.debug
+            || !self.stop_at_breakpoints
+            || !self.breakpoints.contains(&self.position_in_memory)
+        {
+            return false;
+        }
+        info!("Breakpoint at {:03x}", self.position_in_memory);
+        self.debug = true;
+        true
     }
 
-    /// Vx=Vx^Vy
-    fn xor_xy(&mut self, x: u8, y: u8) {
-        self.registers[x as usize] ^= self.registers[y as usize];
+    /// Loop until a valid key is pressed
+    /// returns false when the user quits or closes the window
+    fn wait_on_debug_input(&mut self) -> bool {
+        loop {
+            if !self.frontend.is_open() {
+                return false;
+            }
+            match self.frontend.poll_debug_command() {
+                Some(DebugCommand::Step) => return true,
+                Some(DebugCommand::Quit) => return false,
+                Some(DebugCommand::Resume) => {
+                    self.debug = false;
+                    self.stop_at_breakpoints = false;
+                    return true;
+                }
+                Some(DebugCommand::Continue) => {
+                    self.debug = false;
+                    return true;
+                }
+                Some(DebugCommand::Rewind) => {
+                    self.rewind();
+                    self.draw_graphics();
+                }
+                Some(DebugCommand::DumpMemory) => {
+                    let center = self.dump_addr.unwrap_or(self.i as usize);
+                    let start = center.saturating_sub(DUMP_LEN / 2);
+                    let start = start.min(self.memory.len() - DUMP_LEN);
+                    print!("{}", self.hex_dump(start, DUMP_LEN));
+                }
+                Some(DebugCommand::ShowSprite) => {
+                    // As many rows as the next instruction draws, or the most a sprite can have
+                    let n = match decode(self.opcode_at(self.position_in_memory)) {
+                        Instruction::Draw(_, _, n) => n,
+                        _ => 15,
+                    };
+                    println!("Sprite at {:03x}:", self.i);
+                    print!("{}", sprite_text(&self.read_sprite(n)));
+                }
+                Some(DebugCommand::SaveMemory) => {
+                    let saved = self
+                        .dump_memory_to_file(MEMORY_DUMP_FILE)
+                        .and_then(|_| self.dump_hex_to_file(HEX_DUMP_FILE));
+                    match saved {
+                        Ok(()) => {
+                            println!("Memory saved to {} and {}", MEMORY_DUMP_FILE, HEX_DUMP_FILE)
+                        }
+                        Err(err) => eprintln!("Error saving memory: {}", err),
+                    }
+                }
+                Some(DebugCommand::RunUntilDraw) => match self.run_until_draw() {
+                    Ok(true) => self.draw_graphics(),
+                    Ok(false) => return false,
+                    Err(err) => eprintln!("Error: {}", err),
+                },
+                None => {}
+            }
+        }
     }
 
-    /// Vx += Vy
-    fn add_xy(&mut self, x: u8, y: u8) {
-        let vx = self.registers[x as usize] as u16;
-        let vy: u16 = self.registers[y as usize] as u16;
-        let result = vx + vy;
-        // Set the carry
-        self.memory[VF] = if result > 0xFF { 1 } else { 0 };
-        self.registers[x as usize] = result as u8;
+    /// Formats len bytes of memory from addr as lines of 16 hex bytes followed by their ASCII characters
+    pub fn hex_dump(&self, addr: usize, len: usize) -> String {
+        let end = (addr + len).min(self.memory.len());
+        hex_dump(&self.memory[addr.min(end)..end], addr)
     }
 
-    /// Vx -= Vy
-    fn sub_xy(&mut self, x: u8, y: u8) {
-        self.memory[VF] = if self.memory[x as usize] > self.memory[y as usize] { 1 } else { 0 };
-        self.registers[x as usize] -= self.registers[y as usize];
+    /// The two bytes at addr as an opcode, wrapping around the end of memory like the PC
+    fn opcode_at(&self, addr: usize) -> u16 {
+        let addr = self.wrap_address(addr);
+        (self.memory[addr] as u16) << 8 | self.memory[self.wrap_address(addr + 1)] as u16
+    }
+
+    /// The n bytes at I that Dxyn would draw as a sprite, fewer if they run past the end of memory
+    pub fn read_sprite(&self, n: u8) -> Vec<u8> {
+        let start = (self.i as usize).min(self.memory.len());
+        let end = (start + n as usize).min(self.memory.len());
+        self.memory[start..end].to_vec()
+    }
+
+    /// Writes all of memory to a file as it is
+    pub fn dump_memory_to_file(&self, path: &str) -> std::io::Result<()> {
+        std::fs::write(path, &self.memory[..])
     }
 
-    /// Vx>>=1
-    fn shift_right(&mut self, x: u8) {
-        self.memory[VF] = self.memory[x as usize] & 1;
-        self.registers[x as usize] >>= 1;
+    /// Writes all of memory to a file as a hex dump
+    pub fn dump_hex_to_file(&self, path: &str) -> std::io::Result<()> {
+        std::fs::write(path, self.hex_dump(0, self.memory.len()))
     }
 
-    /// Vx=Vy-Vx
-    fn sub_yx(&mut self, x: u8, y: u8) {
-        // Set the carry
-        self.memory[VF] = if self.registers[y as usize] > self.registers[x as usize] { 1 } else { 0 };
-        self.registers[x as usize] = self.registers[y as usize].wrapping_sub(self.registers[x as usize]);
-    }
+    /// Executes instructions until one draws, checking for the quit key every frame's worth of instructions
+    /// returns false when the program halts, the user quits, or the window is closed
+    fn run_until_draw(&mut self) -> Result<bool, Error> {
+        let mut cycles = 0;
+        loop {
+            if let StepResult::Halted(_) = self.step_with_history()? {
+                return Ok(false);
+            }
+            if self.take_draw_flag() {
+                return Ok(true);
+            }
+            cycles += 1;
+            if cycles % self.cycles_per_frame.max(1) == 0
+                && (!self.frontend.is_open()
+                    || self.frontend.poll_debug_command() == Some(DebugCommand::Quit))
+            {
+                return Ok(false);
+            }
+        }
+    }
+
+    /// Executes a single instruction, keeping enough state to rewind it
+    fn step_with_history(&mut self) -> Result<StepResult, Error> {
+        let display = self.display.clone();
+        let mut state = RewindState {
+            registers: self.registers,
+            i: self.i,
+            position_in_memory: self.position_in_memory,
+            stack: self.stack,
+            stack_pointer: self.stack_pointer,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            display_diff: Vec::new(),
+            memory_diff: Vec::new(),
+        };
+        self.memory_log = Some(Vec::new());
+        let result = self.emulate_cycle();
+        state.memory_diff = self.memory_log.take().unwrap_or_default();
+        state.display_diff = display.changed(&self.display);
+
+        self.history.push_back(state);
+        while self.history.len() > self.rewind_depth {
+            self.history.pop_front();
+        }
+        result
+    }
+
+    /// Undoes the last instruction executed with step_with_history
+    /// returns false when there is nothing left to rewind
+    fn rewind(&mut self) -> bool {
+        let state = match self.history.pop_back() {
+            Some(state) => state,
+            None => return false,
+        };
+        self.registers = state.registers;
+        self.i = state.i;
+        self.position_in_memory = state.position_in_memory;
+        self.stack = state.stack;
+        self.stack_pointer = state.stack_pointer;
+        self.delay_timer = state.delay_timer;
+        self.set_sound_timer(state.sound_timer);
+        for (row, col) in state.display_diff {
+            self.display[row][col] = !self.display[row][col];
+        }
+        for (addr, value) in state.memory_diff.into_iter().rev() {
+            self.memory[addr] = value;
+        }
+        true
+    }
+
+    /// Loads an operation from memory and executes the operation
+    /// returns Halted when it loads a 0x0000 or exit operation, saying whether the 0x0000 was part of the program
+    /// Like the COSMAC VIP's 12-bit addressing, the PC wraps around to 0x000 after the end of memory
+    fn emulate_cycle(&mut self) -> Result<StepResult, Error> {
+        if self.halt_on_pc_wrap && self.position_in_memory + 1 >= self.memory.len() {
+            return Err(Error::PcOutOfRange {
+                pc: self.position_in_memory,
+            });
+        }
+        self.cycle_count += 1;
+        let pc = self.wrap_address(self.position_in_memory);
+        let opcode = self.opcode_at(pc);
+
+        if let Some(hook) = self.instruction_hook.as_mut() {
+            hook(pc, opcode);
+        }
+        if self.trace {
+            eprintln!("{:03x}: {:04x}  {}", pc, opcode, disassemble(opcode));
+        }
+        trace!("{:03x}: {:04x}  {}", pc, opcode, disassemble(opcode));
+        if self.profile || self.coverage {
+            *self.opcode_counts.entry(opcode_class(opcode)).or_insert(0) += 1;
+        }
+
+        // Left unwrapped with halt_on_pc_wrap, so running off the end errors on the next fetch
+        self.position_in_memory = if self.halt_on_pc_wrap {
+            pc + 2
+        } else {
+            self.wrap_address(pc + 2)
+        };
+        self.execute(pc, opcode)
+    }
+
+    /// Runs an opcode as if it had been loaded from the PC, without reading it from memory
+    /// The PC moves past it first, just like running it from memory, so skips and jumps work the same
+    pub fn execute_opcode(&mut self, opcode: u16) -> Result<StepResult, Error> {
+        let pc = self.wrap_address(self.position_in_memory);
+        self.position_in_memory = self.wrap_address(pc + 2);
+        self.execute(pc, opcode)
+    }
+
+    /// Decodes and runs an opcode loaded from pc, after the PC has moved past it
+    fn execute(&mut self, pc: usize, opcode: u16) -> Result<StepResult, Error> {
+        use Instruction::*;
+        let instruction = decode(opcode);
+        if self.strict {
+            self.check_strict(pc, opcode, instruction);
+        }
+        match instruction {
+            Halt if (self.wrap_address(self.start_address)..self.program_end).contains(&pc) => {
+                return Ok(StepResult::Halted(HaltReason::HaltOpcode))
+            }
+            Halt => return Ok(StepResult::Halted(HaltReason::EndOfProgram)),
+            ClearScreen => self.clear_screen(),
+            Return => self.ret()?,
+            // Calls a machine code routine on the COSMAC VIP, which can't be emulated, so it's skipped like modern interpreters do
+            Sys(_) => {}
+            Jump(nnn) if self.detect_idle && nnn as usize == pc => {
+                return Ok(StepResult::Halted(HaltReason::IdleLoop))
+            }
+            Jump(nnn) => self.goto(nnn)?,
+            Call(nnn) => self.call(nnn)?,
+            SkipIfEqual(x, nn) => self.skip_if_equal(x, nn),
+            SkipIfNotEqual(x, nn) => self.skip_if_not_equal(x, nn),
+            SkipXyEqual(x, y) => self.skip_xy_equal(x, y),
+            StoreRange(x, y) if self.xo_chip => self.store_range(x, y)?,
+            LoadRange(x, y) if self.xo_chip => self.load_range(x, y)?,
+            SetXnn(x, nn) => self.set_xnn(x, nn),
+            AddXnn(x, nn) => self.add_xnn(x, nn),
+            AssignXy(x, y) => self.assign_xy(x, y),
+            OrXy(x, y) => self.or_xy(x, y),
+            AndXy(x, y) => self.and_xy(x, y),
+            XorXy(x, y) => self.xor_xy(x, y),
+            AddXy(x, y) => self.add_xy(x, y),
+            SubXy(x, y) => self.sub_xy(x, y),
+            ShiftRight(x, y) => self.shift_right(x, y),
+            SubYx(x, y) => self.sub_yx(x, y),
+            ShiftLeft(x, y) => self.shift_left(x, y),
+            SkipXyNotEqual(x, y) => self.skip_xy_not_equal(x, y),
+            SetI(nnn) => self.set_16bit_register(nnn),
+            JumpPlusV0(nnn) => self.jump_nnn_plus_v0(nnn)?,
+            Rand(x, nn) => self.rand(x, nn),
+            Draw(x, y, n) => self.draw(x, y, n)?,
+            SkipIfKeyPressed(x) => self.skip_if_key_pressed(x),
+            SkipIfKeyNotPressed(x) => self.skip_if_key_not_pressed(x),
+            LoadAudioPattern if self.xo_chip => self.load_audio_pattern()?,
+            SetXToDelayTimer(x) => self.set_x_to_delay_timer(x),
+            SetXToKeypress(x) => self.set_x_to_keypress(x),
+            SetDelayTimer(x) => self.set_delay_timer_to_x(x),
+            SetSoundTimer(x) => self.set_sound_timer_to_x(x),
+            AddIx(x) => self.add_ix(x),
+            SetISpriteAddr(x) => self.set_i_sprite_addr_x(x),
+            SetBcd(x) => self.set_bcd(x)?,
+            SetPitch(x) if self.xo_chip => self.set_pitch(x),
+            RegDump(x) => self.reg_dump(x)?,
+            RegLoad(x) => self.reg_load(x)?,
+            StoreRange(..) | LoadRange(..) | LoadAudioPattern | SetPitch(_) | Unknown(_) => {
+                return Err(Error::UnknownOpcode { pc, opcode })
+            }
+        }
+        Ok(StepResult::Continue)
+    }
+
+    /// Warns once per address about instructions that write VF, the flag register, as their result
+    /// Fx65 counts when it loads all the way up to VF
+    fn check_strict(&mut self, pc: usize, opcode: u16, instruction: Instruction) {
+        use Instruction::*;
+        let writes_vf = match instruction {
+            SetXnn(x, _)
+            | AddXnn(x, _)
+            | AssignXy(x, _)
+            | OrXy(x, _)
+            | AndXy(x, _)
+            | XorXy(x, _)
+            | AddXy(x, _)
+            | SubXy(x, _)
+            | ShiftRight(x, _)
+            | SubYx(x, _)
+            | ShiftLeft(x, _)
+            | Rand(x, _)
+            | SetXToDelayTimer(x)
+            | SetXToKeypress(x)
+            | RegLoad(x) => x == 0xF,
+            LoadRange(x, y) => x == 0xF || y == 0xF,
+            _ => false,
+        };
+        if !writes_vf {
+            return;
+        }
+        let mnemonic = disassemble(opcode);
+        let warning = format!(
+            "{:03x}: {:04x}  {} writes VF, the flag register",
+            pc, opcode, mnemonic
+        );
+        if !self.strict_warnings.contains(&warning) {
+            warn!("{}", warning);
+            self.strict_warnings.push(warning);
+        }
+    }
+
+    /// The warnings strict mode has printed so far
+    pub fn strict_warnings(&self) -> &[String] {
+        &self.strict_warnings
+    }
+
+    /// Formats the opcode counts collected while profiling, most executed first
+    pub fn profile_summary(&self) -> String {
+        let mut counts: Vec<(&&str, &u64)> = self.opcode_counts.iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        counts
+            .iter()
+            .map(|(class, count)| format!("{:>8}  {}\n", count, class))
+            .collect()
+    }
+
+    /// Every opcode class in opcode order with how many times it ran, - for ones that never did,
+    /// then how many of the classes were used
+    pub fn coverage_report(&self) -> String {
+        let mut report = String::new();
+        for class in OPCODE_CLASSES {
+            let count = self
+                .opcode_counts
+                .get(class)
+                .map_or("-".to_string(), u64::to_string);
+            report.push_str(&format!("{}  {:>8}\n", class, count));
+        }
+        let used = OPCODE_CLASSES
+            .iter()
+            .filter(|class| self.opcode_counts.contains_key(*class));
+        report.push_str(&format!(
+            "{} of {} opcodes used\n",
+            used.count(),
+            OPCODE_CLASSES.len()
+        ));
+        report
+    }
+
+    /// How many instructions have run since the program was loaded, counting the one that halted it
+    pub fn cycle_count(&self) -> u64 {
+        self.cycle_count
+    }
+
+    /// The address of the instruction currently being executed
+    fn instruction_address(&self) -> usize {
+        self.wrap_address(self.position_in_memory + self.memory.len() - 2)
+    }
+
+    /// Wraps an address around to the start of memory once it runs past the end
+    fn wrap_address(&self, addr: usize) -> usize {
+        addr % self.memory.len()
+    }
+
+    /// Update the window, skipping it when the frame is the same as the last one presented
+    /// With persistence on, pixels that were turned off fade to the background over a few frames
+    fn draw_graphics(&mut self) {
+        if self.persistence && (self.deterministic || self.last_fade.elapsed() >= FRAME_DURATION) {
+            self.fade_pixels();
+            self.last_fade = Instant::now();
+        }
+        let (width, height) = (self.display.width(), self.display.height());
+        let mut buf = std::mem::take(&mut self.frame_buffer);
+        self.render(&mut buf);
+        if buf != self.last_presented {
+            if self.overlay == Overlay::None {
+                self.frontend.present(&buf, width, height);
+            } else {
+                let (scaled, width, height) = self.overlay.apply(&buf, width, height);
+                self.frontend.present(&scaled, width, height);
+            }
+            // The old frame's buffer is filled in next time
+            std::mem::swap(&mut buf, &mut self.last_presented);
+            if let Some(callback) = self.frame_callback.as_mut() {
+                callback(&self.display);
+            }
+        }
+        self.frame_buffer = buf;
+    }
+
+    /// Fills buf with the display's pixels in the colors they're presented in
+    fn render(&self, buf: &mut Vec<u32>) {
+        let palette = self.palette.unwrap_or_else(|| self.color.palette());
+        let (mut off, mut on) = (palette.color(0), scale(palette.color(1), self.brightness));
+        if self.inverted {
+            std::mem::swap(&mut off, &mut on);
+        }
+        let width = self.display.width();
+        buf.resize(width * self.display.height(), off);
+        for ((row, pixels), out) in self.display.rows().enumerate().zip(buf.chunks_mut(width)) {
+            for (col, (&pixel, out)) in pixels.iter().zip(out).enumerate() {
+                *out = if pixel {
+                    on
+                } else if self.persistence {
+                    blend(on, off, self.intensity[row][col])
+                } else {
+                    off
+                };
+            }
+        }
+    }
+
+    /// The display as a PNG, drawn like the window with the colors and overlay, and each pixel
+    /// scaled up to screenshot_scale pixels across
+    pub fn screenshot(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.render(&mut buf);
+        let (mut width, mut height) = (self.display.width(), self.display.height());
+        if self.overlay != Overlay::None {
+            (buf, width, height) = self.overlay.apply(&buf, width, height);
+        }
+        let scale = self.screenshot_scale.max(1);
+        let mut scaled = Vec::with_capacity(buf.len() * scale * scale);
+        for row in buf.chunks(width) {
+            let scaled_row: Vec<u32> = row
+                .iter()
+                .flat_map(|&pixel| std::iter::repeat(pixel).take(scale))
+                .collect();
+            for _ in 0..scale {
+                scaled.extend_from_slice(&scaled_row);
+            }
+        }
+        png::encode(&scaled, width * scale, height * scale)
+    }
+
+    /// Advances the pixel intensities by a frame: lit pixels are at full intensity and unlit ones fade
+    fn fade_pixels(&mut self) {
+        for (row, pixels) in self.display.rows().enumerate() {
+            for (col, &pixel) in pixels.iter().enumerate() {
+                let intensity = &mut self.intensity[row][col];
+                *intensity = if pixel {
+                    u8::MAX
+                } else {
+                    intensity.saturating_sub(PERSISTENCE_FADE)
+                };
+            }
+        }
+    }
+
+    /// disp_clear()
+    fn clear_screen(&mut self) {
+        self.display.clear();
+    }
+
+    /// goto NNN;
+    fn goto(&mut self, addr: u16) -> Result<(), Error> {
+        self.position_in_memory = self.jump_target(addr as usize)?;
+        Ok(())
+    }
+
+    /// *(0xNNN)()
+    fn call(&mut self, addr: u16) -> Result<(), Error> {
+        let sp = self.stack_pointer;
+
+        if sp >= self.stack.len() {
+            return Err(Error::StackOverflow {
+                pc: self.instruction_address(),
+            });
+        }
+
+        let target = self.jump_target(addr as usize)?;
+        self.stack[sp] = self.position_in_memory as u16;
+        self.stack_pointer += 1;
+        self.position_in_memory = target;
+        Ok(())
+    }
+
+    /// return;
+    fn ret(&mut self) -> Result<(), Error> {
+        if self.stack_pointer == 0 {
+            return Err(Error::StackUnderflow {
+                pc: self.instruction_address(),
+            });
+        }
+
+        self.stack_pointer -= 1;
+        self.position_in_memory = self.stack[self.stack_pointer] as usize;
+        Ok(())
+    }
+
+    /// if(Vx==NN)
+    fn skip_if_equal(&mut self, x: u8, nn: u8) {
+        if self.registers[x as usize] == nn {
+            self.position_in_memory += 2;
+        }
+    }
+
+    /// if(Vx!=NN)
+    fn skip_if_not_equal(&mut self, x: u8, nn: u8) {
+        if self.registers[x as usize] != nn {
+            self.position_in_memory += 2;
+        }
+    }
+
+    /// if(Vx==Vy)
+    fn skip_xy_equal(&mut self, x: u8, y: u8) {
+        if self.registers[x as usize] == self.registers[y as usize] {
+            self.position_in_memory += 2;
+        }
+    }
+
+    /// Vx = NN
+    fn set_xnn(&mut self, x: u8, nn: u8) {
+        self.registers[x as usize] = nn;
+    }
+
+    /// Vx += NN
+    /// Wraps around past 0xFF and never touches VF, unlike 8xy4
+    fn add_xnn(&mut self, x: u8, nn: u8) {
+        self.registers[x as usize] = (self.registers[x as usize] as u16 + nn as u16) as u8;
+    }
+
+    /// Vx=Vy
+    fn assign_xy(&mut self, x: u8, y: u8) {
+        self.registers[x as usize] = self.registers[y as usize];
+    }
+
+    /// Vx=Vx|Vy
+    fn or_xy(&mut self, x: u8, y: u8) {
+        self.registers[x as usize] |= self.registers[y as usize];
+    }
+
+    /// Vx=Vx&Vy
+    fn and_xy(&mut self, x: u8, y: u8) {
+        self.registers[x as usize] &= self.registers[y as usize];
+    }
+
+    /// Vx=Vx^Vy
+    fn xor_xy(&mut self, x: u8, y: u8) {
+        self.registers[x as usize] ^= self.registers[y as usize];
+    }
+
+    /// Vx += Vy
+    fn add_xy(&mut self, x: u8, y: u8) {
+        let vx = self.registers[x as usize] as u16;
+        let vy: u16 = self.registers[y as usize] as u16;
+        let result = vx + vy;
+        // Set the carry
+        self.write_memory(VF, if result > 0xFF { 1 } else { 0 });
+        self.registers[x as usize] = result as u8;
+    }
+
+    /// Vx -= Vy
+    fn sub_xy(&mut self, x: u8, y: u8) {
+        self.write_memory(
+            VF,
+            if self.memory[x as usize] > self.memory[y as usize] {
+                1
+            } else {
+                0
+            },
+        );
+        self.registers[x as usize] -= self.registers[y as usize];
+    }
+
+    /// Vx>>=1, or Vx=Vy>>1 with the shift_uses_vy quirk
+    fn shift_right(&mut self, x: u8, y: u8) {
+        let source = if self.quirks.shift_uses_vy { y } else { x };
+        self.write_memory(VF, self.memory[source as usize] & 1);
+        self.registers[x as usize] = self.registers[source as usize] >> 1;
+    }
+
+    /// Vx=Vy-Vx
+    fn sub_yx(&mut self, x: u8, y: u8) {
+        // Set the carry
+        self.write_memory(
+            VF,
+            if self.registers[y as usize] > self.registers[x as usize] {
+                1
+            } else {
+                0
+            },
+        );
+        self.registers[x as usize] =
+            self.registers[y as usize].wrapping_sub(self.registers[x as usize]);
+    }
+
+    /// Vx<<=1, or Vx=Vy<<1 with the shift_uses_vy quirk
+    fn shift_left(&mut self, x: u8, y: u8) {
+        let source = if self.quirks.shift_uses_vy { y } else { x };
+        self.write_memory(VF, (self.memory[source as usize] & 0b10000000) >> 7);
+        self.registers[x as usize] = self.registers[source as usize] << 1;
+    }
+
+    /// if(Vx==Vy)
+    fn skip_xy_not_equal(&mut self, x: u8, y: u8) {
+        if self.registers[x as usize] != self.registers[y as usize] {
+            self.position_in_memory += 2;
+        }
+    }
+
+    /// I = NNN
+    fn set_16bit_register(&mut self, addr: u16) {
+        self.i = addr;
+    }
+
+    /// PC=V0+NNN
+    /// With the jump_uses_vx quirk, PC=Vx+xnn
+    fn jump_nnn_plus_v0(&mut self, addr: u16) -> Result<(), Error> {
+        let x = if self.quirks.jump_uses_vx {
+            (addr >> 8) as usize
+        } else {
+            0
+        };
+        self.position_in_memory = self.jump_target(self.registers[x] as usize + addr as usize)?;
+        Ok(())
+    }
+
+    /// Checks a jump or call target against the memory
+    /// With the usual 4096 bytes the PC wraps like the COSMAC VIP's 12-bit addressing, but a
+    /// smaller memory can't be wrapped into by a program that expects 4096 bytes, so it errors
+    fn jump_target(&self, addr: usize) -> Result<usize, Error> {
+        if addr >= self.memory.len() && self.memory.len() < MEMORY_SIZE {
+            return Err(Error::JumpOutOfRange {
+                pc: self.instruction_address(),
+                addr,
+            });
+        }
+        Ok(addr)
+    }
+
+    /// Vx=rand()&NN
+    fn rand(&mut self, x: u8, nn: u8) {
+        self.registers[x as usize] = self.rng.gen::<u8>() & nn;
+    }
+
+    /// draw(Vx,Vy,N)
+    fn draw(&mut self, x: u8, y: u8, n: u8) -> Result<(), Error> {
+        if n > 0 {
+            self.i_address(n as usize - 1)?;
+        }
+        // The starting position always wraps, only the rest of the sprite can be clipped
+        let (width, height) = (self.display.width(), self.display.height());
+        let vx = self.registers[x as usize] as usize % width;
+        let vy = self.registers[y as usize] as usize % height;
+        self.registers[VF] = 0;
+        for r in 0..n {
+            let row = self.memory[self.i as usize + r as usize];
+            let screen_y = vy + r as usize;
+            if screen_y >= height && self.quirks.clip_sprites {
+                break;
+            }
+            let screen_y = screen_y % height;
+            for col in 0..8 {
+                let val = (row & 0x80 >> col) > 0;
+                let screen_x = vx + col;
+                if screen_x >= width && self.quirks.clip_sprites {
+                    break;
+                }
+                let screen_x = screen_x % width;
+                // A collision is a lit pixel being turned off
+                if val && self.display[screen_y][screen_x] {
+                    self.registers[VF] = 1;
+                }
+                self.display[screen_y][screen_x] ^= val;
+            }
+        }
+        self.draw_flag = true;
+        // Ends the frame early, so at most one sprite is drawn each frame
+        self.vblank = self.quirks.draw_wait_vblank;
+        Ok(())
+    }
+
+    /// Blocks until the display has focus, checking once a frame
+    fn wait_for_focus(&mut self) {
+        while self.frontend.is_open() && !self.frontend.has_focus() {
+            self.wait_for_vblank();
+        }
+    }
+
+    /// Blocks until the next 60Hz frame boundary
+    /// In deterministic mode frames run back to back without waiting
+    fn wait_for_vblank(&mut self) {
+        if self.deterministic {
+            return;
+        }
+        let next_frame = self.last_frame + FRAME_DURATION;
+        let now = Instant::now();
+        if next_frame > now {
+            std::thread::sleep(next_frame - now);
+            self.last_frame = next_frame;
+        } else {
+            self.last_frame = now;
+        }
+    }
+
+    /// if(key()==Vx)
+    fn skip_if_key_pressed(&mut self, x: u8) {
+        if self.keys[self.registers[x as usize] as usize] {
+            self.position_in_memory += 2;
+        }
+    }
+
+    /// if(key()!=Vx)
+    fn skip_if_key_not_pressed(&mut self, x: u8) {
+        if !self.keys[self.registers[x as usize] as usize] {
+            self.position_in_memory += 2;
+        }
+    }
+
+    /// Vx = get_delay()
+    fn set_x_to_delay_timer(&mut self, x: u8) {
+        self.registers[x as usize] = self.delay_timer;
+    }
+
+    /// Vx = get_key()
+    /// Waits for a new key press, or with key_repeat for any key held down
+    /// With non_blocking_keys, the PC is moved back so Fx0A runs again until a key is pressed
+    fn set_x_to_keypress(&mut self, x: u8) {
+        let key = if self.non_blocking_keys {
+            self.poll_keypress()
+        } else if self.record_input.is_some() || self.input_replay.is_some() {
+            // Waiting a cycle at a time keeps the cycles the presses were read on the same when
+            // they're replayed
+            self.poll_keypress()
+        } else {
+            self.wait_for_keypress()
+        };
+        match key {
+            Some(key) => {
+                self.keys[key] = true;
+                self.registers[x as usize] = key as u8;
+            }
+            None => self.position_in_memory = self.instruction_address(),
+        }
+    }
+
+    /// The first key pressed with set_key or through the frontend since the last poll, or held
+    /// down with key_repeat
+    /// While replaying, the presses come from the replayed events, and while recording each press
+    /// read is added to the recorded input
+    fn poll_keypress(&mut self) -> Option<usize> {
+        if let Some(replay) = self.input_replay.as_mut() {
+            return replay.read_at(self.cycle_count);
+        }
+        let key = self.key_presses.pop_front().or_else(|| {
+            let keys = if self.key_repeat {
+                self.frontend.held_keys()
+            } else {
+                self.frontend.poll_keys()
+            };
+            keys.iter().position(|&pressed| pressed)
+        });
+        if let Some(key) = key.filter(|_| self.record_input.is_some()) {
+            self.input_recorder.record_read(self.cycle_count, key);
+        }
+        key
+    }
+
+    /// Blocking operation that waits on a VALID key press and returns it
+    /// Only the first key pressed is read. i.e. if '1' and '2' are both pressed, only '1' is recorded
+    /// The timers keep counting down at 60Hz while waiting, as they do on real hardware, so a beep
+    /// started just before plays for as long as it would have and stops on time
+    /// Returns None if the window is closed while waiting
+    fn wait_for_keypress(&mut self) -> Option<usize> {
+        loop {
+            if !self.frontend.is_open() {
+                return None;
+            }
+            if let Some(key) = self.poll_keypress() {
+                return Some(key);
+            }
+            if self.take_draw_flag() {
+                self.draw_graphics();
+            }
+            // Tick at the end of the frame, not partway through the one Fx0A started in
+            self.wait_for_vblank();
+            if self.timer_mode == TimerMode::Auto {
+                self.tick_timers();
+            }
+        }
+    }
+
+    /// Records which keys are held down right now, releasing the rest, for Ex9E and ExA1
+    /// While replaying, the keys come from the replayed events instead of the frontend
+    fn set_keys(&mut self) {
+        self.keys = match self.input_replay.as_mut() {
+            Some(replay) => replay.keys_at(self.cycle_count),
+            None => self.frontend.held_keys(),
+        };
+        if self.record_input.is_some() {
+            self.input_recorder.record(self.cycle_count, self.keys);
+        }
+    }
+
+    /// The keys pressed and released so far with record_input set
+    pub fn recorded_input(&self) -> &[InputEvent] {
+        self.input_recorder.events()
+    }
+
+    /// Reads keys from the events instead of the frontend, from recorded_input or a record_input file
+    /// Replaying with the same seed and deterministic set repeats the recorded run exactly
+    pub fn replay_input(&mut self, events: Vec<InputEvent>) {
+        self.input_replay = Some(InputReplay::new(events));
+    }
+
+    /// delay_timer(Vx)
+    fn set_delay_timer_to_x(&mut self, x: u8) {
+        self.delay_timer = self.registers[x as usize];
+    }
+
+    /// sound_timer(Vx)
+    fn set_sound_timer_to_x(&mut self, x: u8) {
+        self.set_sound_timer(self.registers[x as usize]);
+    }
+
+    /// I +=Vx
+    /// I stays within the 12-bit address space
+    fn add_ix(&mut self, x: u8) {
+        let result = self.i as usize + self.registers[x as usize] as usize;
+        if self.quirks.i_add_overflow_flag {
+            self.registers[VF] = if result > ADDRESS_MASK { 1 } else { 0 };
+        }
+        self.i = (result & ADDRESS_MASK) as u16;
+    }
+
+    /// I=sprite_addr[Vx]
+    fn set_i_sprite_addr_x(&mut self, x: u8) {
+        self.i = 0x50 + 5 * (self.registers[x as usize] as u16);
+    }
+
+    /// set_BCD(Vx);
+    /// *(I+0)=BCD(3);
+    /// *(I+1)=BCD(2);
+    /// *(I+2)=BCD(1);
+    fn set_bcd(&mut self, x: u8) -> Result<(), Error> {
+        self.i_address(2)?;
+        let vx = self.registers[x as usize];
+        self.write_memory(self.i as usize, vx / 100);
+        self.write_memory(self.i as usize + 1, (vx / 10) % 10);
+        self.write_memory(self.i as usize + 2, (vx % 100) % 10);
+        Ok(())
+    }
+
+    /// audio(&I), XO-CHIP only
+    fn load_audio_pattern(&mut self) -> Result<(), Error> {
+        let end = self.i_address(15)?;
+        self.audio
+            .pattern
+            .copy_from_slice(&self.memory[self.i as usize..=end]);
+        Ok(())
+    }
+
+    /// pitch(Vx), XO-CHIP only
+    fn set_pitch(&mut self, x: u8) {
+        self.audio.pitch = self.registers[x as usize];
+    }
+
+    /// reg_dump(Vx,&I)
+    fn reg_dump(&mut self, x: u8) -> Result<(), Error> {
+        self.i_address(x as usize)?;
+        for offset in 0..=x as usize {
+            self.write_memory(self.i as usize + offset, self.registers[offset]);
+        }
+        if self.quirks.load_store_increment_i {
+            self.i += x as u16 + 1;
+        }
+        Ok(())
+    }
+
+    /// reg_load(Vx,&I)
+    fn reg_load(&mut self, x: u8) -> Result<(), Error> {
+        let end = self.i_address(x as usize)?;
+        self.registers[0..x as usize + 1].copy_from_slice(&self.memory[(self.i as usize)..=end]);
+        if self.quirks.load_store_increment_i {
+            self.i += x as u16 + 1;
+        }
+        Ok(())
+    }
+
+    /// save(Vx..Vy,&I), XO-CHIP only
+    /// Stores from Vx to Vy, counting down when x > y, and leaves I where it is
+    fn store_range(&mut self, x: u8, y: u8) -> Result<(), Error> {
+        let registers = register_range(x, y);
+        self.i_address(registers.len() - 1)?;
+        for (offset, reg) in registers.into_iter().enumerate() {
+            self.write_memory(self.i as usize + offset, self.registers[reg]);
+        }
+        Ok(())
+    }
+
+    /// load(Vx..Vy,&I), XO-CHIP only
+    /// Loads from Vx to Vy, counting down when x > y, and leaves I where it is
+    fn load_range(&mut self, x: u8, y: u8) -> Result<(), Error> {
+        let registers = register_range(x, y);
+        self.i_address(registers.len() - 1)?;
+        for (offset, reg) in registers.into_iter().enumerate() {
+            self.registers[reg] = self.memory[self.i as usize + offset];
+        }
+        Ok(())
+    }
+
+    /// The address offset bytes past I
+    /// Errors instead of reading or writing past the end of memory
+    fn i_address(&self, offset: usize) -> Result<usize, Error> {
+        let addr = self.i as usize + offset;
+        if addr >= self.memory.len() {
+            return Err(Error::AddressOutOfRange {
+                pc: self.instruction_address(),
+                addr,
+            });
+        }
+        Ok(addr)
+    }
+
+    /// Writes a byte to memory, dropping into debug mode if it changes the watched address
+    fn write_memory(&mut self, addr: usize, value: u8) {
+        let old = self.memory[addr];
+        if self.watch_addr == Some(addr) && old != value {
+            info!("Watchpoint {:03x}: {:02x} -> {:02x}", addr, old, value);
+            self.debug = true;
+        }
+        if let Some(log) = &mut self.memory_log {
+            log.push((addr, old));
+        }
+        self.memory[addr] = value;
+    }
+
+    /// Loads the specified chip8 program into memory
+    /// With the zip feature, the first file in a .zip archive is loaded
+    /// Returns whether the program was loaded, logging the error if it wasn't
+    pub fn load_into_memory(&mut self, file: &str) -> bool {
+        let mut buffer = Vec::new();
+        match File::open(file) {
+            Ok(mut handle) => match handle.read_to_end(&mut buffer) {
+                #[cfg(feature = "zip")]
+                Ok(_) if buffer.starts_with(ZIP_MAGIC) => match unzip_first(&buffer) {
+                    Ok(rom) => return self.load_bytes_or_report(&rom),
+                    Err(err) => error!("Error unzipping {}: {}", file, err),
+                },
+                Ok(_) => return self.load_bytes_or_report(&buffer),
+                Err(err) => error!("Error reading {}: {}", file, err),
+            },
+            Err(err) => error!("Error opening {}: {}", file, err),
+        }
+        false
+    }
+
+    /// Loads a chip8 program into memory at the start address along with the fonts, and points the PC at it
+    /// Errors without loading anything if the program doesn't fit between the start address and the end of memory
+    pub fn load_bytes(&mut self, rom: &[u8]) -> Result<(), Error> {
+        let start = self.wrap_address(self.start_address);
+        let space = self.memory.len() - start;
+        if rom.len() > space {
+            return Err(Error::ProgramTooLarge {
+                size: rom.len(),
+                space,
+            });
+        }
+        self.load_fonts();
+        self.position_in_memory = start;
+        self.program_end = start + rom.len();
+        self.cycle_count = 0;
+        self.input_recorder = InputRecorder::default();
+        let program = &mut self.memory[start..];
+        program[..rom.len()].copy_from_slice(rom);
+        program[rom.len()..].iter_mut().for_each(|byte| *byte = 0);
+        Ok(())
+    }
+
+    fn load_bytes_or_report(&mut self, rom: &[u8]) -> bool {
+        match self.load_bytes(rom) {
+            Ok(()) => true,
+            Err(err) => {
+                error!("Error loading program: {}", err);
+                false
+            }
+        }
+    }
+
+    /// A summary of the loaded program and the settings it runs with, printed with verbose
+    pub fn banner(&self, path: &str) -> String {
+        let start = self.wrap_address(self.start_address);
+        let quirks: Vec<&str> = self
+            .quirks
+            .list()
+            .into_iter()
+            .filter(|(_, on, _)| *on)
+            .map(|(flag, _, _)| flag)
+            .collect();
+        let color = match self.palette {
+            Some(palette) => {
+                let colors: Vec<String> = palette
+                    .colors
+                    .iter()
+                    .map(|color| format!("{:#08x}", color))
+                    .collect();
+                colors.join(",")
+            }
+            None => self.color.to_string(),
+        };
+        let mut banner = format!(
+            "ROM:         {}\n\
+             Size:        {} bytes\n\
+             Entry point: {:03x}\n\
+             Quirks:      {}\n\
+             Color:       {}\n\
+             Speed:       {} IPS\n",
+            path,
+            self.program_end - start,
+            self.position_in_memory,
+            if quirks.is_empty() {
+                "none".to_string()
+            } else {
+                quirks.join(" ")
+            },
+            color,
+            self.cycles_per_frame * 60,
+        );
+        if let Some(info) = identify_rom(&self.memory[start..self.program_end]) {
+            banner.push_str(&format!(
+                "Game:        {}, made for {}\n",
+                info.name, info.platform
+            ));
+        }
+        banner
+    }
+
+    /// Replaces the built in font with an 80 byte font, 5 bytes for each of the digits 0 to F
+    /// The font is kept when a new program is loaded
+    pub fn load_custom_fonts(&mut self, font: &[u8; 80]) {
+        self.fonts = *font;
+        self.load_fonts();
+    }
+
+    fn load_fonts(&mut self) {
+        // 0x50 is the font offset
+        // http://www.multigesture.net/articles/how-to-write-an-emulator-chip-8-interpreter/
+        self.memory[0x50..0xA0].copy_from_slice(&self.fonts);
+    }
+
+    /// Loads a specified Chip8 program into memory and then runs
+    pub fn load_and_run(&mut self, file: &str) -> Result<(), Error> {
+        if self.load_into_memory(file) && self.verbose {
+            print!("{}", self.banner(file));
+        }
+        self.run()
+    }
+
+    /// Loads the built in demo and then runs it
+    pub fn run_demo(&mut self) -> Result<(), Error> {
+        if self.load_bytes_or_report(DEMO_ROM) && self.verbose {
+            print!("{}", self.banner("demo"));
+        }
+        self.run()
+    }
+}
+
+/// Counts the instructions and frames run, to show how fast the emulator is going
+struct Stats {
+    since: Instant,
+    instructions: u64,
+    frames: u64,
+}
+
+impl Stats {
+    fn new() -> Stats {
+        Stats {
+            since: Instant::now(),
+            instructions: 0,
+            frames: 0,
+        }
+    }
+
+    /// The title to show once a second has passed since the last one, starting a new count
+    fn title(&mut self) -> Option<String> {
+        let elapsed = self.since.elapsed();
+        if elapsed < Duration::from_secs(1) {
+            return None;
+        }
+        let title = stats_title(self.instructions, self.frames, elapsed);
+        *self = Stats::new();
+        Some(title)
+    }
+}
+
+/// The window title showing the instructions and frames per second
+fn stats_title(instructions: u64, frames: u64, elapsed: Duration) -> String {
+    let per_second = |count: u64| (count as f64 / elapsed.as_secs_f64()).round();
+    format!(
+        "CHIP8 — {} IPS, {} FPS",
+        per_second(instructions),
+        per_second(frames)
+    )
+}
+
+/// The contents of the first file in a zip archive
+#[cfg(feature = "zip")]
+fn unzip_first(archive: &[u8]) -> zip::result::ZipResult<Vec<u8>> {
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(archive))?;
+    let mut rom = Vec::new();
+    archive.by_index(0)?.read_to_end(&mut rom)?;
+    Ok(rom)
+}
+
+/// A frontend that plays back scripted key presses and debug commands, one per poll
+#[cfg(test)]
+#[derive(Default)]
+struct ScriptedFrontend {
+    keys: VecDeque<[bool; 16]>,
+    debug_commands: VecDeque<DebugCommand>,
+    focus: VecDeque<bool>,
+    invert_toggles: VecDeque<bool>,
+    held: VecDeque<[bool; 16]>,
+    open: VecDeque<bool>,
+    frames: std::rc::Rc<std::cell::RefCell<Vec<Vec<u32>>>>,
+}
+
+#[cfg(test)]
+impl Frontend for ScriptedFrontend {
+    fn present(&mut self, buffer: &[u32], _width: usize, _height: usize) {
+        self.frames.borrow_mut().push(buffer.to_vec());
+    }
+
+    fn poll_keys(&mut self) -> [bool; 16] {
+        self.keys.pop_front().unwrap_or([false; 16])
+    }
+
+    fn poll_debug_command(&mut self) -> Option<DebugCommand> {
+        self.debug_commands.pop_front()
+    }
+
+    /// The last value sticks, like a closed window staying closed
+    fn is_open(&mut self) -> bool {
+        match self.open.len() {
+            0 => true,
+            1 => self.open[0],
+            _ => self.open.pop_front().unwrap(),
+        }
+    }
+
+    fn has_focus(&mut self) -> bool {
+        self.focus.pop_front().unwrap_or(true)
+    }
+
+    fn take_invert_toggle(&mut self) -> bool {
+        self.invert_toggles.pop_front().unwrap_or(false)
+    }
+
+    fn held_keys(&mut self) -> [bool; 16] {
+        self.held.pop_front().unwrap_or([false; 16])
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    /// What CaptureLogger kept for each test thread
+    static LOGGED: std::cell::RefCell<Vec<String>> =
+        const { std::cell::RefCell::new(Vec::new()) };
+}
+
+/// A logger that keeps warnings and errors per thread, so tests running at once don't mix them
+#[cfg(test)]
+struct CaptureLogger;
+
+#[cfg(test)]
+impl log::Log for CaptureLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Warn
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            let line = format!("{} {}", record.level(), record.args());
+            LOGGED.with(|logged| logged.borrow_mut().push(line));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(test)]
+fn key_press(key: usize) -> [bool; 16] {
+    let mut keys = [false; 16];
+    keys[key] = true;
+    keys
+}
+
+#[test]
+fn test_held_keys() {
+    // Key 3 is only pressed and key 5 is held for two frames, then released
+    let frontend = ScriptedFrontend {
+        keys: vec![key_press(3)].into(),
+        held: vec![key_press(5), key_press(5), [false; 16]].into(),
+        ..ScriptedFrontend::default()
+    };
+    let mut chip8 = CHIP8::with_frontend(Box::new(frontend));
+    chip8.set_keys();
+    assert_eq!(chip8.keys, key_press(5));
+    chip8.set_keys();
+    assert_eq!(chip8.keys, key_press(5));
+    chip8.set_keys();
+    assert_eq!(chip8.keys, [false; 16]);
+
+    // Fx0A reads the press, not the held keys
+    chip8.non_blocking_keys = true;
+    chip8.execute_opcode(0xF00A).unwrap();
+    assert_eq!(chip8.registers[0], 3);
+}
+
+#[test]
+fn test_record_and_replay_input() {
+    let program = crate::assembler::assemble(
+        "
+        LD V0, 5
+        loop: SKNP V0
+        ADD V1, 1
+        LD V2, K
+        ADD V3, V2
+        RND V4, 0xFF
+        JP loop
+    ",
+    )
+    .unwrap();
+    let run =
+        |frontend: ScriptedFrontend, replay: Option<Vec<InputEvent>>, record: Option<&str>| {
+            let mut chip8 = CHIP8::with_frontend(Box::new(frontend));
+            chip8.deterministic = true;
+            chip8.set_seed(1);
+            chip8.max_cycles = Some(100);
+            chip8.record_input = record.map(str::to_string);
+            if let Some(events) = replay {
+                chip8.replay_input(events);
+            }
+            chip8.load_bytes(&program).unwrap();
+            assert_eq!(
+                chip8.run(),
+                Err(Error::InstructionLimitReached { limit: 100 })
+            );
+            chip8
+        };
+
+    let path = std::env::temp_dir().join("chip8_test_record_input.txt");
+    let path = path.to_str().unwrap();
+    // Fx0A polls once a cycle while recording, so 5 is pressed on the third try and 3 thirty
+    // tries into the second Fx0A, which 5 being held down doesn't satisfy
+    let mut presses = vec![[false; 16], [false; 16], key_press(5)];
+    presses.extend(vec![[false; 16]; 30]);
+    presses.push(key_press(3));
+    let frontend = ScriptedFrontend {
+        keys: presses.into(),
+        held: vec![
+            [false; 16],
+            key_press(5),
+            key_press(5),
+            [false; 16],
+            key_press(3),
+        ]
+        .into(),
+        ..ScriptedFrontend::default()
+    };
+    let recorded = run(frontend, None, Some(path));
+    let events = input_log::parse(&std::fs::read_to_string(path).unwrap()).unwrap();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(events, recorded.recorded_input());
+    // 5 pressed and released, then 3 pressed and released, with both presses read by Fx0A
+    assert_eq!(events.len(), 6);
+    let reads = events
+        .iter()
+        .filter(|event| event.change == KeyChange::Read);
+    assert_eq!(reads.map(|event| event.key).collect::<Vec<_>>(), [5, 3]);
+    assert_eq!(recorded.register(3), 8);
+
+    let replayed = run(ScriptedFrontend::default(), Some(events), None);
+    assert_eq!(
+        recorded.snapshot().diff(&replayed.snapshot()),
+        Vec::<String>::new()
+    );
+}
+
+#[test]
+fn test_cycle_costs() {
+    // Runs two frames, then the window closes
+    let instructions_run = |program: &[u8], cycle_costs| {
+        let frontend = ScriptedFrontend {
+            open: vec![true, true, false].into(),
+            ..ScriptedFrontend::default()
+        };
+        let mut chip8 = CHIP8::with_frontend(Box::new(frontend));
+        chip8.deterministic = true;
+        chip8.cycle_costs = cycle_costs;
+        chip8.load_bytes(program).unwrap();
+        chip8.run().unwrap();
+        chip8.cycle_count()
+    };
+    // LD I, 0x050; DRW V0, V1, 5; JP 0x202, drawing over and over
+    let draw_loop = [0xA0, 0x50, 0xD0, 0x15, 0x12, 0x02];
+    assert_eq!(instructions_run(&draw_loop, false), 20);
+    // 4 instructions go 280 cycles over the first frame's 500, leaving room for 2 in the second
+    assert_eq!(instructions_run(&draw_loop, true), 6);
+    // Cheap instructions still run about cycles_per_frame a frame
+    assert_eq!(instructions_run(&[0x12, 0x00], true), 20);
+}
+
+#[test]
+fn test_run_sequence() {
+    let mut chip8 = CHIP8::headless();
+    chip8.max_cycles = Some(100);
+    let files = ["testbin/add_xnn.chip8", "testbin/set_16bit_register.chip8"].map(String::from);
+    chip8.run_sequence(&files).unwrap();
+    assert_eq!(chip8.index(), 10); // Set by the second program
+    assert_eq!(chip8.register(0), 0); // Reset after the first program added to it
+
+    // Programs after one that stops with an error don't run
+    let mut chip8 = CHIP8::headless();
+    chip8.max_cycles = Some(100);
+    let files = ["testbin/infinite_loop.chip8", "testbin/add_xnn.chip8"].map(String::from);
+    let result = chip8.run_sequence(&files);
+    assert_eq!(result, Err(Error::InstructionLimitReached { limit: 100 }));
+    assert_eq!(chip8.register(0), 0);
+
+    // Files that can't be loaded are an error, after the programs that could be have run
+    let mut chip8 = CHIP8::headless();
+    chip8.coverage = true;
+    let files = [
+        "testbin/add_xnn.chip8",
+        "testbin/missing.chip8",
+        "testbin/set_16bit_register.chip8",
+    ]
+    .map(String::from);
+    let mut finished = Vec::new();
+    let result = chip8.run_sequence_with(&files, |chip8, file| {
+        finished.push((file.to_string(), chip8.opcode_counts.len()));
+    });
+    let file = "testbin/missing.chip8".to_string();
+    assert_eq!(result, Err(Error::LoadFailed { file }));
+    // Each program's counts only cover its own instructions
+    let expected = [
+        ("testbin/add_xnn.chip8".to_string(), 3),
+        ("testbin/set_16bit_register.chip8".to_string(), 2),
+    ];
+    assert_eq!(finished, expected);
+}
+
+#[test]
+fn test_window_closed() {
+    // Closed after the first frame of a program that never halts
+    let frontend = ScriptedFrontend {
+        open: vec![true, false].into(),
+        ..ScriptedFrontend::default()
+    };
+    let mut chip8 = CHIP8::with_frontend(Box::new(frontend));
+    chip8.deterministic = true;
+    chip8.load_and_run("testbin/infinite_loop.chip8").unwrap();
+    assert_eq!(chip8.cycle_count(), 10);
+
+    // Closed while Fx0A waits for a key
+    let frontend = ScriptedFrontend {
+        open: vec![true, false].into(),
+        ..ScriptedFrontend::default()
+    };
+    let mut chip8 = CHIP8::with_frontend(Box::new(frontend));
+    chip8.deterministic = true;
+    chip8
+        .load_and_run("testbin/set_x_to_key_press.chip8")
+        .unwrap();
+
+    // Closed while stepping in debug mode
+    let frontend = ScriptedFrontend {
+        open: vec![true, false].into(),
+        ..ScriptedFrontend::default()
+    };
+    let mut chip8 = CHIP8::with_frontend(Box::new(frontend));
+    chip8.debug = true;
+    chip8.load_and_run("testbin/infinite_loop.chip8").unwrap();
+    assert_eq!(chip8.cycle_count(), 0);
+}
+
+#[test]
+fn test_key_repeat() {
+    // Key 5 is held down without a new press
+    let scripted = |key_repeat| {
+        let frontend = ScriptedFrontend {
+            held: vec![key_press(5)].into(),
+            ..ScriptedFrontend::default()
+        };
+        let mut chip8 = CHIP8::with_frontend(Box::new(frontend));
+        chip8.non_blocking_keys = true;
+        chip8.key_repeat = key_repeat;
+        chip8
+    };
+    let mut chip8 = scripted(true);
+    chip8.execute_opcode(0xF00A).unwrap();
+    assert_eq!(chip8.registers[0], 5);
+    assert_eq!(chip8.pc(), 0x202);
+
+    let mut chip8 = scripted(false);
+    chip8.execute_opcode(0xF00A).unwrap();
+    assert_eq!(chip8.registers[0], 0);
+    assert_eq!(chip8.pc(), 0x200); // Still waiting
+}
+
+#[test]
+fn test_held_key_read_once() {
+    // Key 5 is pressed once, then held for the following frames
+    let frontend = ScriptedFrontend {
+        keys: vec![key_press(5)].into(),
+        held: vec![key_press(5); 3].into(),
+        ..ScriptedFrontend::default()
+    };
+    let mut chip8 = CHIP8::with_frontend(Box::new(frontend));
+    chip8.non_blocking_keys = true;
+    chip8.set_keys();
+    chip8.execute_opcode(0xF00A).unwrap();
+    assert_eq!(chip8.registers[0], 5);
+    chip8.set_keys();
+    assert_eq!(chip8.keys, key_press(5));
+    chip8.execute_opcode(0xF10A).unwrap();
+    assert_eq!(chip8.registers[1], 0);
+    assert_eq!(chip8.pc(), 0x202); // Still waiting
+
+    // The same goes for keys held down with set_key
+    let mut chip8 = CHIP8::headless();
+    chip8.non_blocking_keys = true;
+    chip8.set_key(5, true).unwrap();
+    chip8.set_key(5, true).unwrap();
+    chip8.execute_opcode(0xF00A).unwrap();
+    assert_eq!(chip8.registers[0], 5);
+    chip8.execute_opcode(0xF10A).unwrap();
+    assert_eq!(chip8.registers[1], 0);
+    assert_eq!(chip8.pc(), 0x202);
+    chip8.release_key(5).unwrap();
+    chip8.set_key(5, true).unwrap();
+    chip8.execute_opcode(0xF10A).unwrap();
+    assert_eq!(chip8.registers[1], 5);
+}
+
+#[test]
+fn test_scripted_set_x_to_keypress() {
+    let frontend = ScriptedFrontend {
+        keys: vec![[false; 16], [false; 16], key_press(5)].into(),
+        ..ScriptedFrontend::default()
+    };
+    let mut chip8 = CHIP8::with_frontend(Box::new(frontend));
+    chip8
+        .load_and_run("testbin/set_x_to_key_press.chip8")
+        .unwrap();
+    assert_eq!(chip8.registers[0], 5);
+}
+
+#[test]
+fn test_timers_run_while_waiting_for_keypress() {
+    let frontend = ScriptedFrontend {
+        keys: vec![[false; 16], [false; 16], [false; 16], key_press(5)].into(),
+        ..ScriptedFrontend::default()
+    };
+    let mut chip8 = CHIP8::with_frontend(Box::new(frontend));
+    chip8.deterministic = true;
+    // LD V1, 0x0A; LD DT, V1; LD ST, V1; LD V0, K
+    chip8
+        .load_bytes(&[0x61, 0x0A, 0xF1, 0x15, 0xF1, 0x18, 0xF0, 0x0A])
+        .unwrap();
+    chip8.run().unwrap();
+    assert_eq!(chip8.registers[0], 5);
+    // Three frames passed without a key before 5 was pressed
+    assert_eq!(chip8.delay_timer, 7);
+    assert_eq!(chip8.sound_timer, 7);
+}
+
+#[test]
+fn test_sound_while_waiting_for_keypress() {
+    // LD V1, 0x03; LD ST, V1; LD V0, K
+    let rom = [0x61, 0x03, 0xF1, 0x18, 0xF0, 0x0A];
+    let run = |frontend: ScriptedFrontend| {
+        let changes = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let recorder = changes.clone();
+        let mut chip8 = CHIP8::with_frontend(Box::new(frontend));
+        chip8.deterministic = true;
+        chip8.set_sound_callback(Box::new(move |playing| recorder.borrow_mut().push(playing)));
+        chip8.load_bytes(&rom).unwrap();
+        chip8.run().unwrap();
+        let changes = changes.borrow().clone();
+        (changes, chip8.sound_timer)
+    };
+    let pressed_after = |frames: usize| ScriptedFrontend {
+        keys: std::iter::repeat([false; 16])
+            .take(frames)
+            .chain([key_press(5)])
+            .collect(),
+        ..ScriptedFrontend::default()
+    };
+
+    // The beep keeps playing through the first two frames of waiting
+    assert_eq!(run(pressed_after(2)), (vec![true], 1));
+    // And stops on the third, without waiting for the key
+    assert_eq!(run(pressed_after(3)), (vec![true, false], 0));
+    assert_eq!(run(pressed_after(10)), (vec![true, false], 0));
+
+    // Closing the window while waiting stops the beep instead of leaving it on
+    let closed = ScriptedFrontend {
+        open: vec![true, true, false].into(),
+        ..ScriptedFrontend::default()
+    };
+    assert_eq!(run(closed), (vec![true, false], 0));
+}
+
+#[test]
+fn test_non_blocking_set_x_to_keypress() {
+    let frontend = ScriptedFrontend {
+        keys: vec![[false; 16], [false; 16], key_press(5)].into(),
+        ..ScriptedFrontend::default()
+    };
+    let mut chip8 = CHIP8::with_frontend(Box::new(frontend));
+    chip8.non_blocking_keys = true;
+    chip8.load_bytes(&[0xF0, 0x0A]).unwrap(); // LD V0, K
+    for _ in 0..2 {
+        assert_eq!(chip8.step(), Ok(StepResult::Continue));
+        assert_eq!(chip8.pc(), 0x200);
+    }
+    chip8.step().unwrap();
+    assert_eq!(chip8.pc(), 0x202);
+    assert_eq!(chip8.registers[0], 5);
+}
+
+#[test]
+fn test_instruction_hook() {
+    let executed = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let recorder = executed.clone();
+    let mut chip8 = CHIP8::headless();
+    chip8.set_instruction_hook(Box::new(move |pc, opcode| {
+        recorder.borrow_mut().push((pc, opcode));
+    }));
+    chip8.load_and_run("testbin/add_xnn.chip8").unwrap();
+    assert_eq!(
+        *executed.borrow(),
+        [(0x200, 0x6005), (0x202, 0x7005), (0x204, 0x0000)]
+    );
+}
+
+#[test]
+fn test_sound_callback() {
+    let changes = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let recorder = changes.clone();
+    let mut chip8 = CHIP8::headless();
+    chip8.set_sound_callback(Box::new(move |playing| recorder.borrow_mut().push(playing)));
+    chip8.load_and_run("testbin/timers.chip8").unwrap(); // Sets the sound timer to 10
+    assert_eq!(*changes.borrow(), [true]);
+    for _ in 0..10 {
+        chip8.tick_timers();
+    }
+    assert_eq!(*changes.borrow(), [true, false]);
+}
+
+#[test]
+fn test_manual_timers() {
+    let mut chip8 = CHIP8::headless();
+    chip8.deterministic = true;
+    chip8.set_timer_mode(TimerMode::Manual);
+    chip8.load_and_run("testbin/timers.chip8").unwrap(); // Sets the delay timer to 5 and sound to 10
+    chip8.end_frame();
+    assert_eq!((chip8.delay_timer, chip8.sound_timer), (5, 10));
+
+    chip8.tick_timers();
+    assert_eq!((chip8.delay_timer, chip8.sound_timer), (4, 9));
+
+    chip8.delay_timer = 0;
+    chip8.tick_timers();
+    assert_eq!((chip8.delay_timer, chip8.sound_timer), (0, 8));
+}
+
+#[test]
+fn test_pause_on_blur() {
+    let scripted_focus = |focus: Vec<bool>| {
+        let frontend = ScriptedFrontend {
+            focus: focus.into(),
+            ..ScriptedFrontend::default()
+        };
+        let mut chip8 = CHIP8::with_frontend(Box::new(frontend));
+        chip8.deterministic = true;
+        chip8
+    };
+    let mut chip8 = scripted_focus(vec![false, false, true, false]);
+    chip8.wait_for_focus();
+    assert!(!chip8.frontend.has_focus()); // Stopped waiting as soon as focus came back
+
+    let mut chip8 = scripted_focus(vec![false, false, true]);
+    chip8.pause_on_blur = true;
+    chip8.load_and_run("testbin/add_xnn.chip8").unwrap();
+    assert_eq!(chip8.registers[0], 10);
+
+    // Focus is never checked unless pause_on_blur is on
+    let mut chip8 = scripted_focus(vec![false]);
+    chip8.load_and_run("testbin/add_xnn.chip8").unwrap();
+    assert!(!chip8.frontend.has_focus());
+}
+
+#[test]
+fn test_scripted_debug_quit() {
+    let frontend = ScriptedFrontend {
+        debug_commands: vec![DebugCommand::Step, DebugCommand::Quit].into(),
+        ..ScriptedFrontend::default()
+    };
+    let mut chip8 = CHIP8::with_frontend(Box::new(frontend));
+    chip8.debug = true;
+    chip8.load_and_run("testbin/add_xnn.chip8").unwrap();
+    assert_eq!(chip8.registers[0], 5); // Quit before 7005 ran
+}
+
+#[test]
+fn test_scripted_run_until_draw() {
+    let frontend = ScriptedFrontend {
+        debug_commands: vec![DebugCommand::RunUntilDraw, DebugCommand::Quit].into(),
+        ..ScriptedFrontend::default()
+    };
+    let frames = frontend.frames.clone();
+    let mut chip8 = CHIP8::with_frontend(Box::new(frontend));
+    chip8.debug = true;
+    chip8.load_and_run("testbin/draw_twice.chip8").unwrap();
+    assert_eq!(frames.borrow().len(), 1);
+    assert_eq!(chip8.pc(), 0x204); // Stopped after the first DRW
+}
+
+#[test]
+fn test_hex_dump() {
+    let mut chip8 = CHIP8::headless();
+    chip8.memory[0x300..0x305].copy_from_slice(b"CHIP8");
+    chip8.memory[0x310] = 0xff;
+    assert_eq!(
+        chip8.hex_dump(0x300, 18),
+        "300: 43 48 49 50 38 00 00 00 00 00 00 00 00 00 00 00  |CHIP8...........|\n\
+         310: ff 00                                            |..|\n"
+    );
+    let end = chip8.hex_dump(0xffe, 16);
+    assert_eq!(
+        end,
+        "ffe: 00 00                                            |..|\n"
+    );
+}
+
+#[test]
+fn test_dump_memory_to_file() {
+    let mut chip8 = CHIP8::headless();
+    chip8.load_into_memory("testbin/draw.chip8");
+    let path = std::env::temp_dir().join("chip8_test_dump_memory.bin");
+    let path = path.to_str().unwrap();
+    chip8.dump_memory_to_file(path).unwrap();
+    let dumped = std::fs::read(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(dumped[..], chip8.memory[..]);
+}
+
+#[test]
+fn test_screenshot_on_halt() {
+    let path = std::env::temp_dir().join("chip8_test_screenshot_on_halt.png");
+    let path = path.to_str().unwrap();
+    let mut chip8 = CHIP8::headless();
+    chip8.screenshot_on_halt = Some(path.to_string());
+    chip8.screenshot_scale = 2;
+    chip8.load_and_run("testbin/draw.chip8").unwrap();
+    let png = std::fs::read(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(png, chip8.screenshot());
+    assert_eq!(png[12..16], *b"IHDR");
+    assert_eq!(png[16..24], [0, 0, 0, 128, 0, 0, 0, 64]); // 64x32 scaled up twice
+
+    // Programs stopped by max_cycles get one too, but not ones that stop with other errors
+    chip8.max_cycles = Some(25);
+    chip8
+        .load_and_run("testbin/infinite_loop.chip8")
+        .unwrap_err();
+    std::fs::remove_file(path).unwrap();
+    chip8
+        .load_and_run("testbin/stack_overflow.chip8")
+        .unwrap_err();
+    assert!(std::fs::metadata(path).is_err());
+}
+
+#[test]
+fn test_autosave() {
+    let path = std::env::temp_dir().join("chip8_test_autosave");
+    let path = path.to_str().unwrap();
+    let slot = |n: usize| format!("{}.{}.state", path, n);
+    let mut chip8 = CHIP8::headless();
+    chip8.deterministic = true;
+    chip8.autosave = Some(2);
+    chip8.autosave_path = path.to_string();
+    // 10 instructions a frame, so this stops partway through the third frame
+    chip8.max_cycles = Some(25);
+    chip8
+        .load_and_run("testbin/infinite_loop.chip8")
+        .unwrap_err();
+    assert!(std::fs::metadata(slot(0)).is_ok());
+    assert!(std::fs::metadata(slot(1)).is_err());
+
+    // Four more frames save twice, moving the first save along to the oldest slot
+    chip8.max_cycles = Some(45);
+    chip8
+        .load_and_run("testbin/infinite_loop.chip8")
+        .unwrap_err();
+    let saved = chip8.snapshot();
+    let mut restored = CHIP8::headless();
+    restored.load_state(&slot(0)).unwrap();
+    assert_eq!(restored.snapshot().diff(&saved), Vec::<String>::new());
+
+    // A sequence can start from a save, here one made just after V0 = 5 with V0 changed to 1
+    chip8.load_into_memory("testbin/add_xnn.chip8");
+    let mut state = chip8.snapshot();
+    (state.registers[0], state.pc) = (1, 0x202);
+    std::fs::write(slot(0), save_state::encode(&state)).unwrap();
+    let mut resumed = CHIP8::headless();
+    resumed.resume_from = Some(slot(0));
+    resumed
+        .run_sequence(&["testbin/add_xnn.chip8".to_string()])
+        .unwrap();
+    assert_eq!(resumed.register(0), 6);
+    resumed.resume_from = Some(slot(5));
+    let result = resumed.run_sequence(&["testbin/add_xnn.chip8".to_string()]);
+    assert!(matches!(result, Err(Error::InvalidSaveState { .. })));
+    for n in 0..AUTOSAVE_SLOTS {
+        std::fs::remove_file(slot(n)).unwrap();
+    }
+}
+
+#[test]
+fn test_dump_on_exit() {
+    let path = std::env::temp_dir().join("chip8_test_dump_on_exit.txt");
+    let path = path.to_str().unwrap();
+    let mut chip8 = CHIP8::headless();
+    chip8.deterministic = true;
+    chip8.dump_on_exit = Some(path.to_string());
+    chip8.load_and_run("testbin/add_xnn.chip8").unwrap();
+    let dump = std::fs::read_to_string(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    assert!(dump.starts_with("Exit: ran past the end of the program\n"));
+    assert!(dump.contains(&format!("\nV0: {:02x}\n", chip8.registers[0])));
+    assert!(dump.contains(&format!("\nPC: {:03x}\n", chip8.position_in_memory)));
+    assert!(dump.contains(&format!("\n{}\n", ".".repeat(WIDTH))));
+    assert!(dump.contains("\n200: 60 "));
+}
+
+#[test]
+fn test_read_sprite() {
+    let mut chip8 = CHIP8::headless();
+    chip8.load_into_memory("testbin/draw.chip8");
+    chip8.registers[0] = 0xA;
+    chip8.execute_opcode(0xF029).unwrap(); // I = the font's A
+    assert_eq!(chip8.read_sprite(5), [0xf0, 0x90, 0xf0, 0x90, 0x90]);
+    assert_eq!(sprite_text(&chip8.read_sprite(2)), "####....\n#..#....\n");
+
+    chip8.i = 0xFFE;
+    assert_eq!(chip8.read_sprite(5).len(), 2);
+}
+
+#[test]
+fn test_scripted_frames() {
+    let frontend = ScriptedFrontend::default();
+    let frames = frontend.frames.clone();
+    let mut chip8 = CHIP8::with_frontend(Box::new(frontend));
+    chip8.load_and_run("testbin/draw.chip8").unwrap();
+    let frames = frames.borrow();
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0][2], chip8.color.hex_color());
+    assert_eq!(frames[0][0], 0x000000);
+}
+
+#[test]
+fn test_scripted_frames_brightness() {
+    let frontend = ScriptedFrontend::default();
+    let frames = frontend.frames.clone();
+    let mut chip8 = CHIP8::with_frontend(Box::new(frontend));
+    chip8.brightness = 0.5;
+    chip8.load_and_run("testbin/draw.chip8").unwrap();
+    let frames = frames.borrow();
+    assert_eq!(frames[0][2], chip8.color.scaled(0.5));
+    assert_eq!(frames[0][0], 0x000000);
+}
+
+#[test]
+fn test_scripted_frames_palette() {
+    let frontend = ScriptedFrontend::default();
+    let frames = frontend.frames.clone();
+    let mut chip8 = CHIP8::with_frontend(Box::new(frontend));
+    chip8.palette = Some(Palette {
+        colors: [0x112233, 0x445566, 0x778899, 0xaabbcc],
+    });
+    chip8.load_and_run("testbin/draw.chip8").unwrap();
+    let frames = frames.borrow();
+    assert_eq!(frames[0][2], 0x445566);
+    assert_eq!(frames[0][0], 0x112233);
+}
+
+#[test]
+fn test_memory_size() {
+    let mut chip8 = CHIP8::headless().with_memory_size(0x2000);
+    chip8.start_address = 0x1000;
+    // LD V0, 0x05; LD I, 0x1FF; LD [I], V0, all past the usual end of memory
+    chip8
+        .load_bytes(&[0x60, 0x05, 0xA1, 0xFF, 0xF0, 0x55])
+        .unwrap();
+    chip8.run().unwrap();
+    assert_eq!(chip8.memory_size(), 0x2000);
+    assert_eq!(chip8.pc(), 0x1008);
+    assert_eq!(chip8.memory[0x1FF], 5);
+
+    let mut chip8 = CHIP8::headless().with_memory_size(0x202);
+    let too_large = Err(Error::ProgramTooLarge { size: 4, space: 2 });
+    assert_eq!(chip8.load_bytes(&[0x60, 0x05, 0x00, 0x00]), too_large);
+    assert_eq!(chip8.load_bytes(&[0x60, 0x05]), Ok(()));
+}
+
+#[test]
+fn test_jump_past_reduced_memory() {
+    // JP 0x800, CALL 0x800 and JP V0, 0x7FF with V0=1 all land past 2048 bytes of memory
+    for program in [
+        [0x18, 0x00, 0x00, 0x00],
+        [0x28, 0x00, 0x00, 0x00],
+        [0x60, 0x01, 0xB7, 0xFF],
+    ] {
+        let mut chip8 = CHIP8::headless().with_memory_size(0x800);
+        chip8.load_bytes(&program).unwrap();
+        let pc = if program[0] == 0x60 { 0x202 } else { 0x200 };
+        assert_eq!(chip8.run(), Err(Error::JumpOutOfRange { pc, addr: 0x800 }));
+    }
+
+    // Jumps inside the reduced memory still work
+    let mut chip8 = CHIP8::headless().with_memory_size(0x800);
+    // JP 0x204; LD V0, 0x01; LD V1, 0x02
+    chip8
+        .load_bytes(&[0x12, 0x04, 0x60, 0x01, 0x61, 0x02])
+        .unwrap();
+    chip8.run().unwrap();
+    assert_eq!(chip8.registers[..2], [0, 2]);
+}
+
+#[test]
+fn test_stats_title() {
+    assert_eq!(
+        stats_title(1080, 120, Duration::from_secs(2)),
+        "CHIP8 — 540 IPS, 60 FPS"
+    );
+    assert_eq!(
+        stats_title(601, 61, Duration::from_millis(1010)),
+        "CHIP8 — 595 IPS, 60 FPS"
+    );
+
+    let mut stats = Stats::new();
+    stats.instructions = 10;
+    assert_eq!(stats.title(), None); // Less than a second has passed
+    stats.since -= Duration::from_secs(1);
+    assert_eq!(stats.title(), Some("CHIP8 — 10 IPS, 0 FPS".to_string()));
+    assert_eq!(stats.instructions, 0);
+}
+
+#[test]
+fn test_with_options() {
+    let mut audio = Audio::default();
+    audio.volume = 50;
+    let options = Options {
+        color: Color::Green,
+        brightness: 0.5,
+        quirks: Quirks::for_platform(crate::quirks::Platform::Schip),
+        cycles_per_frame: 20,
+        seed: Some(7),
+        debug: true,
+        headless: true,
+        memory_size: 0x800,
+        start_address: 0x600,
+        strict: true,
+        key_repeat: true,
+        max_cycles: Some(100),
+        deterministic: true,
+        audio,
+        ..Options::default()
+    };
+    let chip8 = CHIP8::with_options(options);
+    assert_eq!(chip8.memory_size(), 0x800);
+    assert_eq!(chip8.start_address, 0x600);
+    assert!(chip8.strict && chip8.key_repeat && chip8.deterministic);
+    assert_eq!(chip8.max_cycles, Some(100));
+    assert_eq!(chip8.audio.volume, 50);
+    assert_eq!(chip8.color, Color::Green);
+    assert_eq!(chip8.brightness, 0.5);
+    assert_eq!(
+        chip8.quirks,
+        Quirks::for_platform(crate::quirks::Platform::Schip)
+    );
+    assert_eq!(chip8.cycles_per_frame, 20);
+    assert!(chip8.debug);
+
+    let mut seeded = CHIP8::headless();
+    seeded.set_seed(7);
+    let mut chip8 = chip8;
+    assert_eq!(chip8.rng.gen::<u64>(), seeded.rng.gen::<u64>());
+}
+
+#[test]
+fn test_headless() {
+    let mut chip8 = CHIP8::headless();
+    chip8.load_and_run("testbin/add_xnn.chip8").unwrap();
+    assert_eq!(chip8.registers[0], 10);
+}
+
+#[test]
+fn test_add_xnn_wraps_without_setting_vf() {
+    let mut chip8 = CHIP8::headless();
+    // LD VF, 0x07; LD V0, 0xFF; ADD V0, 0x02; LD V1, 0x01; ADD V1, 0xFF
+    chip8
+        .load_bytes(&[0x6F, 0x07, 0x60, 0xFF, 0x70, 0x02, 0x61, 0x01, 0x71, 0xFF])
+        .unwrap();
+    chip8.run().unwrap();
+    assert_eq!(chip8.registers[0], 1);
+    assert_eq!(chip8.registers[1], 0);
+    assert_eq!(chip8.registers[VF], 7);
+}
+
+#[test]
+fn test_host_driven() {
+    let mut chip8 = CHIP8::headless();
+    chip8
+        .load_bytes(&[
+            0x60, 0x00, 0x61, 0x01, 0xE0, 0x9E, 0x61, 0x02, 0xA0, 0x50, 0xD0, 0x05,
+        ])
+        .unwrap();
+    chip8.set_key(0, true).unwrap();
+    assert_eq!(chip8.step(), Ok(StepResult::Continue));
+    assert_eq!(chip8.step(), Ok(StepResult::Continue));
+    assert_eq!(chip8.step(), Ok(StepResult::Continue)); // Skips 6102
+    assert_eq!(chip8.registers[1], 1);
+    assert_eq!(chip8.step(), Ok(StepResult::Continue));
+    assert_eq!(chip8.step(), Ok(StepResult::Continue));
+    // The font for 0 is at 0x50
+    assert_eq!(chip8.framebuffer()[0][..4], [true, true, true, true]);
+    assert_eq!(chip8.framebuffer()[1][..4], [true, false, false, true]);
+    assert_eq!(
+        chip8.step(),
+        Ok(StepResult::Halted(HaltReason::EndOfProgram))
+    );
+}
+
+#[test]
+fn test_frame_diff() {
+    let mut chip8 = CHIP8::headless();
+    // LD I, 0x050; DRW V0, V0, 1; ADD V1, 1; DRW V0, V0, 1, drawing the top of the 0 twice
+    chip8
+        .load_bytes(&[0xA0, 0x50, 0xD0, 0x01, 0x71, 0x01, 0xD0, 0x01])
+        .unwrap();
+    chip8.step().unwrap();
+    chip8.step().unwrap();
+    let drawn: Vec<_> = (0..4).map(|col| (0, col, true)).collect();
+    assert_eq!(chip8.frame_diff(), drawn);
+
+    // Nothing drawn since the last diff
+    chip8.step().unwrap();
+    assert!(chip8.frame_diff().is_empty());
+
+    // Drawing it again erases it
+    chip8.step().unwrap();
+    let erased: Vec<_> = (0..4).map(|col| (0, col, false)).collect();
+    assert_eq!(chip8.frame_diff(), erased);
+}
+
+#[test]
+fn test_framebuffer() {
+    let mut chip8 = CHIP8::headless();
+    chip8.load_into_memory("testbin/draw.chip8");
+    chip8.step().unwrap();
+    assert!(!chip8.take_draw_flag());
+    chip8.step().unwrap();
+    assert!(chip8.take_draw_flag());
+    assert!(!chip8.take_draw_flag());
+
+    //   ****
+    // **    **
+    // ********
+    let framebuffer = chip8.framebuffer();
+    assert_eq!(
+        framebuffer[0][..8],
+        [false, false, true, true, true, true, false, false]
+    );
+    assert_eq!(
+        framebuffer[1][..8],
+        [true, true, false, false, false, false, true, true]
+    );
+    assert_eq!(framebuffer[2][..8], [true; 8]);
+
+    let flat = chip8.framebuffer_flat();
+    assert_eq!(flat.len(), WIDTH * HEIGHT);
+    assert_eq!(flat[..8], framebuffer[0][..8]);
+    assert_eq!(flat[WIDTH..WIDTH + 8], framebuffer[1][..8]);
+    assert_eq!(flat.iter().filter(|&&pixel| pixel).count(), 16);
+}
+
+#[test]
+fn test_draw_collision() {
+    let mut chip8 = CHIP8::headless();
+    let program = [
+        0xA2, 0x10, // LD I, 0x210
+        0x62, 0x04, // LD V2, 0x04
+        0xD2, 0x01, // DRW V2, V0, 1
+        0xD0,
+        0x01, // DRW V0, V0, 1 next to it, only the sprite's blank half covers lit pixels
+        0x81, 0xF0, // LD V1, VF
+        0x62, 0x02, // LD V2, 0x02
+        0xD2, 0x01, // DRW V2, V0, 1 over both
+        0x00, 0x00, // HALT
+        0xF0, // DB 0xF0
+    ];
+    chip8.load_bytes(&program).unwrap();
+    chip8.run().unwrap();
+    assert_eq!(chip8.registers[1], 0);
+    assert_eq!(chip8.registers[VF], 1);
+    assert_eq!(
+        chip8.display[0][..8],
+        [true, true, false, false, false, false, true, true]
+    );
+}
+
+#[test]
+fn test_frame_callback() {
+    let frames = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let recorder = frames.clone();
+    let mut chip8 = CHIP8::headless();
+    chip8.set_frame_callback(Box::new(move |display| {
+        recorder.borrow_mut().push(display.clone())
+    }));
+    chip8.load_and_run("testbin/draw.chip8").unwrap();
+    let frames = frames.borrow();
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0], *chip8.framebuffer());
+    assert_eq!(
+        frames[0][0][..8],
+        [false, false, true, true, true, true, false, false]
+    );
+}
+
+#[test]
+fn test_accessors() {
+    let mut chip8 = CHIP8::headless();
+    chip8.load_and_run("testbin/timers.chip8").unwrap();
+    assert_eq!(chip8.register(0), 5);
+    assert_eq!(chip8.register(1), 5);
+    assert_eq!(chip8.register(2), 10);
+    assert_eq!(chip8.index(), 0);
+    assert_eq!(chip8.pc(), 0x20c);
+    assert_eq!(chip8.delay(), 5);
+    assert_eq!(chip8.sound(), 10);
+
+    chip8.set_register(0xA, 7);
+    chip8.set_index(0x300);
+    chip8.set_pc(0x200);
+    chip8.set_delay(1);
+    chip8.set_sound(2);
+    assert_eq!(chip8.register(0xA), 7);
+    assert_eq!(chip8.index(), 0x300);
+    assert_eq!(chip8.pc(), 0x200);
+    assert_eq!(chip8.delay(), 1);
+    assert_eq!(chip8.sound(), 2);
+}
+
+#[test]
+fn test_identical_frame_not_presented() {
+    let frontend = ScriptedFrontend::default();
+    let frames = frontend.frames.clone();
+    let mut chip8 = CHIP8::with_frontend(Box::new(frontend));
+    chip8.deterministic = true;
+    chip8.load_into_memory("testbin/draw.chip8");
+    chip8.step().unwrap();
+    chip8.step().unwrap();
+    chip8.end_frame();
+    assert!(!chip8.draw_flag);
+    assert_eq!(frames.borrow().len(), 1);
+
+    // Nothing changed, so there's nothing to present
+    chip8.draw_flag = true;
+    chip8.end_frame();
+    assert!(!chip8.draw_flag);
+    assert_eq!(frames.borrow().len(), 1);
+
+    chip8.display[0][0] = true;
+    chip8.draw_flag = true;
+    chip8.end_frame();
+    assert_eq!(frames.borrow().len(), 2);
+}
+
+#[test]
+fn test_run_demo() {
+    let frontend = ScriptedFrontend::default();
+    let frames = frontend.frames.clone();
+    let mut chip8 = CHIP8::with_frontend(Box::new(frontend));
+    chip8.deterministic = true;
+    chip8.max_cycles = Some(100);
+    assert_eq!(
+        chip8.run_demo(),
+        Err(Error::InstructionLimitReached { limit: 100 })
+    );
+    assert!(!frames.borrow().is_empty());
+
+    let source = std::fs::read_to_string("examples/demo.asm").unwrap();
+    assert_eq!(crate::assembler::assemble(&source).unwrap(), DEMO_ROM);
+}
+
+#[test]
+fn test_max_cycles() {
+    let mut chip8 = CHIP8::headless();
+    chip8.deterministic = true;
+    chip8.max_cycles = Some(25);
+    let result = chip8.load_and_run("testbin/infinite_loop.chip8");
+    assert_eq!(result, Err(Error::InstructionLimitReached { limit: 25 }));
+
+    // Programs that halt in time aren't affected
+    chip8.load_and_run("testbin/add_xnn.chip8").unwrap();
+}
+
+#[test]
+fn test_cycle_count() {
+    let mut chip8 = CHIP8::headless();
+    chip8.deterministic = true;
+    chip8.load_and_run("testbin/add_xnn.chip8").unwrap();
+    assert_eq!(chip8.cycle_count(), 3); // 6005, 7005, then the 0000 past the end
+
+    chip8.max_cycles = Some(25);
+    chip8
+        .load_and_run("testbin/infinite_loop.chip8")
+        .unwrap_err();
+    assert_eq!(chip8.cycle_count(), 25);
+
+    chip8.load_into_memory("testbin/add_xnn.chip8");
+    assert_eq!(chip8.cycle_count(), 0);
+}
+
+#[test]
+fn test_strict() {
+    let mut chip8 = CHIP8::headless();
+    chip8.strict = true;
+    chip8
+        .load_bytes(&[0x6F, 0x01, 0x60, 0x05, 0x12, 0x00])
+        .unwrap();
+    chip8.step().unwrap();
+    chip8.step().unwrap();
+    assert_eq!(
+        chip8.strict_warnings(),
+        ["200: 6f01  LD VF, 0x01 writes VF, the flag register"]
+    );
+
+    // Each address only warns once
+    chip8.step().unwrap();
+    chip8.step().unwrap();
+    assert_eq!(chip8.strict_warnings().len(), 1);
+
+    // Flags set by the arithmetic instructions themselves are fine, as is leaving strict mode off
+    let mut chip8 = CHIP8::headless();
+    chip8.strict = true;
+    chip8.execute_opcode(0x8014).unwrap();
+    chip8.execute_opcode(0xFE65).unwrap();
+    assert!(chip8.strict_warnings().is_empty());
+    chip8.execute_opcode(0xFF65).unwrap();
+    assert_eq!(chip8.strict_warnings().len(), 1);
+
+    let mut chip8 = CHIP8::headless();
+    chip8.execute_opcode(0x6F01).unwrap();
+    assert!(chip8.strict_warnings().is_empty());
+}
+
+#[test]
+fn test_snapshot_diff() {
+    let mut chip8 = CHIP8::headless();
+    chip8.load_into_memory("testbin/add_xnn.chip8");
+    let before = chip8.snapshot();
+    chip8.draw_graphics();
+    assert_eq!(chip8.snapshot(), before);
+    assert!(before.diff(&chip8.snapshot()).is_empty());
+    chip8.execute_opcode(0x0123).unwrap(); // SYS is skipped, only moving the PC on
+    assert_eq!(before.diff(&chip8.snapshot()), ["PC: 200 -> 202"]);
+
+    chip8.position_in_memory = 0x200;
+    chip8.step().unwrap();
+    chip8.execute_opcode(0xA300).unwrap();
+    chip8.execute_opcode(0xF055).unwrap();
+    chip8.display[0][0] = true;
+    assert_eq!(
+        before.diff(&chip8.snapshot()),
+        [
+            "V0: 00 -> 05",
+            "I: 000 -> 300",
+            "PC: 200 -> 206",
+            "display: 1 pixels changed",
+            "memory 300: 00 -> 05"
+        ]
+    );
+}
+
+#[test]
+fn test_detect_idle() {
+    let mut chip8 = CHIP8::headless();
+    chip8.detect_idle = true;
+    chip8.load_into_memory("testbin/infinite_loop.chip8");
+    assert_eq!(chip8.step(), Ok(StepResult::Halted(HaltReason::IdleLoop)));
+    assert_eq!(chip8.pc(), 0x202);
+
+    chip8.deterministic = true;
+    chip8.max_cycles = Some(25);
+    chip8.load_and_run("testbin/infinite_loop.chip8").unwrap();
+
+    // Jumps anywhere else still run
+    chip8
+        .load_bytes(&[0x12, 0x04, 0x00, 0x00, 0x60, 0x05])
+        .unwrap();
+    assert_eq!(chip8.step(), Ok(StepResult::Continue));
+    assert_eq!(chip8.pc(), 0x204);
+}
+
+#[test]
+fn test_execute_opcode() {
+    let mut chip8 = CHIP8::headless();
+    chip8.execute_opcode(0x6005).unwrap();
+    assert_eq!(chip8.registers[0], 5);
+    assert_eq!(chip8.pc(), 0x202);
+
+    chip8.execute_opcode(0x7003).unwrap();
+    assert_eq!(chip8.registers[0], 8);
+    chip8.execute_opcode(0x3008).unwrap(); // Skips when V0 is 8
+    assert_eq!(chip8.pc(), 0x208);
+    chip8.execute_opcode(0x1300).unwrap();
+    assert_eq!(chip8.pc(), 0x300);
+    assert_eq!(
+        chip8.execute_opcode(0x0000),
+        Ok(StepResult::Halted(HaltReason::EndOfProgram))
+    );
+    assert_eq!(
+        chip8.execute_opcode(0x800F),
+        Err(Error::UnknownOpcode {
+            pc: 0x302,
+            opcode: 0x800F
+        })
+    );
+    assert_eq!(chip8.memory[0x200..0x204], [0; 4]); // Nothing is read from or written to memory
+}
+
+#[test]
+fn test_skip_xy_needs_zero_low_nibble() {
+    let mut chip8 = CHIP8::headless();
+    // V1 and V2 are equal, so 0x5120 would skip
+    assert_eq!(
+        chip8.execute_opcode(0x5123),
+        Err(Error::UnknownOpcode {
+            pc: 0x200,
+            opcode: 0x5123
+        })
+    );
+    assert_eq!(chip8.pc(), 0x202);
+    assert_eq!(
+        chip8.execute_opcode(0x9121),
+        Err(Error::UnknownOpcode {
+            pc: 0x202,
+            opcode: 0x9121
+        })
+    );
+    chip8.execute_opcode(0x5120).unwrap();
+    assert_eq!(chip8.pc(), 0x208);
+}
+
+#[test]
+fn test_clear_screen() {
+    let mut chip8 = CHIP8::headless();
+    chip8.display[0][0] = true;
+    chip8.load_and_run("testbin/clear_screen.chip8").unwrap();
+    assert_eq!(chip8.display[0][0], false);
+}
+
+#[test]
+fn test_large_display() {
+    let mut chip8 = CHIP8::with_display_size(Box::new(NullFrontend), 128, 64);
+    chip8.display[63][127] = true;
+    chip8.clear_screen();
+    assert!(!chip8.display[63][127]);
+
+    // Draws 0 at (100, 40), which would wrap around on a 64x32 display
+    chip8.registers[0] = 100;
+    chip8.registers[1] = 40;
+    chip8.i = 0x50;
+    chip8.load_fonts();
+    chip8.draw(0, 1, 5).unwrap();
+    assert_eq!(chip8.framebuffer()[40][100..104], [true, true, true, true]);
+    assert_eq!(chip8.framebuffer()[44][100..104], [true, true, true, true]);
+    assert_eq!(chip8.framebuffer_flat().len(), 128 * 64);
+    assert_eq!(
+        chip8
+            .framebuffer_flat()
+            .iter()
+            .filter(|&&pixel| pixel)
+            .count(),
+        14
+    );
+}
+
+#[test]
+fn test_custom_fonts() {
+    let mut chip8 = CHIP8::headless();
+    let mut font = [0; 80];
+    font[5..10].copy_from_slice(&[0x20, 0x20, 0x20, 0x20, 0x20]);
+    chip8.load_custom_fonts(&font);
+    chip8.load_bytes(&[0x60, 0x01, 0xF0, 0x29]).unwrap(); // LD V0, 1; LD F, V0
+    chip8.step().unwrap();
+    chip8.step().unwrap();
+    assert_eq!(chip8.index(), 0x55);
+    assert_eq!(chip8.memory[0x55..0x5A], [0x20; 5]);
+    assert_eq!(chip8.memory[0x50..0x55], [0; 5]);
+}
 
-    /// Vx<<=1
-    fn shift_left(&mut self, x: u8) {
-        self.memory[VF] = (self.memory[x as usize] & 0b10000000) >> 7;
-        self.registers[x as usize] <<= 1;
-    }
+#[test]
+fn test_halt_reason() {
+    let mut chip8 = CHIP8::headless();
+    chip8.load_into_memory("testbin/halt_mid_program.chip8");
+    chip8.step().unwrap();
+    assert_eq!(chip8.step(), Ok(StepResult::Halted(HaltReason::HaltOpcode)));
 
-    /// if(Vx==Vy)
-    fn skip_xy_not_equal(&mut self, x: u8, y: u8) {
-        if self.registers[x as usize] != self.registers[y as usize] {
-            self.position_in_memory += 2;
-        }
-    }
+    chip8.load_into_memory("testbin/add_xnn.chip8");
+    chip8.step().unwrap();
+    chip8.step().unwrap();
+    assert_eq!(
+        chip8.step(),
+        Ok(StepResult::Halted(HaltReason::EndOfProgram))
+    );
+}
 
-    /// I = NNN
-    fn set_16bit_register(&mut self, addr: u16) {
-        self.i = addr;
-    }
+#[test]
+#[cfg(feature = "zip")]
+fn test_load_zip() {
+    let mut chip8 = CHIP8::headless();
+    chip8.load_into_memory("testbin/add_xnn.zip");
+    assert_eq!(chip8.memory[0x200..0x204], [0x60, 0x05, 0x70, 0x05]);
+    assert_eq!(chip8.memory[0x204], 0);
+}
 
-    /// PC=V0+NNN
-    fn jump_nnn_plus_v0(&mut self, addr: u16) {
-        self.position_in_memory = (self.registers[0] as u16 + addr) as usize;
-    }
+#[test]
+fn test_start_address() {
+    let mut chip8 = CHIP8::headless();
+    chip8.start_address = 0x600;
+    chip8.load_into_memory("testbin/load_v0.chip8");
+    assert_eq!(chip8.pc(), 0x600);
+    assert_eq!(chip8.memory[0x600..0x602], [0x60, 0x07]);
+    assert_eq!(chip8.memory[0x200], 0);
+    chip8.step().unwrap();
+    assert_eq!(chip8.register(0), 7);
+    assert_eq!(chip8.pc(), 0x602);
+    assert_eq!(chip8.step(), Ok(StepResult::Halted(HaltReason::HaltOpcode)));
+}
 
-    /// Vx=rand()&NN
-    fn rand(&mut self, x: u8, nn: u8) {
-        self.registers[x as usize] = rand::random::<u8>() & nn;
-    }
+#[test]
+fn test_fill_audio() {
+    let mut chip8 = CHIP8::headless();
+    chip8.audio.volume = 100;
+    chip8.audio.waveform = crate::audio::Waveform::Square;
+    let mut buffer = [1.0; 8];
+    chip8.fill_audio(&mut buffer, 8000);
+    assert_eq!(buffer, [0.0; 8]);
+    chip8.set_sound(5);
+    chip8.fill_audio(&mut buffer, 8000);
+    assert_eq!(buffer, [1.0; 8]); // 440Hz at 8000Hz is about 18 samples per cycle
+}
 
-    /// draw(Vx,Vy,N)
-    fn draw(&mut self, x: u8, y: u8, n: u8) {
-        let vx = self.registers[x as usize];
-        let vy = self.registers[y as usize];
-        self.memory[VF] = 0;
-        for r in 0..n {
-            let row = self.memory[(self.i + r as u16) as usize];
-            let screen_y = ((vy + r) % 32) as usize;
-            for col in 0..8 {
-                let val = (row & 0x80 >> col) > 0;
-                let screen_x = ((vx + col) % 64) as usize;
-                if val & self.display[screen_y][screen_x] != self.display[screen_y][screen_x] {
-                    self.memory[VF] = 1;
-                }
-                self.display[screen_y][screen_x] ^= val;
-            }
-        }
-        self.draw_flag = true;
-    }
+#[test]
+fn test_xo_chip_audio() {
+    let mut chip8 = CHIP8::headless();
+    chip8.xo_chip = true;
+    chip8.load_and_run("testbin/xo_audio.chip8").unwrap();
+    assert_eq!(chip8.audio.pattern[..4], [0xFF, 0x00, 0xF0, 0x0F]);
+    assert_eq!(chip8.audio.pattern[4..8], [0xAA, 0x55, 0x01, 0x80]);
+    assert_eq!(chip8.audio.pattern[8..], [0; 8]);
+    assert_eq!(chip8.audio.pitch, 0x70);
+}
 
-    /// if(key()==Vx)
-    fn skip_if_key_pressed(&mut self, x: u8) {
-        if self.keys[self.registers[x as usize] as usize] {
-            self.position_in_memory += 2;
-        }
-    }
+#[test]
+fn test_xo_chip_audio_needs_xo_chip_mode() {
+    let mut chip8 = CHIP8::headless();
+    let result = chip8.load_and_run("testbin/xo_audio.chip8");
+    assert_eq!(
+        result,
+        Err(Error::UnknownOpcode {
+            pc: 0x202,
+            opcode: 0xF002
+        })
+    );
+}
 
-    /// if(key()!=Vx)
-    fn skip_if_key_not_pressed(&mut self, x: u8) {
-        if !self.keys[self.registers[x as usize] as usize] {
-            self.position_in_memory += 2;
-        }
-    }
+#[test]
+fn test_xo_chip_store_and_load_range() {
+    let mut chip8 = CHIP8::headless();
+    chip8.xo_chip = true;
+    chip8.registers[1..4].copy_from_slice(&[1, 2, 3]);
+    chip8.i = 0x300;
+    chip8.execute_opcode(0x5132).unwrap(); // V1 to V3
+    assert_eq!(chip8.memory[0x300..0x304], [1, 2, 3, 0]);
+    chip8.execute_opcode(0x5312).unwrap(); // V3 down to V1
+    assert_eq!(chip8.memory[0x300..0x304], [3, 2, 1, 0]);
+    assert_eq!(chip8.i, 0x300);
 
-    /// Vx = get_delay()
-    fn set_x_to_delay_timer(&mut self, x: u8) {
-        self.registers[x as usize] = self.delay_timer;
-    }
+    chip8.execute_opcode(0x5463).unwrap(); // V4 to V6
+    assert_eq!(chip8.registers[4..7], [3, 2, 1]);
+    chip8.execute_opcode(0x5A83).unwrap(); // VA down to V8
+    assert_eq!(chip8.registers[8..11], [1, 2, 3]);
+    assert_eq!(chip8.i, 0x300);
 
-    /// Vx = get_key()
-    fn set_x_to_keypress(&mut self, x: u8) {
-        self.wait_for_keypress_and_set_keys();
-        for (pos, &key) in self.keys.iter().enumerate() {
-            if key {
-                self.registers[x as usize] = pos as u8;
-            }
-        }
-    }
+    chip8.xo_chip = false;
+    assert_eq!(
+        chip8.execute_opcode(0x5132),
+        Err(Error::UnknownOpcode {
+            pc: 0x208,
+            opcode: 0x5132
+        })
+    );
+}
 
-    /// Reads raw stdin and records key presses
-    /// Only the first key pressed is read. i.e. if '1' and '2' are both pressed, only '1' is set
-    /// Blocking operation that waits on a VALID key press
-    fn wait_for_keypress_and_set_keys(&mut self) {
-        let mut key_pressed = false;
-        self.window.update(); // Get current state before we check
-        while !key_pressed {
-            key_pressed = self.set_keys();
-        }
-    }
+#[test]
+fn test_cycles_per_frame() {
+    let mut chip8 = CHIP8::headless();
+    chip8
+        .load_and_run("testbin/count_until_delay.chip8")
+        .unwrap();
+    assert_eq!(chip8.register(0), 3); // 2 loops in the first 10 cycles, then the first of the second frame
+    assert_eq!(chip8.delay(), 254);
 
-    fn set_keys(&mut self) -> bool {
-        let mut key_pressed = false;
-        self.window.get_keys_pressed(KeyRepeat::No).iter().for_each(|key|
-            match key {
-                Key::Key1 => {
-                    self.keys[1] = true;
-                    key_pressed = true;
-                }
-                Key::Key2 => {
-                    self.keys[2] = true;
-                    key_pressed = true;
-                }
-                Key::Key3 => {
-                    self.keys[3] = true;
-                    key_pressed = true;
-                }
-                Key::Key4 => {
-                    self.keys[12] = true;
-                    key_pressed = true;
-                }
-                Key::Q => {
-                    self.keys[4] = true;
-                    key_pressed = true;
-                }
-                Key::W => {
-                    self.keys[5] = true;
-                    key_pressed = true;
-                }
-                Key::E => {
-                    self.keys[6] = true;
-                    key_pressed = true;
-                }
-                Key::R => {
-                    self.keys[13] = true;
-                    key_pressed = true;
-                }
-                Key::A => {
-                    self.keys[7] = true;
-                    key_pressed = true;
-                }
-                Key::S => {
-                    self.keys[8] = true;
-                    key_pressed = true;
-                }
-                Key::D => {
-                    self.keys[9] = true;
-                    key_pressed = true;
-                }
-                Key::F => {
-                    self.keys[14] = true;
-                    key_pressed = true;
-                }
-                Key::Z => {
-                    self.keys[10] = true;
-                    key_pressed = true;
-                }
-                Key::X => {
-                    self.keys[0] = true;
-                    key_pressed = true;
-                }
-                Key::C => {
-                    self.keys[11] = true;
-                    key_pressed = true;
-                }
-                Key::V => {
-                    self.keys[15] = true;
-                    key_pressed = true;
-                }
-                _ => {},
-            }
-        );
-        self.window.update(); // Update the window each time otherwise the state is static
-        key_pressed
-    }
+    let mut chip8 = CHIP8::headless();
+    chip8.cycles_per_frame = 20;
+    chip8
+        .load_and_run("testbin/count_until_delay.chip8")
+        .unwrap();
+    assert_eq!(chip8.register(0), 5);
+    assert_eq!(chip8.delay(), 254);
+}
 
-    /// delay_timer(Vx)
-    fn set_delay_timer_to_x(&mut self, x: u8) {
-        self.delay_timer = self.registers[x as usize];
-    }
+#[test]
+fn test_profile() {
+    let mut chip8 = CHIP8::headless();
+    chip8.profile = true;
+    chip8.load_and_run("testbin/add_xnn.chip8").unwrap();
+    assert_eq!(chip8.opcode_counts["6xnn"], 1);
+    assert_eq!(chip8.opcode_counts["7xnn"], 1);
+    assert_eq!(chip8.opcode_counts["0000"], 1);
+    assert_eq!(chip8.profile_summary().lines().count(), 3);
+}
 
-    /// sound_timer(Vx)
-    fn set_sound_timer_to_x(&mut self, x: u8) {
-        self.sound_timer = self.registers[x as usize];
-    }
+#[test]
+fn test_coverage_report() {
+    let mut chip8 = CHIP8::headless();
+    chip8.coverage = true;
+    // Calls a subroutine that runs ADD V0, V1 twice, twice
+    chip8.load_and_run("testbin/stack_math.chip8").unwrap();
+    let report = chip8.coverage_report();
+    assert!(report.contains("\n8xy4         4\n"));
+    assert!(report.contains("\n2nnn         2\n"));
+    assert!(report.contains("\n8xy5         -\n"));
+    assert!(report.ends_with("\n5 of 40 opcodes used\n"));
+    assert_eq!(report.lines().count(), 41);
+}
 
-    /// I +=Vx
-    fn add_ix(&mut self, x: u8) {
-        self.i += self.registers[x as usize] as u16;
-    }
+#[test]
+fn test_profile_disabled() {
+    let mut chip8 = CHIP8::headless();
+    chip8.load_and_run("testbin/add_xnn.chip8").unwrap();
+    assert!(chip8.opcode_counts.is_empty());
+}
 
-    /// I=sprite_addr[Vx]
-    fn set_i_sprite_addr_x(&mut self, x: u8) {
-        self.i = 0x50 + 5 * (self.registers[x as usize] as u16);
+#[test]
+fn test_breakpoint() {
+    let mut chip8 = CHIP8::headless();
+    chip8.breakpoints.insert(0x204);
+    chip8.load_into_memory("testbin/add_xy.chip8");
+    while !chip8.hit_breakpoint() {
+        chip8.emulate_cycle().unwrap();
     }
+    assert!(chip8.debug);
+    assert_eq!(chip8.position_in_memory, 0x204);
+    assert_eq!(chip8.registers[1], 10);
+    assert_eq!(chip8.registers[0], 5); // 8014 has not run yet
+}
 
-    /// set_BCD(Vx);
-    /// *(I+0)=BCD(3);
-    /// *(I+1)=BCD(2);
-    /// *(I+2)=BCD(1);
-    fn set_bcd(&mut self, x: u8) {
-        let vx = self.registers[x as usize];
-        self.memory[self.i as usize] = vx / 100;
-        self.memory[self.i as usize + 1] = (vx / 10) % 10;
-        self.memory[self.i as usize + 2] = (vx % 100) % 10;
-    }
+#[test]
+fn test_breakpoint_ignored_in_debug() {
+    let mut chip8 = CHIP8::headless();
+    chip8.breakpoints.insert(0x200);
+    chip8.debug = true;
+    assert!(!chip8.hit_breakpoint());
+}
 
-    /// reg_dump(Vx,&I)
-    fn reg_dump(&mut self, x: u8) {
-        self.memory[(self.i as usize)..(self.i + x as u16 + 1) as usize]
-            .copy_from_slice(&self.registers[0..(x as usize + 1)])
-    }
+#[test]
+fn test_rewind() {
+    let mut chip8 = CHIP8::headless();
+    chip8.load_into_memory("testbin/add_xnn.chip8");
+    chip8.step_with_history().unwrap();
+    chip8.step_with_history().unwrap();
+    assert_eq!(chip8.position_in_memory, 0x204);
+    assert_eq!(chip8.registers[0], 10);
+    assert!(chip8.rewind());
+    assert_eq!(chip8.position_in_memory, 0x202);
+    assert_eq!(chip8.registers[0], 5);
+}
 
-    /// reg_load(Vx,&I)
-    fn reg_load(&mut self, x: u8) {
-        self.registers[0..x as usize + 1]
-            .copy_from_slice(&self.memory[(self.i as usize)..(self.i + x as u16 + 1) as usize]);
-    }
+#[test]
+fn test_rewind_draw_and_memory() {
+    let mut chip8 = CHIP8::headless();
+    chip8.load_into_memory("testbin/draw.chip8");
+    chip8.step_with_history().unwrap();
+    chip8.step_with_history().unwrap();
+    assert!(chip8.display[0][2]);
+    assert!(chip8.rewind());
+    assert!(!chip8.display[0][2]);
+    assert!(chip8.display.cells().iter().all(|&pixel| !pixel));
+    assert!(chip8.rewind());
+    assert_eq!(chip8.i, 0);
+    assert!(!chip8.rewind());
+}
 
-    /// Loads the specified chip8 program into memory
-    pub fn load_into_memory(&mut self, file: &str) {
-        self.load_fonts();
-        let mut buffer = [0u8; 3584];
-        match File::open(file) {
-            Ok(mut file) => match file.read(&mut buffer[..]) {
-                Ok(_bytes) => {
-                    self.memory[0x200..].copy_from_slice(&buffer);
-                }
-                Err(err) => eprintln!("Error reading file: {}", err),
-            },
-            Err(err) => eprintln!("Error opening file: {}", err),
-        }
-    }
+#[test]
+fn test_rewind_depth() {
+    let mut chip8 = CHIP8::headless();
+    chip8.rewind_depth = 1;
+    chip8.load_into_memory("testbin/add_xnn.chip8");
+    chip8.step_with_history().unwrap();
+    chip8.step_with_history().unwrap();
+    assert!(chip8.rewind());
+    assert!(!chip8.rewind());
+}
 
-    fn load_fonts(&mut self) {
-        let fonts: [u8; 80] = [
-            0xf0, 0x90, 0x90, 0x90, 0xf0, 0x20, 0x60, 0x20, 0x20, 0x70, 0xf0, 0x10, 0xf0, 0x80,
-            0xf0, 0xf0, 0x10, 0xf0, 0x10, 0xf0, 0x90, 0x90, 0xf0, 0x10, 0x10, 0xf0, 0x80, 0xf0,
-            0x10, 0xf0, 0xf0, 0x80, 0xf0, 0x90, 0xf0, 0xf0, 0x10, 0x20, 0x40, 0x40, 0xf0, 0x90,
-            0xf0, 0x90, 0xf0, 0xf0, 0x90, 0xf0, 0x10, 0xf0, 0xf0, 0x90, 0xf0, 0x90, 0x90, 0xe0,
-            0x90, 0xe0, 0x90, 0xe0, 0xf0, 0x80, 0x80, 0x80, 0xf0, 0xe0, 0x90, 0x90, 0x90, 0xe0,
-            0xf0, 0x80, 0xf0, 0x80, 0xf0, 0xf0, 0x80, 0xf0, 0x80, 0x80,
-        ];
+#[test]
+fn test_stack_overflow() {
+    let mut chip8 = CHIP8::headless();
+    // 0x200 calls itself until the stack runs out
+    let result = chip8.load_and_run("testbin/stack_overflow.chip8");
+    assert_eq!(result, Err(Error::StackOverflow { pc: 0x200 }));
+    assert_eq!(chip8.stack_pointer, 16);
+}
 
-        // 0x50 is the font offset
-        // http://www.multigesture.net/articles/how-to-write-an-emulator-chip-8-interpreter/
-        self.memory[0x50..0xA0].copy_from_slice(&fonts);
-    }
+#[test]
+fn test_stack_underflow() {
+    let mut chip8 = CHIP8::headless();
+    let result = chip8.load_and_run("testbin/stack_underflow.chip8");
+    assert_eq!(result, Err(Error::StackUnderflow { pc: 0x200 }));
+    assert_eq!(chip8.stack_pointer, 0);
+}
 
-    /// Loads a specified Chip8 program into memory and then runs
-    pub fn load_and_run(&mut self, file: &str) {
-        self.load_into_memory(file);
-        self.run();
-    }
+#[test]
+fn test_pc_wraps_at_end_of_memory() {
+    let mut chip8 = CHIP8::headless();
+    chip8.load_into_memory("testbin/jump_to_top.chip8");
+    chip8.memory[0xFFE] = 0x60; // LD V0, 0x07
+    chip8.memory[0xFFF] = 0x07;
+    chip8.run().unwrap();
+    // Wrapped around to 0x000, which halts since it's empty
+    assert_eq!(chip8.registers[0], 7);
+    assert_eq!(chip8.position_in_memory, 0x002);
 }
 
 #[test]
-fn test_clear_screen() {
-    let mut chip8 = CHIP8::new();
-    chip8.display[0][0] = true;
-    chip8.load_and_run("testbin/clear_screen.chip8");
-    assert_eq!(chip8.display[0][0], false);
+fn test_halt_on_pc_wrap() {
+    let mut chip8 = CHIP8::headless();
+    chip8.halt_on_pc_wrap = true;
+    chip8.load_into_memory("testbin/jump_to_top.chip8");
+    chip8.memory[0xFFE] = 0x60; // LD V0, 0x07
+    chip8.memory[0xFFF] = 0x07;
+    assert_eq!(chip8.run(), Err(Error::PcOutOfRange { pc: 0x1000 }));
+    assert_eq!(chip8.registers[0], 7);
 }
 
 #[test]
 fn test_skip_if_equal_iseq() {
-    let mut chip8 = CHIP8::new();
-    chip8.load_and_run("testbin/is_eq.chip8");
+    let mut chip8 = CHIP8::headless();
+    chip8.load_and_run("testbin/is_eq.chip8").unwrap();
     assert_eq!(chip8.registers[0], 5);
 }
 
 #[test]
 fn test_skip_if_equal_noteq() {
-    let mut chip8 = CHIP8::new();
-    chip8.load_and_run("testbin/not_eq.chip8");
+    let mut chip8 = CHIP8::headless();
+    chip8.load_and_run("testbin/not_eq.chip8").unwrap();
     assert_eq!(chip8.registers[0], 6);
 }
 
 #[test]
 fn test_skip_if_not_equal_iseq() {
-    let mut chip8 = CHIP8::new();
-    chip8.load_and_run("testbin/skip_not_eq_iseq.chip8");
+    let mut chip8 = CHIP8::headless();
+    chip8
+        .load_and_run("testbin/skip_not_eq_iseq.chip8")
+        .unwrap();
     assert_eq!(chip8.registers[0], 6);
 }
 
 #[test]
 fn test_skip_if_not_equal_neq() {
-    let mut chip8 = CHIP8::new();
-    chip8.load_and_run("testbin/skip_not_eq_neq.chip8");
+    let mut chip8 = CHIP8::headless();
+    chip8.load_and_run("testbin/skip_not_eq_neq.chip8").unwrap();
     assert_eq!(chip8.registers[0], 5);
 }
 
 #[test]
 fn test_skip_xy_equal_eq() {
-    let mut chip8 = CHIP8::new();
-    chip8.load_and_run("testbin/xy_eq.chip8");
+    let mut chip8 = CHIP8::headless();
+    chip8.load_and_run("testbin/xy_eq.chip8").unwrap();
     assert_eq!(chip8.registers[0], 5);
 }
 
 #[test]
 fn test_skip_xy_equal_neq() {
-    let mut chip8 = CHIP8::new();
-    chip8.load_and_run("testbin/xy_neq.chip8");
+    let mut chip8 = CHIP8::headless();
+    chip8.load_and_run("testbin/xy_neq.chip8").unwrap();
     assert_eq!(chip8.registers[0], 6);
 }
 
 #[test]
 fn test_skip_xy_not_equal_eq() {
-    let mut chip8 = CHIP8::new();
-    chip8.load_and_run("testbin/xy_neq_eq.chip8");
+    let mut chip8 = CHIP8::headless();
+    chip8.load_and_run("testbin/xy_neq_eq.chip8").unwrap();
     assert_eq!(chip8.registers[0], 6);
 }
 
 #[test]
 fn test_skip_xy_not_equal_neq() {
-    let mut chip8 = CHIP8::new();
-    chip8.load_and_run("testbin/xy_neq_neq.chip8");
+    let mut chip8 = CHIP8::headless();
+    chip8.load_and_run("testbin/xy_neq_neq.chip8").unwrap();
     assert_eq!(chip8.registers[0], 5);
 }
 
 #[test]
 fn test_set_xnn() {
-    let mut chip8 = CHIP8::new();
-    chip8.load_and_run("testbin/set_registers.chip8");
+    let mut chip8 = CHIP8::headless();
+    chip8.load_and_run("testbin/set_registers.chip8").unwrap();
     assert_eq!(chip8.registers[0], 5);
     assert_eq!(chip8.registers[1], 10);
 }
 
 #[test]
 fn test_add_xnn() {
-    let mut chip8 = CHIP8::new();
-    chip8.load_and_run("testbin/add_xnn.chip8");
+    let mut chip8 = CHIP8::headless();
+    chip8.load_and_run("testbin/add_xnn.chip8").unwrap();
     assert_eq!(chip8.registers[0], 10);
 }
 
 #[test]
 fn test_assign_xy() {
-    let mut chip8 = CHIP8::new();
-    chip8.load_and_run("testbin/assign_xy.chip8");
+    let mut chip8 = CHIP8::headless();
+    chip8.load_and_run("testbin/assign_xy.chip8").unwrap();
     assert_eq!(chip8.registers[0], 6);
 }
 
 #[test]
 fn test_or_xy() {
-    let mut chip8 = CHIP8::new();
-    chip8.load_and_run("testbin/or_xy.chip8");
+    let mut chip8 = CHIP8::headless();
+    chip8.load_and_run("testbin/or_xy.chip8").unwrap();
     assert_eq!(chip8.registers[0], 255);
 }
 
 #[test]
 fn test_and_xy() {
-    let mut chip8 = CHIP8::new();
-    chip8.load_and_run("testbin/and_xy.chip8");
+    let mut chip8 = CHIP8::headless();
+    chip8.load_and_run("testbin/and_xy.chip8").unwrap();
     assert_eq!(chip8.registers[0], 0);
 }
 
 #[test]
 fn test_xor_xy() {
-    let mut chip8 = CHIP8::new();
-    chip8.load_and_run("testbin/xor_xy.chip8");
+    let mut chip8 = CHIP8::headless();
+    chip8.load_and_run("testbin/xor_xy.chip8").unwrap();
     assert_eq!(chip8.registers[0], 255);
 }
 
 #[test]
 fn test_add_xy() {
-    let mut chip8 = CHIP8::new();
-    chip8.load_and_run("testbin/add_xy.chip8");
+    let mut chip8 = CHIP8::headless();
+    chip8.load_and_run("testbin/add_xy.chip8").unwrap();
     assert_eq!(chip8.registers[0], 15);
 }
 
 #[test]
 fn test_sub_xy() {
-    let mut chip8 = CHIP8::new();
-    chip8.load_and_run("testbin/sub_xy.chip8");
+    let mut chip8 = CHIP8::headless();
+    chip8.load_and_run("testbin/sub_xy.chip8").unwrap();
     assert_eq!(chip8.registers[0], 5);
 }
 
 #[test]
 fn test_shift_right() {
-    let mut chip8 = CHIP8::new();
-    chip8.load_and_run("testbin/shift_right.chip8");
+    let mut chip8 = CHIP8::headless();
+    chip8.load_and_run("testbin/shift_right.chip8").unwrap();
     assert_eq!(chip8.registers[0], 2);
 }
 
 #[test]
 fn test_sub_yx() {
-    let mut chip8 = CHIP8::new();
-    chip8.load_and_run("testbin/sub_yx.chip8");
+    let mut chip8 = CHIP8::headless();
+    chip8.load_and_run("testbin/sub_yx.chip8").unwrap();
     assert_eq!(chip8.registers[0], 5);
 }
 
 #[test]
 fn test_shift_left() {
-    let mut chip8 = CHIP8::new();
+    let mut chip8 = CHIP8::headless();
     chip8.load_into_memory("testbin/shift_left.chip8");
-    chip8.run();
+    chip8.run().unwrap();
     assert_eq!(chip8.registers[0], 10);
 }
 
 #[test]
 fn test_set_16bit_register() {
-    let mut chip8 = CHIP8::new();
-    chip8.load_and_run("testbin/set_16bit_register.chip8");
+    let mut chip8 = CHIP8::headless();
+    chip8
+        .load_and_run("testbin/set_16bit_register.chip8")
+        .unwrap();
     assert_eq!(chip8.i, 10);
 }
 
 #[test]
 fn test_jump_nnn_plus_v0() {
-    let mut chip8 = CHIP8::new();
-    chip8.load_and_run("testbin/jump_nnn_plus_v0.chip8");
+    let mut chip8 = CHIP8::headless();
+    chip8
+        .load_and_run("testbin/jump_nnn_plus_v0.chip8")
+        .unwrap();
     assert_eq!(chip8.registers[1], 5); // We skipped 0x610A
 }
 
+#[test]
+fn test_quirk_shift_uses_vy() {
+    let mut chip8 = CHIP8::headless();
+    chip8.quirks.shift_uses_vy = true;
+    // LD V0, 0x01; LD V1, 0x06; SHR V0, V1; LD V2, 0x81; SHL V3, V2
+    chip8
+        .load_bytes(&[0x60, 0x01, 0x61, 0x06, 0x80, 0x16, 0x62, 0x81, 0x83, 0x2E])
+        .unwrap();
+    chip8.run().unwrap();
+    assert_eq!(chip8.registers[0], 3);
+    assert_eq!(chip8.registers[3], 2);
+}
+
+#[test]
+fn test_quirk_load_store_increment_i() {
+    let mut chip8 = CHIP8::headless();
+    chip8.quirks.load_store_increment_i = true;
+    // LD I, 0x300; LD [I], V2; LD V2, [I]
+    chip8
+        .load_bytes(&[0xA3, 0x00, 0xF2, 0x55, 0xF2, 0x65])
+        .unwrap();
+    chip8.run().unwrap();
+    assert_eq!(chip8.i, 0x306);
+}
+
+#[test]
+fn test_quirk_jump_uses_vx() {
+    let mut chip8 = CHIP8::headless();
+    chip8.quirks.jump_uses_vx = true;
+    // LD V0, 0x10; LD V2, 0x02; JP V2, 0x206 lands on LD V3, 0x07 instead of past the end
+    chip8
+        .load_bytes(&[0x60, 0x10, 0x62, 0x02, 0xB2, 0x06, 0x00, 0x00, 0x63, 0x07])
+        .unwrap();
+    chip8.run().unwrap();
+    assert_eq!(chip8.registers[3], 7);
+}
+
 #[test]
 fn test_rand() {
-    let mut chip8 = CHIP8::new();
+    let mut chip8 = CHIP8::headless();
     assert_eq!(chip8.registers[0], 0);
-    chip8.load_and_run("testbin/rand.chip8");
+    chip8.load_and_run("testbin/rand.chip8").unwrap();
     assert_ne!(chip8.registers[0], 0);
 }
 
+#[test]
+fn test_deterministic() {
+    let run = || {
+        let mut chip8 = CHIP8::headless();
+        chip8.deterministic = true;
+        chip8.set_seed(1234);
+        chip8.load_and_run("testbin/rand_to_memory.chip8").unwrap();
+        chip8
+    };
+    let first = run();
+    let second = run();
+    assert_eq!(first.memory[..], second.memory[..]);
+    assert_eq!(first.registers, second.registers);
+    assert_ne!(first.memory[0x300..0x303], [0; 3]);
+}
+
+#[test]
+fn test_unknown_opcode() {
+    let mut chip8 = CHIP8::headless();
+    let result = chip8.load_and_run("testbin/unknown_opcode.chip8");
+    assert_eq!(
+        result,
+        Err(Error::UnknownOpcode {
+            pc: 0x202,
+            opcode: 0x801F
+        })
+    );
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "unknown opcode 0x801f at 0x202"
+    );
+}
+
+#[test]
+fn test_sys_is_skipped() {
+    let mut chip8 = CHIP8::headless();
+    chip8.load_and_run("testbin/sys.chip8").unwrap();
+    assert_eq!(chip8.register(0), 5);
+    assert_eq!(chip8.pc(), 0x206);
+}
+
+#[test]
+fn test_validate_rom() {
+    let mut chip8 = CHIP8::headless();
+    chip8.load_into_memory("testbin/unknown_opcode.chip8");
+    assert_eq!(chip8.validate_rom(), vec![(0x202, 0x801F)]);
+
+    // The audio pattern after the HALT is read as opcodes too
+    chip8.load_into_memory("testbin/xo_audio.chip8");
+    let data = vec![(0x20a, 0xFF00), (0x20c, 0xF00F)];
+    assert_eq!(
+        chip8.validate_rom()[..2],
+        [(0x202, 0xF002), (0x206, 0xF03A)]
+    );
+    assert_eq!(chip8.validate_rom()[2..], data[..]);
+    chip8.xo_chip = true;
+    assert_eq!(chip8.validate_rom(), data);
+}
+
 #[test]
 fn test_draw() {
-    let mut chip8 = CHIP8::new();
-    chip8.load_and_run("testbin/draw.chip8");
+    let mut chip8 = CHIP8::headless();
+    chip8.load_and_run("testbin/draw.chip8").unwrap();
 
     // Checking if we drew this:
     //   ****
@@ -727,44 +3848,219 @@ fn test_draw() {
     assert_eq!(chip8.display[2][7], true);
 }
 
+#[test]
+fn test_draw_wraps_at_bottom_edge() {
+    let mut chip8 = CHIP8::headless();
+    chip8
+        .load_and_run("testbin/draw_bottom_edge.chip8")
+        .unwrap();
+    assert!(chip8.display[30][2]);
+    assert!(chip8.display[31][0]);
+    assert!(chip8.display[0].iter().take(8).all(|&pixel| pixel));
+}
+
+#[test]
+fn test_draw_clips_at_bottom_edge() {
+    let mut chip8 = CHIP8::headless();
+    chip8.quirks.clip_sprites = true;
+    chip8
+        .load_and_run("testbin/draw_bottom_edge.chip8")
+        .unwrap();
+    assert!(chip8.display[30][2]);
+    assert!(chip8.display[31][0]);
+    assert!(chip8.display[0].iter().all(|&pixel| !pixel));
+}
+
+#[test]
+fn test_draw_wait_vblank() {
+    let mut chip8 = CHIP8::headless();
+    chip8.quirks.draw_wait_vblank = true;
+    let start = Instant::now();
+    chip8.load_and_run("testbin/draw_twice.chip8").unwrap();
+    // The second draw can't happen until a frame after the first
+    assert!(start.elapsed() >= FRAME_DURATION);
+}
+
+#[test]
+fn test_persistence_fades_pixels() {
+    let mut chip8 = CHIP8::headless();
+    chip8.persistence = true;
+    chip8.display[0][0] = true;
+    chip8.fade_pixels();
+    assert_eq!(chip8.intensity[0][0], 255);
+
+    chip8.display[0][0] = false;
+    chip8.fade_pixels();
+    assert_eq!(chip8.intensity[0][0], 191);
+    chip8.fade_pixels();
+    assert_eq!(chip8.intensity[0][0], 127);
+    chip8.fade_pixels();
+    chip8.fade_pixels();
+    chip8.fade_pixels();
+    assert_eq!(chip8.intensity[0][0], 0);
+    assert!(!chip8.display[0][0]);
+}
+
+#[test]
+fn test_draw_graphics_buffer() {
+    // The frame built by pushing every pixel into a new Vec, as drawing used to
+    fn expected(chip8: &CHIP8) -> Vec<u32> {
+        let palette = chip8.color.palette();
+        let (off, on) = (palette.color(0), scale(palette.color(1), chip8.brightness));
+        let mut buf = Vec::new();
+        for i in 0..chip8.display.height() {
+            for j in 0..chip8.display.width() {
+                if chip8.display[i][j] {
+                    buf.push(on)
+                } else if chip8.persistence {
+                    buf.push(blend(on, off, chip8.intensity[i][j]))
+                } else {
+                    buf.push(off)
+                }
+            }
+        }
+        buf
+    }
+
+    let mut chip8 = CHIP8::headless();
+    chip8.deterministic = true;
+    chip8.persistence = true;
+    chip8.display[0][0] = true;
+    chip8.display[5][63] = true;
+    chip8.display[31][10] = true;
+    chip8.draw_graphics();
+    assert_eq!(chip8.last_presented, expected(&chip8));
+
+    // Fading pixels and a second frame drawn into the reused buffer
+    chip8.display[0][0] = false;
+    chip8.display[20][20] = true;
+    chip8.draw_graphics();
+    assert_eq!(chip8.last_presented, expected(&chip8));
+    assert_eq!(chip8.last_presented.len(), WIDTH * HEIGHT);
+
+    chip8.persistence = false;
+    chip8.display.clear();
+    chip8.draw_graphics();
+    assert_eq!(chip8.last_presented, expected(&chip8));
+}
+
+#[test]
+fn test_inverted() {
+    let mut chip8 = CHIP8::headless();
+    chip8.inverted = true;
+    chip8.display[0][0] = true;
+    chip8.draw_graphics();
+    assert_eq!(chip8.last_presented[0], 0x000000);
+    assert_eq!(chip8.last_presented[1], Color::Purple.hex_color());
+    assert!(chip8.display[0][0]); // Only the colors change
+
+    // Toggling while running redraws straight away
+    let frames = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let frontend = ScriptedFrontend {
+        invert_toggles: vec![false, true].into(),
+        frames: frames.clone(),
+        ..ScriptedFrontend::default()
+    };
+    let mut chip8 = CHIP8::with_frontend(Box::new(frontend));
+    chip8.deterministic = true;
+    chip8.cycles_per_frame = 1;
+    chip8.load_bytes(&[0x12, 0x00]).unwrap();
+    chip8.max_cycles = Some(3);
+    chip8.run().unwrap_err();
+    assert!(chip8.inverted);
+    assert_eq!(frames.borrow().len(), 1);
+    assert!(frames.borrow()[0]
+        .iter()
+        .all(|&pixel| pixel == Color::Purple.hex_color()));
+}
+
 #[test]
 fn test_skip_if_key_pressed() {
-    let mut chip8 = CHIP8::new();
+    let mut chip8 = CHIP8::headless();
     chip8.keys[0] = true;
-    chip8.load_and_run("testbin/skip_if_key_pressed.chip8");
+    chip8
+        .load_and_run("testbin/skip_if_key_pressed.chip8")
+        .unwrap();
     assert_eq!(chip8.registers[1], 1); // Skips last operation
 }
 
 #[test]
 fn test_skip_if_key_pressed_not_pressed() {
-    let mut chip8 = CHIP8::new();
+    let mut chip8 = CHIP8::headless();
     chip8.keys[0] = false;
-    chip8.load_and_run("testbin/skip_if_key_pressed.chip8");
+    chip8
+        .load_and_run("testbin/skip_if_key_pressed.chip8")
+        .unwrap();
     assert_eq!(chip8.registers[1], 2); // Does not skip last operation
 }
 
+#[test]
+fn test_set_key() {
+    let mut chip8 = CHIP8::headless();
+    chip8.set_key(0, true).unwrap();
+    chip8
+        .load_and_run("testbin/skip_if_key_pressed.chip8")
+        .unwrap();
+    assert_eq!(chip8.register(1), 1); // Skips last operation
+}
+
+#[test]
+fn test_release_key() {
+    let mut chip8 = CHIP8::headless();
+    chip8.set_key(0, true).unwrap();
+    chip8.release_key(0).unwrap();
+    chip8
+        .load_and_run("testbin/skip_if_key_pressed.chip8")
+        .unwrap();
+    assert_eq!(chip8.register(1), 2); // Does not skip last operation
+}
+
+#[test]
+fn test_set_invalid_key() {
+    let mut chip8 = CHIP8::headless();
+    assert_eq!(chip8.set_key(16, true), Err(Error::InvalidKey { key: 16 }));
+}
+
+#[test]
+fn test_pressed_keys() {
+    let mut chip8 = CHIP8::headless();
+    chip8.set_key(0x1, true).unwrap();
+    chip8.set_key(0xF, true).unwrap();
+    let mut expected = [false; 16];
+    expected[0x1] = true;
+    expected[0xF] = true;
+    assert_eq!(chip8.pressed_keys(), expected);
+    chip8.release_key(0x1).unwrap();
+    expected[0x1] = false;
+    assert_eq!(chip8.pressed_keys(), expected);
+}
+
 #[test]
 fn test_skip_if_key_not_pressed_np() {
-    let mut chip8 = CHIP8::new();
+    let mut chip8 = CHIP8::headless();
     chip8.keys[0] = false;
-    chip8.load_and_run("testbin/skip_if_key_not_pressed.chip8");
+    chip8
+        .load_and_run("testbin/skip_if_key_not_pressed.chip8")
+        .unwrap();
     assert_eq!(chip8.registers[1], 1); // Skips last operation
 }
 
 #[test]
 fn test_skip_if_key_not_pressed_p() {
-    let mut chip8 = CHIP8::new();
+    let mut chip8 = CHIP8::headless();
     chip8.keys[0] = true;
-    chip8.load_and_run("testbin/skip_if_key_not_pressed.chip8");
+    chip8
+        .load_and_run("testbin/skip_if_key_not_pressed.chip8")
+        .unwrap();
     assert_eq!(chip8.registers[1], 2); // Does not skip last operation
 }
 
 #[test]
 fn test_set_timers() {
-    let mut chip8 = CHIP8::new();
+    let mut chip8 = CHIP8::headless();
     assert_eq!(chip8.sound_timer, 0);
     assert_eq!(chip8.delay_timer, 0);
-    chip8.load_and_run("testbin/timers.chip8");
+    chip8.load_and_run("testbin/timers.chip8").unwrap();
     assert_eq!(chip8.registers[0], 5);
     assert_eq!(chip8.delay_timer, 5);
     assert_eq!(chip8.sound_timer, 10);
@@ -773,46 +4069,158 @@ fn test_set_timers() {
 #[test]
 #[ignore] // Ignoring because this test waits for a keyboardinterrupt, pressing 'w' will make the test pass
 fn test_set_x_to_keypress() {
-    let mut chip8 = CHIP8::new();
-    chip8.load_and_run("testbin/set_x_to_key_press.chip8");
+    let mut chip8 = CHIP8::headless();
+    chip8
+        .load_and_run("testbin/set_x_to_key_press.chip8")
+        .unwrap();
     assert_eq!(chip8.registers[0], 5);
 }
 
 #[test]
 fn test_add_ix() {
-    let mut chip8 = CHIP8::new();
-    chip8.load_and_run("testbin/add_ix.chip8");
+    let mut chip8 = CHIP8::headless();
+    chip8.load_and_run("testbin/add_ix.chip8").unwrap();
     assert_eq!(chip8.registers[0], 5);
     assert_eq!(chip8.i, 5);
 }
 
+#[test]
+fn test_add_ix_overflow() {
+    let mut chip8 = CHIP8::headless();
+    chip8.i = 0x0FFE;
+    chip8.load_and_run("testbin/add_ix.chip8").unwrap();
+    assert_eq!(chip8.i, 0x003);
+    assert_eq!(chip8.registers[0x0f], 0);
+}
+
+#[test]
+fn test_add_ix_overflow_flag_quirk() {
+    let mut chip8 = CHIP8::headless();
+    chip8.quirks.i_add_overflow_flag = true;
+    chip8.i = 0x0FFE;
+    chip8.load_and_run("testbin/add_ix.chip8").unwrap();
+    assert_eq!(chip8.i, 0x003);
+    assert_eq!(chip8.registers[0x0f], 1);
+}
+
+#[test]
+fn test_add_ix_overflow_flag_quirk_no_overflow() {
+    let mut chip8 = CHIP8::headless();
+    chip8.quirks.i_add_overflow_flag = true;
+    chip8.registers[0x0f] = 1;
+    chip8.load_and_run("testbin/add_ix.chip8").unwrap();
+    assert_eq!(chip8.i, 5);
+    assert_eq!(chip8.registers[0x0f], 0);
+}
+
+#[test]
+fn test_add_ix_large_vx_stays_in_range() {
+    for flag_quirk in [false, true] {
+        let mut chip8 = CHIP8::headless();
+        chip8.quirks.i_add_overflow_flag = flag_quirk;
+        chip8.i = 0x0FF0;
+        chip8.registers[3] = 0xFF;
+        chip8.execute_opcode(0xF31E).unwrap();
+        assert_eq!(chip8.i, 0x0EF);
+        assert_eq!(chip8.registers[0x0f], flag_quirk as u8);
+        // I can be used to load straight away
+        chip8.execute_opcode(0xF365).unwrap();
+    }
+}
+
 #[test]
 fn test_set_i_sprite_addr_x() {
-    let mut chip8 = CHIP8::new();
-    chip8.load_and_run("testbin/set_i_to_sprite.chip8");
+    let mut chip8 = CHIP8::headless();
+    chip8.load_and_run("testbin/set_i_to_sprite.chip8").unwrap();
     assert_eq!(chip8.i, 130);
 }
 
 #[test]
 fn test_set_bcd() {
-    let mut chip8 = CHIP8::new();
+    let mut chip8 = CHIP8::headless();
     chip8.i = 0x600;
-    chip8.load_and_run("testbin/set_bcd.chip8");
+    chip8.load_and_run("testbin/set_bcd.chip8").unwrap();
 
     assert_eq!(chip8.memory[1536], 2);
     assert_eq!(chip8.memory[1537], 3);
     assert_eq!(chip8.memory[1538], 8);
 }
 
+#[test]
+fn test_watchpoint() {
+    let mut chip8 = CHIP8::headless();
+    chip8.i = 0x600;
+    chip8.watch_addr = Some(0x601);
+    chip8.load_into_memory("testbin/set_bcd.chip8");
+    chip8.emulate_cycle().unwrap();
+    assert!(!chip8.debug);
+    chip8.emulate_cycle().unwrap();
+    assert!(chip8.debug);
+    assert_eq!(chip8.memory[0x601], 3);
+}
+
+#[test]
+fn test_watchpoint_unchanged_value() {
+    let mut chip8 = CHIP8::headless();
+    chip8.i = 0x600;
+    chip8.watch_addr = Some(0x601);
+    chip8.load_into_memory("testbin/set_bcd.chip8");
+    chip8.memory[0x601] = 3;
+    chip8.emulate_cycle().unwrap();
+    chip8.emulate_cycle().unwrap();
+    assert!(!chip8.debug);
+}
+
+#[test]
+fn test_reg_dump_out_of_range() {
+    let mut chip8 = CHIP8::headless();
+    chip8.i = 0x0FFE;
+    let result = chip8.load_and_run("testbin/reg_dump_v3.chip8");
+    assert_eq!(
+        result,
+        Err(Error::AddressOutOfRange {
+            pc: 0x200,
+            addr: 0x1001
+        })
+    );
+    assert_eq!(chip8.memory[0xFFE], 0); // Nothing is written on error
+}
+
+#[test]
+fn test_set_bcd_out_of_range() {
+    let mut chip8 = CHIP8::headless();
+    chip8.i = 0x0FFE;
+    let result = chip8.load_and_run("testbin/set_bcd.chip8");
+    assert_eq!(
+        result,
+        Err(Error::AddressOutOfRange {
+            pc: 0x202,
+            addr: 0x1000
+        })
+    );
+}
+
 #[test]
 fn test_reg_dump() {}
 
 #[test]
 fn test_reg_load() {}
 
+#[test]
+fn test_missing_file_logs_error() {
+    static LOGGER: CaptureLogger = CaptureLogger;
+    let _ = log::set_logger(&LOGGER); // Already set when another test got here first
+    log::set_max_level(log::LevelFilter::Warn);
+    let mut chip8 = CHIP8::headless();
+    assert!(!chip8.load_into_memory("testbin/missing.chip8"));
+    let logged = LOGGED.with(|logged| logged.take());
+    assert_eq!(logged.len(), 1);
+    assert!(logged[0].starts_with("ERROR Error opening testbin/missing.chip8: "));
+}
+
 #[test]
 fn test_load_into_memory() {
-    let mut chip8 = CHIP8::new();
+    let mut chip8 = CHIP8::headless();
     chip8.load_into_memory("testbin/stack_math.chip8");
 
     // Check that everything is in place
@@ -844,9 +4252,40 @@ fn test_load_into_memory() {
     assert_eq!(chip8.memory[0x304], 0x00);
     assert_eq!(chip8.memory[0x305], 0xEE);
 
-    chip8.run();
+    chip8.run().unwrap();
 
     // Check the results in the registers
     assert_eq!(chip8.registers[1], 10);
     assert_eq!(chip8.registers[0], 45);
 }
+
+#[test]
+fn test_banner() {
+    let mut chip8 = CHIP8::headless();
+    chip8.color = Color::Green;
+    chip8.cycles_per_frame = 20;
+    chip8.quirks.clip_sprites = true;
+    chip8.quirks.draw_wait_vblank = true;
+    chip8.load_bytes(&[0x60, 0x05, 0x00, 0x00]).unwrap();
+    assert_eq!(
+        chip8.banner("roms/test.chip8"),
+        "ROM:         roms/test.chip8\n\
+         Size:        4 bytes\n\
+         Entry point: 200\n\
+         Quirks:      --quirk-clip --quirk-vblank\n\
+         Color:       green\n\
+         Speed:       1200 IPS\n"
+    );
+
+    chip8.palette = Some(Palette {
+        colors: [0x000000, 0xaf12e8, 0xffffff, 0xd789f4],
+    });
+    chip8.quirks = Quirks::default();
+    let banner = chip8.banner("test.chip8");
+    assert!(banner.contains("Quirks:      none\n"));
+    assert!(banner.contains("Color:       0x000000,0xaf12e8,0xffffff,0xd789f4\n"));
+
+    chip8.load_into_memory("examples/draw_a.chip8");
+    let banner = chip8.banner("examples/draw_a.chip8");
+    assert!(banner.ends_with("Game:        Draw A example, made for chip8\n"));
+}
@@ -1,8 +1,14 @@
+const OFF: u32 = 0x000000; // Black
+const WHITE: u32 = 0xffffff;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Color {
     Purple,
     Green,
     Red,
     Blue,
+    /// Any 0xRRGGBB color, written as #rrggbb
+    Custom(u32),
 }
 
 impl std::str::FromStr for Color {
@@ -13,7 +19,23 @@ impl std::str::FromStr for Color {
             "green" => Ok(Color::Green),
             "red" => Ok(Color::Red),
             "blue" => Ok(Color::Blue),
-            _ => Ok(Color::Purple),
+            _ => match s.strip_prefix('#').map(parse_hex_color) {
+                Some(Ok(color)) => Ok(Color::Custom(color)),
+                _ => Ok(Color::Purple),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Color {
+    /// The name FromStr parses back into the same color
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Color::Purple => write!(f, "purple"),
+            Color::Green => write!(f, "green"),
+            Color::Red => write!(f, "red"),
+            Color::Blue => write!(f, "blue"),
+            Color::Custom(color) => write!(f, "#{:06x}", color),
         }
     }
 }
@@ -25,6 +47,150 @@ impl Color {
             Color::Blue => 0x0000ff,
             Color::Green => 0x008000,
             Color::Red => 0xff0000,
+            Color::Custom(color) => *color,
+        }
+    }
+
+    /// The color with each channel multiplied by factor, clamped to 0xff
+    pub fn scaled(&self, factor: f32) -> u32 {
+        scale(self.hex_color(), factor)
+    }
+
+    /// Lit pixels in this color on black, with plane 2 in white and both planes in between
+    pub fn palette(&self) -> Palette {
+        let color = self.hex_color();
+        Palette {
+            colors: [OFF, color, WHITE, blend(color, WHITE, 128)],
+        }
+    }
+}
+
+/// The color for each pixel value, indexed by the planes it's lit in: off, plane 1, plane 2, both
+/// Single plane programs only use the first two
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Palette {
+    pub colors: [u32; 4],
+}
+
+impl Palette {
+    /// The color of a pixel lit in the given planes, bit 0 for plane 1 and bit 1 for plane 2
+    pub fn color(&self, planes: u8) -> u32 {
+        self.colors[planes as usize & 0b11]
+    }
+}
+
+impl std::str::FromStr for Palette {
+    type Err = String;
+    /// Parses four comma separated hex colors, e.g. `0x000000,0xaf12e8,0xffffff,0xd789f4`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let colors = s
+            .split(',')
+            .map(|color| parse_hex_color(color.trim()))
+            .collect::<Result<Vec<u32>, String>>()?;
+        match colors[..] {
+            [c0, c1, c2, c3] => Ok(Palette {
+                colors: [c0, c1, c2, c3],
+            }),
+            _ => Err(format!("expected 4 colors but got {}", colors.len())),
         }
     }
 }
+
+/// Parses a 0xRRGGBB color, the 0x is optional
+pub fn parse_hex_color(s: &str) -> Result<u32, String> {
+    let hex = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    match u32::from_str_radix(hex, 16) {
+        Ok(color) if color <= WHITE => Ok(color),
+        _ => Err(format!(
+            "invalid color {}, expected a hex color like 0xaf12e8",
+            s
+        )),
+    }
+}
+
+/// Mixes two 0xRRGGBB colors channel by channel
+/// An intensity of 255 gives fg and 0 gives bg
+pub fn blend(fg: u32, bg: u32, intensity: u8) -> u32 {
+    let intensity = intensity as u32;
+    [16, 8, 0].iter().fold(0, |color, shift| {
+        let fg_channel = (fg >> shift) & 0xff;
+        let bg_channel = (bg >> shift) & 0xff;
+        let channel = (fg_channel * intensity + bg_channel * (255 - intensity)) / 255;
+        color | channel << shift
+    })
+}
+
+/// Multiplies each channel of a 0xRRGGBB color by factor, clamping at 0xff
+pub fn scale(color: u32, factor: f32) -> u32 {
+    [16, 8, 0].iter().fold(0, |scaled, shift| {
+        let channel = ((color >> shift) & 0xff) as f32 * factor;
+        scaled | (channel.round().clamp(0.0, 255.0) as u32) << shift
+    })
+}
+
+#[test]
+fn test_blend() {
+    assert_eq!(blend(0xaf12e8, 0x000000, 255), 0xaf12e8);
+    assert_eq!(blend(0xaf12e8, 0x000000, 0), 0x000000);
+    assert_eq!(blend(0xff8000, 0x000000, 128), 0x804000);
+}
+
+#[test]
+fn test_palette() {
+    let palette: Palette = "0x000000, 0xff0000,00ff00,0x0000FF".parse().unwrap();
+    assert_eq!(palette.color(0), 0x000000);
+    assert_eq!(palette.color(1), 0xff0000);
+    assert_eq!(palette.color(2), 0x00ff00);
+    assert_eq!(palette.color(3), 0x0000ff);
+    assert!("0x000000,0xff0000".parse::<Palette>().is_err());
+    assert!("0x000000,0xff0000,0x00ff00,purple"
+        .parse::<Palette>()
+        .is_err());
+    assert!("0x000000,0xff0000,0x00ff00,0x1000000"
+        .parse::<Palette>()
+        .is_err());
+
+    let palette = Color::Red.palette();
+    assert_eq!(palette.color(0), 0x000000);
+    assert_eq!(palette.color(1), 0xff0000);
+}
+
+#[test]
+fn test_scaled() {
+    assert_eq!(Color::Purple.scaled(1.0), 0xaf12e8);
+    assert_eq!(Color::Purple.scaled(0.5), 0x580974);
+    assert_eq!(Color::Purple.scaled(2.0), 0xff24ff); // 0xaf and 0xe8 are clamped
+    assert_eq!(Color::Blue.scaled(0.0), 0x000000);
+}
+
+#[test]
+fn test_color_derives() {
+    assert_eq!(format!("{:?}", Color::Purple), "Purple");
+    assert_eq!(format!("{:?}", Color::Green), "Green");
+    assert_eq!(format!("{:?}", Color::Red), "Red");
+    assert_eq!(format!("{:?}", Color::Blue), "Blue");
+    let color = Color::Green;
+    let copy = color;
+    assert_eq!(color, copy);
+    assert_ne!(Color::Red, Color::Blue);
+}
+
+#[test]
+fn test_color_display_round_trips() {
+    let colors = [
+        Color::Purple,
+        Color::Green,
+        Color::Red,
+        Color::Blue,
+        Color::Custom(0x00ff88),
+    ];
+    for &color in colors.iter() {
+        assert_eq!(color.to_string().parse(), Ok(color));
+    }
+    assert_eq!(Color::Custom(0x00ff88).to_string(), "#00ff88");
+    assert_eq!("#FF8000".parse::<Color>().unwrap().hex_color(), 0xff8000);
+    assert_eq!("#orange".parse(), Ok(Color::Purple));
+}
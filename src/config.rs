@@ -0,0 +1,152 @@
+use crate::color::{Color, Palette};
+use crate::options::Options;
+use crate::quirks::{Platform, QuirkOverrides, Quirks};
+use serde::Deserialize;
+use std::path::PathBuf;
+
+/// Defaults for the command line options, read from a TOML file
+/// Anything left out keeps its built in default, and command line flags override the file
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// purple, green, red, blue, or #rrggbb
+    pub color: Option<String>,
+    /// Four comma separated hex colors, like --palette
+    pub palette: Option<String>,
+    pub brightness: Option<f32>,
+    pub cycles_per_frame: Option<usize>,
+    /// chip8, schip, or xochip
+    pub platform: Option<String>,
+    pub quirks: QuirksConfig,
+    pub keys: KeysConfig,
+}
+
+/// Quirks to turn on or off, named after their --quirk-* flags
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuirksConfig {
+    pub i_overflow: Option<bool>,
+    pub clip: Option<bool>,
+    pub vblank: Option<bool>,
+    pub shift_vy: Option<bool>,
+    pub load_store_i: Option<bool>,
+    pub jump_vx: Option<bool>,
+}
+
+/// Debug keys by name, like --step-key
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeysConfig {
+    pub step: Option<String>,
+    pub quit: Option<String>,
+    pub resume: Option<String>,
+    #[serde(rename = "continue")]
+    pub run_to_breakpoint: Option<String>,
+    pub draw: Option<String>,
+    pub save: Option<String>,
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Config, String> {
+        toml::from_str(text).map_err(|err| err.to_string())
+    }
+
+    /// chip8.toml in the working directory, or failing that ~/.config/chip8/config.toml
+    pub fn find() -> Option<PathBuf> {
+        let local = PathBuf::from("chip8.toml");
+        if local.is_file() {
+            return Some(local);
+        }
+        let home = std::env::var_os("HOME")?;
+        let user = PathBuf::from(home)
+            .join(".config")
+            .join("chip8")
+            .join("config.toml");
+        if user.is_file() {
+            Some(user)
+        } else {
+            None
+        }
+    }
+
+    /// Reads the config file if there is one, otherwise every option keeps its built in default
+    pub fn load() -> Result<Config, String> {
+        match Config::find() {
+            Some(path) => {
+                let text = std::fs::read_to_string(&path).map_err(|err| err.to_string())?;
+                Config::parse(&text).map_err(|err| format!("{}: {}", path.display(), err))
+            }
+            None => Ok(Config::default()),
+        }
+    }
+
+    /// Sets the options the file gives, leaving the rest as they are
+    pub fn apply(&self, options: &mut Options) -> Result<(), String> {
+        if let Some(Ok(color)) = self.color.as_ref().map(|color| color.parse::<Color>()) {
+            options.color = color;
+        }
+        if let Some(palette) = &self.palette {
+            options.palette = Some(palette.parse::<Palette>()?);
+        }
+        if let Some(brightness) = self.brightness {
+            if !(0.0..=2.0).contains(&brightness) {
+                return Err(format!(
+                    "invalid brightness {}, expected 0.0 to 2.0",
+                    brightness
+                ));
+            }
+            options.brightness = brightness;
+        }
+        if let Some(cycles_per_frame) = self.cycles_per_frame {
+            options.cycles_per_frame = cycles_per_frame;
+        }
+        if let Some(platform) = &self.platform {
+            options.quirks = Quirks::for_platform(platform.parse::<Platform>()?);
+        }
+        options.quirks.apply(&QuirkOverrides {
+            i_overflow: self.quirks.i_overflow,
+            clip: self.quirks.clip,
+            vblank: self.quirks.vblank,
+            shift_vy: self.quirks.shift_vy,
+            load_store_i: self.quirks.load_store_i,
+            jump_vx: self.quirks.jump_vx,
+        });
+        Ok(())
+    }
+}
+
+#[test]
+fn test_parse_config() {
+    let config = Config::parse(
+        r##"
+        color = "#ff8000"
+        brightness = 0.8
+        cycles_per_frame = 20
+        platform = "schip"
+
+        [quirks]
+        vblank = true
+        clip = false
+
+        [keys]
+        step = "Space"
+        "##,
+    )
+    .unwrap();
+    assert_eq!(config.keys.step, Some("Space".to_string()));
+    assert_eq!(config.keys.quit, None);
+
+    let mut options = Options::default();
+    config.apply(&mut options).unwrap();
+    assert_eq!(options.color, Color::Custom(0xff8000));
+    assert_eq!(options.brightness, 0.8);
+    assert_eq!(options.cycles_per_frame, 20);
+    assert_eq!(options.palette, None);
+    assert!(options.quirks.jump_uses_vx); // From the schip platform
+    assert!(options.quirks.draw_wait_vblank);
+    assert!(!options.quirks.clip_sprites); // Turned off though schip turns it on
+
+    assert!(Config::parse("speed = 2").is_err());
+    let bad_platform = Config::parse("platform = \"chip48\"").unwrap();
+    assert!(bad_platform.apply(&mut Options::default()).is_err());
+}
@@ -0,0 +1,214 @@
+use crate::instruction::{decode, Instruction};
+
+/// Decodes a single opcode into a human readable mnemonic
+/// Mnemonics follow Cowgod's Chip-8 technical reference, e.g. `LD V0, 0x05` or `DRW V0, V1, 5`
+pub fn disassemble(opcode: u16) -> String {
+    use Instruction::*;
+    match decode(opcode) {
+        Halt => "HALT".to_string(),
+        ClearScreen => "CLS".to_string(),
+        Return => "RET".to_string(),
+        Sys(nnn) => format!("SYS 0x{:03x}", nnn),
+        Jump(nnn) => format!("JP 0x{:03x}", nnn),
+        Call(nnn) => format!("CALL 0x{:03x}", nnn),
+        SkipIfEqual(x, nn) => format!("SE V{:X}, 0x{:02x}", x, nn),
+        SkipIfNotEqual(x, nn) => format!("SNE V{:X}, 0x{:02x}", x, nn),
+        SkipXyEqual(x, y) => format!("SE V{:X}, V{:X}", x, y),
+        StoreRange(x, y) => format!("SAVE V{:X}, V{:X}", x, y),
+        LoadRange(x, y) => format!("LOAD V{:X}, V{:X}", x, y),
+        SetXnn(x, nn) => format!("LD V{:X}, 0x{:02x}", x, nn),
+        AddXnn(x, nn) => format!("ADD V{:X}, 0x{:02x}", x, nn),
+        AssignXy(x, y) => format!("LD V{:X}, V{:X}", x, y),
+        OrXy(x, y) => format!("OR V{:X}, V{:X}", x, y),
+        AndXy(x, y) => format!("AND V{:X}, V{:X}", x, y),
+        XorXy(x, y) => format!("XOR V{:X}, V{:X}", x, y),
+        AddXy(x, y) => format!("ADD V{:X}, V{:X}", x, y),
+        SubXy(x, y) => format!("SUB V{:X}, V{:X}", x, y),
+        ShiftRight(x, _) => format!("SHR V{:X}", x),
+        SubYx(x, y) => format!("SUBN V{:X}, V{:X}", x, y),
+        ShiftLeft(x, _) => format!("SHL V{:X}", x),
+        SkipXyNotEqual(x, y) => format!("SNE V{:X}, V{:X}", x, y),
+        SetI(nnn) => format!("LD I, 0x{:03x}", nnn),
+        JumpPlusV0(nnn) => format!("JP V0, 0x{:03x}", nnn),
+        Rand(x, nn) => format!("RND V{:X}, 0x{:02x}", x, nn),
+        Draw(x, y, n) => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        SkipIfKeyPressed(x) => format!("SKP V{:X}", x),
+        SkipIfKeyNotPressed(x) => format!("SKNP V{:X}", x),
+        LoadAudioPattern => "AUDIO".to_string(),
+        SetXToDelayTimer(x) => format!("LD V{:X}, DT", x),
+        SetXToKeypress(x) => format!("LD V{:X}, K", x),
+        SetDelayTimer(x) => format!("LD DT, V{:X}", x),
+        SetSoundTimer(x) => format!("LD ST, V{:X}", x),
+        AddIx(x) => format!("ADD I, V{:X}", x),
+        SetISpriteAddr(x) => format!("LD F, V{:X}", x),
+        SetBcd(x) => format!("LD B, V{:X}", x),
+        SetPitch(x) => format!("PITCH V{:X}", x),
+        RegDump(x) => format!("LD [I], V{:X}", x),
+        RegLoad(x) => format!("LD V{:X}, [I]", x),
+        Unknown(_) => "UNKNOWN".to_string(),
+    }
+}
+
+/// Disassembles a whole program into assembly that assembles back into the same bytes
+/// Opcodes that can't be written with a mnemonic are written as `DB` bytes instead: unknown opcodes,
+/// 8xy6 and 8xyE with a Vy other than V0 since SHR and SHL only name Vx,
+/// and 5xyn and 9xyn with an n other than 0 since SE and SNE have no n
+pub fn disassemble_program(program: &[u8]) -> String {
+    let mut source = String::new();
+    for word in program.chunks(2) {
+        let line = match *word {
+            [high, low] => {
+                let opcode = (high as u16) << 8 | low as u16;
+                let mnemonic = disassemble(opcode);
+                if mnemonic == "UNKNOWN" || !has_mnemonic(opcode) {
+                    format!("DB 0x{:02x}, 0x{:02x}", high, low)
+                } else {
+                    mnemonic
+                }
+            }
+            _ => format!("DB 0x{:02x}", word[0]),
+        };
+        source.push_str(&line);
+        source.push('\n');
+    }
+    source
+}
+
+/// Whether the opcode's mnemonic says everything about it, see disassemble_program
+fn has_mnemonic(opcode: u16) -> bool {
+    match opcode & 0xF00F {
+        0x8006 | 0x800E => opcode & 0x00F0 == 0,
+        _ => true,
+    }
+}
+
+/// Groups an opcode into its instruction class, e.g. `0x8014` -> `8xy4`
+pub fn opcode_class(opcode: u16) -> &'static str {
+    use Instruction::*;
+    match decode(opcode) {
+        Halt => "0000",
+        ClearScreen => "00E0",
+        Return => "00EE",
+        Sys(_) => "0nnn",
+        Jump(_) => "1nnn",
+        Call(_) => "2nnn",
+        SkipIfEqual(..) => "3xnn",
+        SkipIfNotEqual(..) => "4xnn",
+        SkipXyEqual(..) => "5xy0",
+        StoreRange(..) => "5xy2",
+        LoadRange(..) => "5xy3",
+        SetXnn(..) => "6xnn",
+        AddXnn(..) => "7xnn",
+        AssignXy(..) => "8xy0",
+        OrXy(..) => "8xy1",
+        AndXy(..) => "8xy2",
+        XorXy(..) => "8xy3",
+        AddXy(..) => "8xy4",
+        SubXy(..) => "8xy5",
+        ShiftRight(..) => "8xy6",
+        SubYx(..) => "8xy7",
+        ShiftLeft(..) => "8xyE",
+        SkipXyNotEqual(..) => "9xy0",
+        SetI(_) => "Annn",
+        JumpPlusV0(_) => "Bnnn",
+        Rand(..) => "Cxnn",
+        Draw(..) => "Dxyn",
+        SkipIfKeyPressed(_) => "Ex9E",
+        SkipIfKeyNotPressed(_) => "ExA1",
+        LoadAudioPattern => "F002",
+        SetXToDelayTimer(_) => "Fx07",
+        SetXToKeypress(_) => "Fx0A",
+        SetDelayTimer(_) => "Fx15",
+        SetSoundTimer(_) => "Fx18",
+        AddIx(_) => "Fx1E",
+        SetISpriteAddr(_) => "Fx29",
+        SetBcd(_) => "Fx33",
+        SetPitch(_) => "Fx3A",
+        RegDump(_) => "Fx55",
+        RegLoad(_) => "Fx65",
+        Unknown(_) => "unknown",
+    }
+}
+
+/// Every class opcode_class returns for a real instruction, in opcode order
+pub const OPCODE_CLASSES: [&str; 40] = [
+    "0000", "00E0", "00EE", "0nnn", "1nnn", "2nnn", "3xnn", "4xnn", "5xy0", "5xy2", "5xy3", "6xnn",
+    "7xnn", "8xy0", "8xy1", "8xy2", "8xy3", "8xy4", "8xy5", "8xy6", "8xy7", "8xyE", "9xy0", "Annn",
+    "Bnnn", "Cxnn", "Dxyn", "Ex9E", "ExA1", "F002", "Fx07", "Fx0A", "Fx15", "Fx18", "Fx1E", "Fx29",
+    "Fx33", "Fx3A", "Fx55", "Fx65",
+];
+
+#[test]
+fn test_opcode_classes() {
+    for class in OPCODE_CLASSES {
+        // Filling in the operands gives an opcode of the class
+        let opcode = class.replace('x', "1").replace('y', "2").replace('n', "3");
+        let opcode = u16::from_str_radix(&opcode, 16).unwrap();
+        assert_eq!(opcode_class(opcode), class);
+    }
+}
+
+#[test]
+fn test_disassemble() {
+    assert_eq!(disassemble(0x0000), "HALT");
+    assert_eq!(disassemble(0x00E0), "CLS");
+    assert_eq!(disassemble(0x0123), "SYS 0x123");
+    assert_eq!(disassemble(0x1206), "JP 0x206");
+    assert_eq!(disassemble(0x6005), "LD V0, 0x05");
+    assert_eq!(disassemble(0x8014), "ADD V0, V1");
+    assert_eq!(disassemble(0xD015), "DRW V0, V1, 5");
+    assert_eq!(disassemble(0xFA65), "LD VA, [I]");
+    assert_eq!(disassemble(0xF002), "AUDIO");
+    assert_eq!(disassemble(0xF13A), "PITCH V1");
+    assert_eq!(disassemble(0x5312), "SAVE V3, V1");
+}
+
+#[test]
+fn test_disassemble_unknown() {
+    assert_eq!(disassemble(0x801F), "UNKNOWN");
+    assert_eq!(disassemble(0xE0FF), "UNKNOWN");
+}
+
+#[test]
+fn test_opcode_class() {
+    assert_eq!(opcode_class(0x6005), "6xnn");
+    assert_eq!(opcode_class(0x8014), "8xy4");
+    assert_eq!(opcode_class(0xF11E), "Fx1E");
+    assert_eq!(opcode_class(0x801F), "unknown");
+}
+
+#[test]
+fn test_disassemble_program_round_trips() {
+    for entry in std::fs::read_dir("testbin").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().and_then(|ext| ext.to_str()) == Some("chip8") {
+            let program = std::fs::read(&path).unwrap();
+            let source = disassemble_program(&program);
+            assert_eq!(
+                crate::assembler::assemble(&source),
+                Ok(program),
+                "{}",
+                path.display()
+            );
+        }
+    }
+}
+
+#[test]
+fn test_every_opcode_round_trips() {
+    for opcode in 0..=0xFFFF {
+        let program = (opcode as u16).to_be_bytes().to_vec();
+        let source = disassemble_program(&program);
+        assert_eq!(
+            crate::assembler::assemble(&source),
+            Ok(program),
+            "{}",
+            source
+        );
+    }
+    assert_eq!(disassemble_program(&[0x81, 0x26]), "DB 0x81, 0x26\n"); // SHR V1 would lose V2
+    assert_eq!(
+        disassemble_program(&[0x61, 0x05, 0xFF]),
+        "LD V1, 0x05\nDB 0xff\n"
+    );
+}
@@ -0,0 +1,68 @@
+use std::fmt;
+
+/// Errors that stop a program from executing
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// A subroutine was called with every stack slot already in use
+    StackOverflow { pc: usize },
+    /// A return was made without a matching call
+    StackUnderflow { pc: usize },
+    /// An instruction tried to access memory past the end through I
+    AddressOutOfRange { pc: usize, addr: usize },
+    /// A key outside of the 16 key keypad was pressed or released
+    InvalidKey { key: usize },
+    /// The opcode isn't a CHIP8 instruction, usually because data is being run as code
+    UnknownOpcode { pc: usize, opcode: u16 },
+    /// The program ran for the maximum number of instructions without halting
+    InstructionLimitReached { limit: u64 },
+    /// The program is bigger than the memory between the start address and the end
+    ProgramTooLarge { size: usize, space: usize },
+    /// The PC ran past the end of memory with wrapping turned off
+    PcOutOfRange { pc: usize },
+    /// A jump or call went past the end of a memory smaller than the usual 4096 bytes
+    JumpOutOfRange { pc: usize, addr: usize },
+    /// A program in a sequence couldn't be read, the reason having been logged already
+    LoadFailed { file: String },
+    /// The save state to start from couldn't be read or doesn't fit this machine
+    InvalidSaveState { file: String, reason: String },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::StackOverflow { pc } => write!(f, "stack overflow at PC={:03x}", pc),
+            Error::StackUnderflow { pc } => write!(f, "invalid RET at PC={:03x}", pc),
+            Error::AddressOutOfRange { pc, addr } => {
+                write!(f, "address {:04x} out of range at PC={:03x}", addr, pc)
+            }
+            Error::InvalidKey { key } => write!(f, "invalid key {}, keys go from 0 to 15", key),
+            Error::UnknownOpcode { pc, opcode } => {
+                write!(f, "unknown opcode 0x{:04x} at 0x{:03x}", opcode, pc)
+            }
+            Error::InstructionLimitReached { limit } => {
+                write!(f, "instruction limit reached after {} instructions", limit)
+            }
+            Error::ProgramTooLarge { size, space } => {
+                write!(
+                    f,
+                    "program is {} bytes but only {} bytes of memory are free",
+                    size, space
+                )
+            }
+            Error::PcOutOfRange { pc } => write!(f, "PC ran past the end of memory to {:03x}", pc),
+            Error::JumpOutOfRange { pc, addr } => {
+                write!(
+                    f,
+                    "jump to {:03x} past the end of memory at PC={:03x}",
+                    addr, pc
+                )
+            }
+            Error::LoadFailed { file } => write!(f, "couldn't load {}", file),
+            Error::InvalidSaveState { file, reason } => {
+                write!(f, "couldn't load the save state {}: {}", file, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,201 @@
+#[cfg(feature = "termion")]
+mod terminal;
+#[cfg(feature = "minifb")]
+mod window;
+
+#[cfg(feature = "termion")]
+pub use terminal::{parse_terminal_key, TerminalFrontend};
+#[cfg(feature = "minifb")]
+pub use window::{parse_key, MinifbFrontend};
+
+/// The keys for the debug commands that can be remapped, in the frontend's own kind of key
+/// None of the defaults are keypad keys in any layout, so they don't get in the way of the game
+pub struct DebugKeys<K> {
+    pub step: K,
+    pub quit: K,
+    pub resume: K,
+    pub run_to_breakpoint: K,
+    pub run_until_draw: K,
+    pub save_memory: K,
+}
+
+/// The frontends that can be picked from the command line
+pub enum Backend {
+    Minifb,
+    Terminal,
+    None,
+}
+
+impl std::str::FromStr for Backend {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "minifb" => Ok(Backend::Minifb),
+            "terminal" => Ok(Backend::Terminal),
+            "none" => Ok(Backend::None),
+            _ => Err(format!(
+                "unknown display {}, expected minifb, terminal, or none",
+                s
+            )),
+        }
+    }
+}
+
+/// How pixels are scaled up to the window size
+/// minifb and the terminal only scale to the nearest pixel, keeping pixels crisp
+#[derive(Debug, PartialEq)]
+pub enum ScaleMode {
+    Nearest,
+    Smooth,
+}
+
+impl std::str::FromStr for ScaleMode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nearest" => Ok(ScaleMode::Nearest),
+            "smooth" => Ok(ScaleMode::Smooth),
+            _ => Err(format!(
+                "unknown scale mode {}, expected nearest or smooth",
+                s
+            )),
+        }
+    }
+}
+
+/// Which keyboard keys stand in for the 16 CHIP8 keys
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum KeypadLayout {
+    /// The COSMAC VIP's 4x4 keypad laid over 1234/QWER/ASDF/ZXCV
+    #[default]
+    Qwerty,
+    /// Each key on its hex digit: 0-9 on the number row and A-F on the letters
+    Numeric,
+}
+
+impl KeypadLayout {
+    /// The keyboard key for each CHIP8 key, indexed by key value
+    pub fn keys(&self) -> [char; 16] {
+        match self {
+            KeypadLayout::Qwerty => [
+                'x', '1', '2', '3', 'q', 'w', 'e', 'a', 's', 'd', 'z', 'c', '4', 'r', 'f', 'v',
+            ],
+            KeypadLayout::Numeric => [
+                '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'a', 'b', 'c', 'd', 'e', 'f',
+            ],
+        }
+    }
+
+    /// The CHIP8 key a keyboard key is mapped to, ignoring case
+    pub fn keypad_index(&self, key: char) -> Option<usize> {
+        self.keys()
+            .iter()
+            .position(|&mapped| mapped == key.to_ascii_lowercase())
+    }
+}
+
+impl std::str::FromStr for KeypadLayout {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "qwerty" => Ok(KeypadLayout::Qwerty),
+            "numeric" => Ok(KeypadLayout::Numeric),
+            _ => Err(format!(
+                "unknown keypad layout {}, expected qwerty or numeric",
+                s
+            )),
+        }
+    }
+}
+
+/// Commands for stepping through a program in debug mode
+#[derive(Debug, PartialEq)]
+pub enum DebugCommand {
+    Step,
+    Quit,
+    Resume,
+    Continue,
+    Rewind,
+    RunUntilDraw,
+    DumpMemory,
+    SaveMemory,
+    ShowSprite,
+}
+
+/// Where frames are drawn and where key presses come from
+pub trait Frontend {
+    /// Draws a frame of width * height 0xRRGGBB pixels, row by row
+    fn present(&mut self, buffer: &[u32], width: usize, height: usize);
+
+    /// Returns the CHIP8 keys pressed since the last poll, indexed by key value
+    fn poll_keys(&mut self) -> [bool; 16];
+
+    /// Returns the CHIP8 keys held down right now, frontends that only see presses report those
+    fn held_keys(&mut self) -> [bool; 16] {
+        self.poll_keys()
+    }
+
+    /// Returns the debug command entered since the last poll, if any
+    fn poll_debug_command(&mut self) -> Option<DebugCommand>;
+
+    /// Whether the display is still open, false once the user closes the window
+    fn is_open(&mut self) -> bool {
+        true
+    }
+
+    /// Whether the display is focused, frontends that can't lose focus always are
+    fn has_focus(&mut self) -> bool {
+        true
+    }
+
+    /// Shows text in the title bar, frontends without one ignore it
+    fn set_title(&mut self, _title: &str) {}
+
+    /// Whether the invert key, I, was pressed since the last call
+    fn take_invert_toggle(&mut self) -> bool {
+        false
+    }
+}
+
+/// Draws nothing and never has keys pressed, for running programs headless
+/// Debug mode steps straight through since there's nobody to press a key
+pub struct NullFrontend;
+
+impl Frontend for NullFrontend {
+    fn present(&mut self, _buffer: &[u32], _width: usize, _height: usize) {}
+
+    fn poll_keys(&mut self) -> [bool; 16] {
+        [false; 16]
+    }
+
+    fn poll_debug_command(&mut self) -> Option<DebugCommand> {
+        Some(DebugCommand::Step)
+    }
+}
+
+#[test]
+fn test_keypad_layout() {
+    let qwerty = KeypadLayout::default();
+    assert_eq!(qwerty.keypad_index('x'), Some(0));
+    assert_eq!(qwerty.keypad_index('4'), Some(0xC));
+    assert_eq!(qwerty.keypad_index('V'), Some(0xF));
+    assert_eq!(qwerty.keypad_index('0'), None);
+
+    let numeric: KeypadLayout = "numeric".parse().unwrap();
+    assert_eq!(numeric.keypad_index('0'), Some(0));
+    assert_eq!(numeric.keypad_index('9'), Some(9));
+    assert_eq!(numeric.keypad_index('a'), Some(0xA));
+    assert_eq!(numeric.keypad_index('F'), Some(0xF));
+    assert_eq!(numeric.keypad_index('x'), None);
+    assert!("dvorak".parse::<KeypadLayout>().is_err());
+}
+
+#[test]
+fn test_parse_scale_mode() {
+    assert_eq!("nearest".parse(), Ok(ScaleMode::Nearest));
+    assert_eq!("smooth".parse(), Ok(ScaleMode::Smooth));
+    assert_eq!(
+        "bilinear".parse::<ScaleMode>(),
+        Err("unknown scale mode bilinear, expected nearest or smooth".to_string())
+    );
+}
@@ -0,0 +1,212 @@
+use super::{DebugCommand, DebugKeys, Frontend, KeypadLayout};
+use std::io::{Stdout, Write};
+use termion::color::{Bg, Fg, Rgb};
+use termion::event::Key;
+use termion::input::{Keys, TermRead};
+use termion::raw::{IntoRawMode, RawTerminal};
+use termion::AsyncReader;
+
+/// Draws to the terminal with block characters, two pixels per character
+/// Key presses are read from raw stdin
+pub struct TerminalFrontend {
+    stdout: RawTerminal<Stdout>,
+    stdin: Keys<AsyncReader>,
+    keypad_layout: KeypadLayout,
+    debug_keys: DebugKeys<Key>,
+    invert_pressed: bool,
+}
+
+impl Default for DebugKeys<Key> {
+    /// The same keys as the window's, as the terminal reads them
+    fn default() -> Self {
+        DebugKeys {
+            step: Key::Char('\n'),
+            quit: Key::Esc,
+            resume: Key::Delete,
+            run_to_breakpoint: Key::Char('g'),
+            run_until_draw: Key::Char('n'),
+            save_memory: Key::Char('o'),
+        }
+    }
+}
+
+impl TerminalFrontend {
+    pub fn new() -> TerminalFrontend {
+        let mut stdout = std::io::stdout().into_raw_mode().unwrap_or_else(|e| {
+            panic!("Error switching the terminal to raw mode: {}", e);
+        });
+        write!(stdout, "{}{}", termion::clear::All, termion::cursor::Hide).unwrap();
+        TerminalFrontend {
+            stdout,
+            stdin: termion::async_stdin().keys(),
+            keypad_layout: KeypadLayout::default(),
+            debug_keys: DebugKeys::default(),
+            invert_pressed: false,
+        }
+    }
+
+    /// Reads the CHIP8 keys from the layout's keyboard keys
+    pub fn with_keypad_layout(mut self, layout: KeypadLayout) -> TerminalFrontend {
+        self.keypad_layout = layout;
+        self
+    }
+
+    /// Uses other keys for the debug commands
+    pub fn with_debug_keys(mut self, debug_keys: DebugKeys<Key>) -> TerminalFrontend {
+        self.debug_keys = debug_keys;
+        self
+    }
+
+    /// Puts the terminal back the way we found it
+    fn restore(&mut self) {
+        write!(
+            self.stdout,
+            "{}{}\r\n",
+            termion::style::Reset,
+            termion::cursor::Show
+        )
+        .unwrap();
+        self.stdout.flush().unwrap();
+        self.stdout.suspend_raw_mode().unwrap();
+    }
+
+    /// Returns the keys typed since the last read
+    /// Raw mode swallows Ctrl-C, so it's handled here by exiting
+    fn read_keys(&mut self) -> Vec<Key> {
+        let mut keys = Vec::new();
+        while let Some(Ok(key)) = self.stdin.next() {
+            if key == Key::Ctrl('c') {
+                self.restore();
+                std::process::exit(130);
+            }
+            keys.push(key);
+        }
+        keys
+    }
+}
+
+impl Default for TerminalFrontend {
+    fn default() -> Self {
+        TerminalFrontend::new()
+    }
+}
+
+impl Drop for TerminalFrontend {
+    fn drop(&mut self) {
+        self.restore();
+    }
+}
+
+/// Parses a key name like `Enter`, `Space`, `F5`, `P`, or `9`, ignoring case, into the key the
+/// terminal reads, taking the same names as parse_key
+pub fn parse_terminal_key(name: &str) -> Result<Key, String> {
+    let key = match name.to_uppercase().as_str() {
+        "ENTER" | "RETURN" => Some(Key::Char('\n')),
+        "ESCAPE" | "ESC" => Some(Key::Esc),
+        "DELETE" | "DEL" => Some(Key::Delete),
+        "BACKSPACE" => Some(Key::Backspace),
+        "SPACE" => Some(Key::Char(' ')),
+        "TAB" => Some(Key::Char('\t')),
+        "INSERT" => Some(Key::Insert),
+        "HOME" => Some(Key::Home),
+        "END" => Some(Key::End),
+        "PAGEUP" => Some(Key::PageUp),
+        "PAGEDOWN" => Some(Key::PageDown),
+        "UP" => Some(Key::Up),
+        "DOWN" => Some(Key::Down),
+        "LEFT" => Some(Key::Left),
+        "RIGHT" => Some(Key::Right),
+        upper => match upper.as_bytes() {
+            // Letters come through raw mode lowercase unless shift is held
+            [key @ (b'A'..=b'Z' | b'0'..=b'9')] => {
+                Some(Key::Char(key.to_ascii_lowercase() as char))
+            }
+            [b'F', number @ ..] => std::str::from_utf8(number)
+                .ok()
+                .and_then(|number| number.parse::<u8>().ok())
+                .filter(|number| (1..=12).contains(number))
+                .map(Key::F),
+            _ => None,
+        },
+    };
+    key.ok_or_else(|| {
+        format!(
+            "unknown key {}, expected a letter, a digit, F1 to F12, or a name like Enter or Space",
+            name
+        )
+    })
+}
+
+/// Splits a 0xRRGGBB pixel into a terminal color
+fn rgb(pixel: u32) -> Rgb {
+    Rgb((pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8)
+}
+
+impl Frontend for TerminalFrontend {
+    fn present(&mut self, buffer: &[u32], width: usize, height: usize) {
+        let mut frame = format!("{}", termion::cursor::Goto(1, 1));
+        // '▀' colors its top half with the foreground and its bottom half with the background
+        for row in (0..height).step_by(2) {
+            for col in 0..width {
+                let top = buffer[row * width + col];
+                let bottom = if row + 1 < height {
+                    buffer[(row + 1) * width + col]
+                } else {
+                    0
+                };
+                frame.push_str(&format!("{}{}\u{2580}", Fg(rgb(top)), Bg(rgb(bottom))));
+            }
+            frame.push_str(&format!("{}\r\n", termion::style::Reset));
+        }
+        self.stdout.write_all(frame.as_bytes()).unwrap();
+        self.stdout.flush().unwrap();
+    }
+
+    fn poll_keys(&mut self) -> [bool; 16] {
+        let mut keys = [false; 16];
+        let pressed = self.read_keys();
+        let layout = self.keypad_layout;
+        let indexes = pressed.iter().filter_map(|key| match key {
+            Key::Char(key) => layout.keypad_index(*key),
+            _ => None,
+        });
+        indexes.for_each(|index| keys[index] = true);
+        self.invert_pressed |= pressed.contains(&Key::Char('i'));
+        keys
+    }
+
+    fn poll_debug_command(&mut self) -> Option<DebugCommand> {
+        let pressed = self.read_keys();
+        let keys = &self.debug_keys;
+        pressed.into_iter().find_map(|key| match key {
+            _ if key == keys.step => Some(DebugCommand::Step),
+            _ if key == keys.quit => Some(DebugCommand::Quit),
+            _ if key == keys.resume => Some(DebugCommand::Resume),
+            _ if key == keys.run_to_breakpoint => Some(DebugCommand::Continue),
+            _ if key == keys.run_until_draw => Some(DebugCommand::RunUntilDraw),
+            _ if key == keys.save_memory => Some(DebugCommand::SaveMemory),
+            // Kept off the keypad keys of every layout, like the window's
+            Key::Backspace => Some(DebugCommand::Rewind),
+            Key::Char('m') => Some(DebugCommand::DumpMemory),
+            Key::Char('p') => Some(DebugCommand::ShowSprite),
+            _ => None,
+        })
+    }
+
+    fn take_invert_toggle(&mut self) -> bool {
+        std::mem::replace(&mut self.invert_pressed, false)
+    }
+}
+
+#[test]
+fn test_parse_terminal_key() {
+    assert_eq!(parse_terminal_key("Enter"), Ok(Key::Char('\n')));
+    assert_eq!(parse_terminal_key("space"), Ok(Key::Char(' ')));
+    assert_eq!(parse_terminal_key("P"), Ok(Key::Char('p')));
+    assert_eq!(parse_terminal_key("9"), Ok(Key::Char('9')));
+    assert_eq!(parse_terminal_key("F10"), Ok(Key::F(10)));
+    assert_eq!(parse_terminal_key("End"), Ok(Key::End));
+    assert!(parse_terminal_key("F13").is_err());
+    assert!(parse_terminal_key("F0").is_err());
+    assert!(parse_terminal_key("Hyper").is_err());
+}
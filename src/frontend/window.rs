@@ -0,0 +1,287 @@
+use super::{DebugCommand, DebugKeys, Frontend, KeypadLayout};
+use minifb::{Key, KeyRepeat, Scale, Window, WindowOptions};
+
+impl Default for DebugKeys<Key> {
+    fn default() -> Self {
+        DebugKeys {
+            step: Key::Enter,
+            quit: Key::Escape,
+            resume: Key::Delete,
+            run_to_breakpoint: Key::G,
+            run_until_draw: Key::N,
+            save_memory: Key::O,
+        }
+    }
+}
+
+/// Draws to a desktop window
+pub struct MinifbFrontend {
+    window: Window,
+    debug_keys: DebugKeys<Key>,
+    /// The keyboard key for each CHIP8 key
+    keypad: [Key; 16],
+    invert_pressed: bool,
+}
+
+impl MinifbFrontend {
+    /// Opens a window scale times the size of the display, rounding the scale down to 1, 2, 4, 8,
+    /// 16, or 32
+    pub fn new(width: usize, height: usize, scale: usize) -> MinifbFrontend {
+        MinifbFrontend {
+            window: Window::new(
+                "CHIP8",
+                width,
+                height,
+                WindowOptions {
+                    scale: match scale {
+                        0..=1 => Scale::X1,
+                        2..=3 => Scale::X2,
+                        4..=7 => Scale::X4,
+                        8..=15 => Scale::X8,
+                        16..=31 => Scale::X16,
+                        _ => Scale::X32,
+                    },
+                    ..WindowOptions::default()
+                },
+            )
+            .unwrap_or_else(|e| {
+                panic!("Error creating window: {}", e);
+            }),
+            debug_keys: DebugKeys::default(),
+            keypad: keypad_keys(KeypadLayout::default()),
+            invert_pressed: false,
+        }
+    }
+
+    /// Reads the CHIP8 keys from the layout's keyboard keys
+    pub fn with_keypad_layout(mut self, layout: KeypadLayout) -> MinifbFrontend {
+        self.keypad = keypad_keys(layout);
+        self
+    }
+
+    /// The CHIP8 key a keyboard key is mapped to
+    fn keypad_index(&self, key: &Key) -> Option<usize> {
+        self.keypad.iter().position(|mapped| mapped == key)
+    }
+
+    /// Uses other keys for the debug commands
+    pub fn with_debug_keys(mut self, debug_keys: DebugKeys<Key>) -> MinifbFrontend {
+        self.debug_keys = debug_keys;
+        self
+    }
+}
+
+/// Parses a key name like `Enter`, `Space`, `F5`, `P`, or `9`, ignoring case
+pub fn parse_key(name: &str) -> Result<Key, String> {
+    const LETTERS: [Key; 26] = [
+        Key::A,
+        Key::B,
+        Key::C,
+        Key::D,
+        Key::E,
+        Key::F,
+        Key::G,
+        Key::H,
+        Key::I,
+        Key::J,
+        Key::K,
+        Key::L,
+        Key::M,
+        Key::N,
+        Key::O,
+        Key::P,
+        Key::Q,
+        Key::R,
+        Key::S,
+        Key::T,
+        Key::U,
+        Key::V,
+        Key::W,
+        Key::X,
+        Key::Y,
+        Key::Z,
+    ];
+    const DIGITS: [Key; 10] = [
+        Key::Key0,
+        Key::Key1,
+        Key::Key2,
+        Key::Key3,
+        Key::Key4,
+        Key::Key5,
+        Key::Key6,
+        Key::Key7,
+        Key::Key8,
+        Key::Key9,
+    ];
+    const FUNCTION_KEYS: [Key; 12] = [
+        Key::F1,
+        Key::F2,
+        Key::F3,
+        Key::F4,
+        Key::F5,
+        Key::F6,
+        Key::F7,
+        Key::F8,
+        Key::F9,
+        Key::F10,
+        Key::F11,
+        Key::F12,
+    ];
+    let upper = name.to_uppercase();
+    let key = match upper.as_str() {
+        "ENTER" | "RETURN" => Some(Key::Enter),
+        "ESCAPE" | "ESC" => Some(Key::Escape),
+        "DELETE" | "DEL" => Some(Key::Delete),
+        "BACKSPACE" => Some(Key::Backspace),
+        "SPACE" => Some(Key::Space),
+        "TAB" => Some(Key::Tab),
+        "INSERT" => Some(Key::Insert),
+        "HOME" => Some(Key::Home),
+        "END" => Some(Key::End),
+        "PAGEUP" => Some(Key::PageUp),
+        "PAGEDOWN" => Some(Key::PageDown),
+        "UP" => Some(Key::Up),
+        "DOWN" => Some(Key::Down),
+        "LEFT" => Some(Key::Left),
+        "RIGHT" => Some(Key::Right),
+        _ => match upper.as_bytes() {
+            [letter @ b'A'..=b'Z'] => Some(LETTERS[(letter - b'A') as usize]),
+            [digit @ b'0'..=b'9'] => Some(DIGITS[(digit - b'0') as usize]),
+            [b'F', number @ ..] => std::str::from_utf8(number)
+                .ok()
+                .and_then(|number| number.parse::<usize>().ok())
+                .and_then(|number| FUNCTION_KEYS.get(number.wrapping_sub(1)).copied()),
+            _ => None,
+        },
+    };
+    key.ok_or_else(|| {
+        format!(
+            "unknown key {}, expected a letter, a digit, F1 to F12, or a name like Enter or Space",
+            name
+        )
+    })
+}
+
+/// The minifb key for each CHIP8 key in the layout
+fn keypad_keys(layout: KeypadLayout) -> [Key; 16] {
+    // Layouts only use letters and digits, which always parse
+    layout
+        .keys()
+        .map(|key| parse_key(&key.to_string()).unwrap())
+}
+
+impl Frontend for MinifbFrontend {
+    fn present(&mut self, buffer: &[u32], width: usize, height: usize) {
+        // The run loop stops at the end of the frame the window was closed in
+        if self.window.is_open() {
+            self.window
+                .update_with_buffer(buffer, width, height)
+                .unwrap();
+        }
+    }
+
+    fn poll_keys(&mut self) -> [bool; 16] {
+        let mut keys = [false; 16];
+        let pressed = self.window.get_keys_pressed(KeyRepeat::No);
+        let indexes = pressed.iter().filter_map(|key| self.keypad_index(key));
+        indexes.for_each(|index| keys[index] = true);
+        self.invert_pressed |= pressed.contains(&Key::I);
+        self.window.update(); // Update the window each time otherwise the state is static
+        keys
+    }
+
+    fn held_keys(&mut self) -> [bool; 16] {
+        let mut keys = [false; 16];
+        let held = self.window.get_keys();
+        held.iter()
+            .filter_map(|key| self.keypad_index(key))
+            .for_each(|index| keys[index] = true);
+        self.invert_pressed |= self
+            .window
+            .get_keys_pressed(KeyRepeat::No)
+            .contains(&Key::I);
+        self.window.update();
+        keys
+    }
+
+    fn poll_debug_command(&mut self) -> Option<DebugCommand> {
+        self.window.update();
+        self.window
+            .get_keys_pressed(KeyRepeat::No)
+            .iter()
+            .find_map(|&key| match key {
+                _ if key == self.debug_keys.step => Some(DebugCommand::Step),
+                _ if key == self.debug_keys.quit => Some(DebugCommand::Quit),
+                _ if key == self.debug_keys.resume => Some(DebugCommand::Resume),
+                _ if key == self.debug_keys.run_to_breakpoint => Some(DebugCommand::Continue),
+                _ if key == self.debug_keys.run_until_draw => Some(DebugCommand::RunUntilDraw),
+                _ if key == self.debug_keys.save_memory => Some(DebugCommand::SaveMemory),
+                Key::Backspace => Some(DebugCommand::Rewind),
+                Key::M => Some(DebugCommand::DumpMemory),
+                Key::P => Some(DebugCommand::ShowSprite),
+                _ => None,
+            })
+    }
+
+    fn is_open(&mut self) -> bool {
+        self.window.is_open()
+    }
+
+    fn has_focus(&mut self) -> bool {
+        self.window.update(); // Keeps the window responsive while paused
+        self.window.is_active()
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.window.set_title(title);
+    }
+
+    fn take_invert_toggle(&mut self) -> bool {
+        std::mem::replace(&mut self.invert_pressed, false)
+    }
+}
+
+#[test]
+fn test_keypad_keys() {
+    let qwerty = keypad_keys(KeypadLayout::Qwerty);
+    assert_eq!(qwerty[..2], [Key::X, Key::Key1]);
+    assert_eq!([qwerty[0xC], qwerty[0xF]], [Key::Key4, Key::V]);
+    let numeric = keypad_keys(KeypadLayout::Numeric);
+    assert_eq!(numeric[..3], [Key::Key0, Key::Key1, Key::Key2]);
+    assert_eq!(
+        [numeric[9], numeric[0xA], numeric[0xF]],
+        [Key::Key9, Key::A, Key::F]
+    );
+}
+
+#[test]
+fn test_debug_keys_miss_keypad() {
+    let keys = DebugKeys::<Key>::default();
+    let debug_keys = [
+        keys.step,
+        keys.quit,
+        keys.resume,
+        keys.run_to_breakpoint,
+        keys.run_until_draw,
+        keys.save_memory,
+        Key::Backspace,
+        Key::M,
+        Key::P,
+    ];
+    for layout in [KeypadLayout::Qwerty, KeypadLayout::Numeric] {
+        let keypad = keypad_keys(layout);
+        assert!(debug_keys.iter().all(|key| !keypad.contains(key)));
+    }
+}
+
+#[test]
+fn test_parse_key() {
+    assert_eq!(parse_key("Enter"), Ok(Key::Enter));
+    assert_eq!(parse_key("space"), Ok(Key::Space));
+    assert_eq!(parse_key("p"), Ok(Key::P));
+    assert_eq!(parse_key("9"), Ok(Key::Key9));
+    assert_eq!(parse_key("F10"), Ok(Key::F10));
+    assert!(parse_key("F13").is_err());
+    assert!(parse_key("F0").is_err());
+    assert!(parse_key("Hyper").is_err());
+}
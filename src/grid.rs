@@ -0,0 +1,96 @@
+use std::ops::{Index, IndexMut};
+use std::slice::Chunks;
+
+/// A width x height grid of cells stored row by row, indexed as `grid[row][col]`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T: Clone + Default> Grid<T> {
+    pub fn new(width: usize, height: usize) -> Grid<T> {
+        Grid {
+            width,
+            height,
+            cells: vec![T::default(); width * height],
+        }
+    }
+
+    /// Resets every cell to its default value
+    pub fn clear(&mut self) {
+        for cell in self.cells.iter_mut() {
+            *cell = T::default();
+        }
+    }
+}
+
+impl<T> Grid<T> {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Iterates over the rows from top to bottom
+    pub fn rows(&self) -> Chunks<'_, T> {
+        self.cells.chunks(self.width)
+    }
+
+    /// All the cells flattened row by row
+    pub fn cells(&self) -> &[T] {
+        &self.cells
+    }
+}
+
+impl<T: PartialEq> Grid<T> {
+    /// The (row, col) of every cell that's different in other, which should be the same size
+    pub fn changed(&self, other: &Grid<T>) -> Vec<(usize, usize)> {
+        let pairs = self.cells.iter().zip(&other.cells).enumerate();
+        pairs
+            .filter(|(_, (a, b))| a != b)
+            .map(|(i, _)| (i / self.width, i % self.width))
+            .collect()
+    }
+}
+
+impl<T> Index<usize> for Grid<T> {
+    type Output = [T];
+
+    fn index(&self, row: usize) -> &[T] {
+        &self.cells[row * self.width..(row + 1) * self.width]
+    }
+}
+
+impl<T> IndexMut<usize> for Grid<T> {
+    fn index_mut(&mut self, row: usize) -> &mut [T] {
+        &mut self.cells[row * self.width..(row + 1) * self.width]
+    }
+}
+
+#[test]
+fn test_grid_indexing() {
+    let mut grid: Grid<bool> = Grid::new(4, 2);
+    grid[1][2] = true;
+    assert_eq!(
+        grid.cells(),
+        &[false, false, false, false, false, false, true, false]
+    );
+    assert_eq!(grid[1], [false, false, true, false]);
+    assert_eq!(grid.rows().count(), 2);
+    grid.clear();
+    assert!(grid.cells().iter().all(|&cell| !cell));
+}
+
+#[test]
+fn test_grid_changed() {
+    let mut grid: Grid<bool> = Grid::new(4, 2);
+    let old = grid.clone();
+    grid[0][3] = true;
+    grid[1][1] = true;
+    assert_eq!(old.changed(&grid), [(0, 3), (1, 1)]);
+    assert!(grid.changed(&grid).is_empty());
+}
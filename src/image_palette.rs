@@ -0,0 +1,55 @@
+use crate::color::{Color, Palette};
+use image::RgbImage;
+use std::collections::HashMap;
+
+/// Loads an image and makes a palette from its two most common colors, see dominant_colors
+pub fn palette_from_image(path: &str) -> Result<Palette, String> {
+    let image = image::open(path).map_err(|err| err.to_string())?.to_rgb8();
+    let (off, on) = dominant_colors(&image).ok_or("the image has no pixels")?;
+    let mut palette = Color::Custom(on).palette();
+    palette.colors[0] = off;
+    Ok(palette)
+}
+
+/// The two most common colors in the image, the most common first since it's usually the background
+/// Close colors are grouped and averaged so gradients and compression noise don't split them up
+/// An image with only one color gets its inverse as the second
+pub fn dominant_colors(image: &RgbImage) -> Option<(u32, u32)> {
+    // How many pixels are in each group and the sums of their channels, for averaging
+    let mut groups: HashMap<[u8; 3], (u64, [u64; 3])> = HashMap::new();
+    for pixel in image.pixels() {
+        let [r, g, b] = pixel.0;
+        let (count, sums) = groups
+            .entry([r >> 4, g >> 4, b >> 4])
+            .or_insert((0, [0; 3]));
+        *count += 1;
+        for (sum, channel) in sums.iter_mut().zip(&pixel.0) {
+            *sum += *channel as u64;
+        }
+    }
+    let mut groups: Vec<_> = groups.into_iter().collect();
+    groups.sort_by(|a, b| b.1 .0.cmp(&a.1 .0).then(a.0.cmp(&b.0)));
+    let average = |(count, sums): (u64, [u64; 3])| {
+        sums.iter()
+            .fold(0, |color, sum| color << 8 | (sum / count) as u32)
+    };
+    let first = average(groups.first()?.1);
+    let second = groups
+        .get(1)
+        .map_or(first ^ 0xffffff, |group| average(group.1));
+    Some((first, second))
+}
+
+#[test]
+fn test_dominant_colors() {
+    // Mostly dark blue with a yellow square, plus a slightly different blue that's grouped in
+    let image = RgbImage::from_fn(8, 8, |x, y| match (x, y) {
+        (0..=2, 0..=2) => image::Rgb([0xff, 0xd7, 0x00]),
+        (7, 7) => image::Rgb([0x10, 0x20, 0x7f]),
+        _ => image::Rgb([0x10, 0x20, 0x70]),
+    });
+    assert_eq!(dominant_colors(&image), Some((0x102070, 0xffd700)));
+
+    let image = RgbImage::from_fn(2, 2, |_, _| image::Rgb([0, 0, 0]));
+    assert_eq!(dominant_colors(&image), Some((0x000000, 0xffffff)));
+}
@@ -0,0 +1,192 @@
+use std::collections::VecDeque;
+
+/// A key being pressed or released at the end of the frame where cycle instructions had run, or
+/// a new press of it being read by Fx0A on that cycle
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InputEvent {
+    pub cycle: u64,
+    pub key: usize,
+    pub change: KeyChange,
+}
+
+/// What happened to the key in an InputEvent
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KeyChange {
+    Released,
+    Pressed,
+    /// Fx0A took a new press of the key, which Ex9E and ExA1 don't see
+    Read,
+}
+
+/// Collects an event for every key that changes between frames
+#[derive(Default)]
+pub struct InputRecorder {
+    events: Vec<InputEvent>,
+    keys: [bool; 16],
+}
+
+impl InputRecorder {
+    /// Records the keys that changed since the last call
+    pub fn record(&mut self, cycle: u64, keys: [bool; 16]) {
+        for (key, (&was, &pressed)) in self.keys.iter().zip(&keys).enumerate() {
+            if was != pressed {
+                let change = if pressed {
+                    KeyChange::Pressed
+                } else {
+                    KeyChange::Released
+                };
+                self.events.push(InputEvent { cycle, key, change });
+            }
+        }
+        self.keys = keys;
+    }
+
+    /// Records Fx0A taking a new press of the key on the cycle
+    pub fn record_read(&mut self, cycle: u64, key: usize) {
+        self.events.push(InputEvent {
+            cycle,
+            key,
+            change: KeyChange::Read,
+        });
+    }
+
+    pub fn events(&self) -> &[InputEvent] {
+        &self.events
+    }
+}
+
+/// Plays events back, holding each key from the cycle it was pressed on until it's released
+/// Keys stay the way the last event left them once the events run out
+pub struct InputReplay {
+    events: VecDeque<InputEvent>,
+    reads: VecDeque<InputEvent>,
+    keys: [bool; 16],
+}
+
+impl InputReplay {
+    pub fn new(events: Vec<InputEvent>) -> InputReplay {
+        let (reads, events) = events
+            .into_iter()
+            .partition(|event| event.change == KeyChange::Read);
+        InputReplay {
+            events,
+            reads,
+            keys: [false; 16],
+        }
+    }
+
+    /// The keys held once every event up to and including cycle has happened
+    pub fn keys_at(&mut self, cycle: u64) -> [bool; 16] {
+        while let Some(event) = self.events.front().filter(|event| event.cycle <= cycle) {
+            self.keys[event.key] = event.change == KeyChange::Pressed;
+            self.events.pop_front();
+        }
+        self.keys
+    }
+
+    /// The key Fx0A read on the cycle, if it read one then
+    pub fn read_at(&mut self, cycle: u64) -> Option<usize> {
+        let event = self.reads.front().filter(|event| event.cycle <= cycle)?;
+        let key = event.key;
+        self.reads.pop_front();
+        Some(key)
+    }
+}
+
+/// Formats events one per line as `cycle key pressed`, e.g. `120 5 1` for key 5 pressed at 120,
+/// with 0 for released and k for read by Fx0A
+pub fn format(events: &[InputEvent]) -> String {
+    let mut text = String::from("# cycle key pressed\n");
+    for event in events {
+        let change = match event.change {
+            KeyChange::Released => '0',
+            KeyChange::Pressed => '1',
+            KeyChange::Read => 'k',
+        };
+        text.push_str(&format!("{} {} {}\n", event.cycle, event.key, change));
+    }
+    text
+}
+
+/// Parses events written by format, skipping blank lines and # comments
+pub fn parse(text: &str) -> Result<Vec<InputEvent>, String> {
+    let mut events = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |message: &str| format!("line {}: {}", number + 1, message);
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (cycle, key, change) = match fields[..] {
+            [cycle, key, change] => (cycle, key, change),
+            _ => return Err(error("expected a cycle, key, and 1, 0, or k for pressed")),
+        };
+        let cycle = cycle.parse().map_err(|_| error("invalid cycle"))?;
+        let key = key
+            .parse()
+            .ok()
+            .filter(|&key| key < 16)
+            .ok_or_else(|| error("invalid key"))?;
+        let change = match change {
+            "1" => KeyChange::Pressed,
+            "0" => KeyChange::Released,
+            "k" => KeyChange::Read,
+            _ => return Err(error("pressed should be 1, 0, or k")),
+        };
+        events.push(InputEvent { cycle, key, change });
+    }
+    Ok(events)
+}
+
+#[test]
+fn test_format_and_parse() {
+    let events = vec![
+        InputEvent {
+            cycle: 10,
+            key: 5,
+            change: KeyChange::Pressed,
+        },
+        InputEvent {
+            cycle: 12,
+            key: 5,
+            change: KeyChange::Read,
+        },
+        InputEvent {
+            cycle: 30,
+            key: 5,
+            change: KeyChange::Released,
+        },
+    ];
+    let text = format(&events);
+    assert_eq!(text, "# cycle key pressed\n10 5 1\n12 5 k\n30 5 0\n");
+    assert_eq!(parse(&text), Ok(events));
+    assert_eq!(parse("\n10 16 1"), Err("line 2: invalid key".to_string()));
+    let missing = "line 1: expected a cycle, key, and 1, 0, or k for pressed".to_string();
+    assert_eq!(parse("10 5"), Err(missing));
+}
+
+#[test]
+fn test_replay() {
+    let mut recorder = InputRecorder::default();
+    let mut keys = [false; 16];
+    recorder.record(10, keys);
+    keys[5] = true;
+    recorder.record(20, keys);
+    keys[5] = false;
+    keys[3] = true;
+    recorder.record_read(25, 5);
+    recorder.record(30, keys);
+    assert_eq!(recorder.events().len(), 4);
+
+    let mut replay = InputReplay::new(recorder.events().to_vec());
+    assert_eq!(replay.keys_at(19), [false; 16]);
+    assert!(replay.keys_at(25)[5]);
+    assert_eq!(replay.keys_at(30), keys);
+    assert_eq!(replay.keys_at(1000), keys);
+
+    // Reads are separate from the held keys, and each is only read once
+    assert_eq!(replay.read_at(24), None);
+    assert_eq!(replay.read_at(25), Some(5));
+    assert_eq!(replay.read_at(26), None);
+}
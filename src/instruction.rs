@@ -0,0 +1,234 @@
+/// A decoded opcode with its operands pulled out, shared by the executor and the disassembler
+/// Operands are register numbers (x, y), bytes (nn), nibbles (n), or addresses (nnn)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Instruction {
+    /// 0000
+    Halt,
+    /// 00E0
+    ClearScreen,
+    /// 00EE
+    Return,
+    /// 0nnn, a COSMAC VIP machine code routine
+    Sys(u16),
+    /// 1nnn
+    Jump(u16),
+    /// 2nnn
+    Call(u16),
+    /// 3xnn
+    SkipIfEqual(u8, u8),
+    /// 4xnn
+    SkipIfNotEqual(u8, u8),
+    /// 5xy0
+    SkipXyEqual(u8, u8),
+    /// 5xy2, XO-CHIP only
+    StoreRange(u8, u8),
+    /// 5xy3, XO-CHIP only
+    LoadRange(u8, u8),
+    /// 6xnn
+    SetXnn(u8, u8),
+    /// 7xnn
+    AddXnn(u8, u8),
+    /// 8xy0
+    AssignXy(u8, u8),
+    /// 8xy1
+    OrXy(u8, u8),
+    /// 8xy2
+    AndXy(u8, u8),
+    /// 8xy3
+    XorXy(u8, u8),
+    /// 8xy4
+    AddXy(u8, u8),
+    /// 8xy5
+    SubXy(u8, u8),
+    /// 8xy6
+    ShiftRight(u8, u8),
+    /// 8xy7
+    SubYx(u8, u8),
+    /// 8xyE
+    ShiftLeft(u8, u8),
+    /// 9xy0
+    SkipXyNotEqual(u8, u8),
+    /// Annn
+    SetI(u16),
+    /// Bnnn
+    JumpPlusV0(u16),
+    /// Cxnn
+    Rand(u8, u8),
+    /// Dxyn
+    Draw(u8, u8, u8),
+    /// Ex9E
+    SkipIfKeyPressed(u8),
+    /// ExA1
+    SkipIfKeyNotPressed(u8),
+    /// F002, XO-CHIP only
+    LoadAudioPattern,
+    /// Fx07
+    SetXToDelayTimer(u8),
+    /// Fx0A
+    SetXToKeypress(u8),
+    /// Fx15
+    SetDelayTimer(u8),
+    /// Fx18
+    SetSoundTimer(u8),
+    /// Fx1E
+    AddIx(u8),
+    /// Fx29
+    SetISpriteAddr(u8),
+    /// Fx33
+    SetBcd(u8),
+    /// Fx3A, XO-CHIP only
+    SetPitch(u8),
+    /// Fx55
+    RegDump(u8),
+    /// Fx65
+    RegLoad(u8),
+    /// Anything else
+    Unknown(u16),
+}
+
+/// Splits an opcode into the instruction it runs
+pub fn decode(opcode: u16) -> Instruction {
+    use Instruction::*;
+    let x = ((opcode & 0x0F00) >> 8) as u8;
+    let y = ((opcode & 0x00F0) >> 4) as u8;
+    let nn = (opcode & 0x00FF) as u8;
+    let n = (opcode & 0x000F) as u8;
+    let nnn = opcode & 0x0FFF;
+
+    match opcode {
+        0x0000 => Halt,
+        0x00E0 => ClearScreen,
+        0x00EE => Return,
+        0x0001..=0x0FFF => Sys(nnn),
+        0x1000..=0x1FFF => Jump(nnn),
+        0x2000..=0x2FFF => Call(nnn),
+        0x3000..=0x3FFF => SkipIfEqual(x, nn),
+        0x4000..=0x4FFF => SkipIfNotEqual(x, nn),
+        0x5000..=0x5FFF => match n {
+            0 => SkipXyEqual(x, y),
+            2 => StoreRange(x, y),
+            3 => LoadRange(x, y),
+            _ => Unknown(opcode),
+        },
+        0x6000..=0x6FFF => SetXnn(x, nn),
+        0x7000..=0x7FFF => AddXnn(x, nn),
+        0x8000..=0x8FFF => match n {
+            0 => AssignXy(x, y),
+            1 => OrXy(x, y),
+            2 => AndXy(x, y),
+            3 => XorXy(x, y),
+            4 => AddXy(x, y),
+            5 => SubXy(x, y),
+            6 => ShiftRight(x, y),
+            7 => SubYx(x, y),
+            14 => ShiftLeft(x, y),
+            _ => Unknown(opcode),
+        },
+        0x9000..=0x9FFF if n == 0 => SkipXyNotEqual(x, y),
+        0xA000..=0xAFFF => SetI(nnn),
+        0xB000..=0xBFFF => JumpPlusV0(nnn),
+        0xC000..=0xCFFF => Rand(x, nn),
+        0xD000..=0xDFFF => Draw(x, y, n),
+        0xE000..=0xEFFF => match nn {
+            0x9E => SkipIfKeyPressed(x),
+            0xA1 => SkipIfKeyNotPressed(x),
+            _ => Unknown(opcode),
+        },
+        0xF002 => LoadAudioPattern,
+        0xF000..=0xFFFF => match nn {
+            0x07 => SetXToDelayTimer(x),
+            0x0A => SetXToKeypress(x),
+            0x15 => SetDelayTimer(x),
+            0x18 => SetSoundTimer(x),
+            0x1E => AddIx(x),
+            0x29 => SetISpriteAddr(x),
+            0x33 => SetBcd(x),
+            0x3A => SetPitch(x),
+            0x55 => RegDump(x),
+            0x65 => RegLoad(x),
+            _ => Unknown(opcode),
+        },
+        _ => Unknown(opcode),
+    }
+}
+
+/// COSMAC VIP machine cycles the interpreter spends fetching and decoding every instruction
+const FETCH_CYCLES: u32 = 40;
+
+/// The cost of a typical instruction like 7xnn, so cycles_per_frame of them fill a frame's budget
+pub const TYPICAL_CYCLE_COST: u32 = FETCH_CYCLES + 10;
+
+/// Roughly how many COSMAC VIP machine cycles the instruction takes, for pacing by cost
+/// Approximations of the original interpreter: drawing and the register loops cost by the byte,
+/// and Fx0A is counted once even though it waits for a key
+pub fn cycle_cost(instruction: Instruction) -> u32 {
+    use Instruction::*;
+    let execute = match instruction {
+        Halt | Unknown(_) => 0,
+        ClearScreen => 24,
+        Return => 10,
+        Sys(_) => 26,
+        Jump(_) => 12,
+        Call(_) => 26,
+        SkipIfEqual(..) | SkipIfNotEqual(..) => 10,
+        SkipXyEqual(..) | SkipXyNotEqual(..) => 14,
+        SetXnn(..) => 6,
+        AddXnn(..) => 10,
+        AssignXy(..) | OrXy(..) | AndXy(..) | XorXy(..) => 44,
+        AddXy(..) | SubXy(..) | SubYx(..) | ShiftRight(..) | ShiftLeft(..) => 44,
+        SetI(_) => 12,
+        JumpPlusV0(_) => 22,
+        Rand(..) => 36,
+        Draw(_, _, n) => 68 + 46 * n as u32,
+        SkipIfKeyPressed(_) | SkipIfKeyNotPressed(_) => 14,
+        SetXToDelayTimer(_) | SetDelayTimer(_) | SetSoundTimer(_) => 10,
+        SetXToKeypress(_) => 20,
+        AddIx(_) => 16,
+        SetISpriteAddr(_) => 20,
+        SetBcd(_) => 84,
+        SetPitch(_) => 10,
+        LoadAudioPattern => 14 + 14 * 16,
+        RegDump(x) | RegLoad(x) => 14 + 14 * (x as u32 + 1),
+        StoreRange(x, y) | LoadRange(x, y) => 14 + 14 * (x.abs_diff(y) as u32 + 1),
+    };
+    FETCH_CYCLES + execute
+}
+
+#[test]
+fn test_decode() {
+    use Instruction::*;
+    assert_eq!(decode(0x0000), Halt);
+    assert_eq!(decode(0x00E0), ClearScreen);
+    assert_eq!(decode(0x0123), Sys(0x123));
+    assert_eq!(decode(0x1206), Jump(0x206));
+    assert_eq!(decode(0x6005), SetXnn(0, 0x05));
+    assert_eq!(decode(0x5122), StoreRange(1, 2));
+    assert_eq!(decode(0x5313), LoadRange(3, 1));
+    assert_eq!(decode(0x8014), AddXy(0, 1));
+    assert_eq!(decode(0x8AB6), ShiftRight(0xA, 0xB));
+    assert_eq!(decode(0xB300), JumpPlusV0(0x300));
+    assert_eq!(decode(0xD015), Draw(0, 1, 5));
+    assert_eq!(decode(0xE2A1), SkipIfKeyNotPressed(2));
+    assert_eq!(decode(0xF002), LoadAudioPattern);
+    assert_eq!(decode(0xF13A), SetPitch(1));
+    assert_eq!(decode(0xFA65), RegLoad(0xA));
+}
+
+#[test]
+fn test_decode_unknown() {
+    assert_eq!(decode(0x801F), Instruction::Unknown(0x801F));
+    assert_eq!(decode(0xE0FF), Instruction::Unknown(0xE0FF));
+    assert_eq!(decode(0x5FF1), Instruction::Unknown(0x5FF1));
+    assert_eq!(decode(0x5124), Instruction::Unknown(0x5124));
+    assert_eq!(decode(0x912F), Instruction::Unknown(0x912F));
+    assert_eq!(decode(0xF0FF), Instruction::Unknown(0xF0FF));
+}
+
+#[test]
+fn test_cycle_cost() {
+    assert_eq!(cycle_cost(decode(0x7005)), TYPICAL_CYCLE_COST);
+    // Drawing 5 rows costs about as much as 7 adds, and more rows cost more
+    assert_eq!(cycle_cost(decode(0xD015)), 338);
+    assert!(cycle_cost(decode(0xD01F)) > cycle_cost(decode(0xD015)));
+    assert!(cycle_cost(decode(0xF355)) > cycle_cost(decode(0xF055)));
+}
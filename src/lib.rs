@@ -0,0 +1,26 @@
+pub mod assembler;
+pub mod audio;
+pub mod batch;
+pub mod benchmark;
+pub mod chip8;
+pub mod color;
+#[cfg(feature = "config")]
+pub mod config;
+pub mod disassembler;
+pub mod error;
+pub mod frontend;
+pub mod grid;
+#[cfg(feature = "image")]
+pub mod image_palette;
+pub mod input_log;
+pub mod instruction;
+pub mod options;
+pub mod overlay;
+pub mod png;
+pub mod quirks;
+pub mod replay;
+pub mod rom_info;
+pub mod save_state;
+pub mod selftest;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -1,8 +1,425 @@
-mod chip8;
-mod color;
-
+use chip_8_emulator::audio::Waveform;
+#[cfg(feature = "config")]
+use chip_8_emulator::config::Config;
+use chip_8_emulator::frontend::{
+    parse_key, parse_terminal_key, Backend, DebugKeys, KeypadLayout, MinifbFrontend, ScaleMode,
+    TerminalFrontend,
+};
+use chip_8_emulator::options::Options;
+use chip_8_emulator::overlay::Overlay;
+use chip_8_emulator::quirks::{Platform, QuirkOverrides, Quirks};
+use chip_8_emulator::{
+    assembler, batch, benchmark, chip8, color, input_log, replay, save_state, selftest,
+};
+use std::convert::TryInto;
 use structopt::StructOpt;
 
+#[derive(StructOpt)]
+struct RunOptions {
+    #[structopt(
+        long,
+        help = "Prints the PC, opcode, and mnemonic of each instruction to stderr"
+    )]
+    trace: bool,
+    #[structopt(
+        long,
+        help = "How much to log to stderr: error, warn, info, debug, or trace, defaults to RUST_LOG or info"
+    )]
+    log_level: Option<log::LevelFilter>,
+    #[structopt(long, help = "Prints how often each opcode class was executed on exit")]
+    profile: bool,
+    #[structopt(
+        long,
+        help = "Prints a table of every opcode class and how often it ran on exit, including the unused ones"
+    )]
+    coverage: bool,
+    #[structopt(
+        long = "break",
+        parse(try_from_str = parse_address),
+        help = "Drops into debug mode before executing the instruction at this address, e.g. 0x2A6. Can be repeated"
+    )]
+    breakpoints: Vec<usize>,
+    #[structopt(
+        long = "watch",
+        parse(try_from_str = parse_address),
+        help = "Drops into debug mode when an instruction changes the byte at this address, e.g. 0x300"
+    )]
+    watch_addr: Option<usize>,
+    #[structopt(
+        long,
+        parse(try_from_str = parse_address),
+        help = "Where the M key prints memory around in debug mode, instead of around I"
+    )]
+    dump_addr: Option<usize>,
+    #[structopt(
+        long,
+        default_value = "100",
+        help = "How many debug steps can be rewound with BACKSPACE"
+    )]
+    rewind_depth: usize,
+    #[structopt(
+        long,
+        help = "How many instructions run in each 60Hz frame, between timer ticks and redraws, defaults to 10"
+    )]
+    cycles_per_frame: Option<usize>,
+    #[structopt(
+        long,
+        help = "Paces instructions by roughly what they cost on the COSMAC VIP, so drawing takes longer than adding"
+    )]
+    cycle_costs: bool,
+    #[structopt(
+        long,
+        help = "Stops with an error after running this many instructions"
+    )]
+    max_cycles: Option<u64>,
+    #[structopt(
+        long,
+        help = "Runs frames back to back instead of at 60Hz and seeds the random numbers, so every run is the same"
+    )]
+    deterministic: bool,
+    #[structopt(
+        long,
+        help = "Seeds the random numbers used by Cxnn, defaults to 0 with --deterministic"
+    )]
+    seed: Option<u64>,
+    #[structopt(
+        long,
+        help = "Turns on the quirks programs for a platform expect: chip8, schip, or xochip"
+    )]
+    platform: Option<Platform>,
+    #[structopt(
+        long,
+        require_equals = true,
+        help = "Fx1E sets VF when I overflows past 0xFFF (Amiga interpreter quirk), =false turns it off"
+    )]
+    quirk_i_overflow: Option<Option<bool>>,
+    #[structopt(
+        long,
+        require_equals = true,
+        help = "Sprites are clipped at the screen edges instead of wrapping around, =false turns it off"
+    )]
+    quirk_clip: Option<Option<bool>>,
+    #[structopt(
+        long,
+        require_equals = true,
+        help = "Drawing waits for the next 60Hz frame, limiting draws to one per frame, =false turns it off"
+    )]
+    quirk_vblank: Option<Option<bool>>,
+    #[structopt(
+        long,
+        require_equals = true,
+        help = "8xy6 and 8xyE shift Vy into Vx instead of shifting Vx in place, =false turns it off"
+    )]
+    quirk_shift_vy: Option<Option<bool>>,
+    #[structopt(
+        long,
+        require_equals = true,
+        help = "Fx55 and Fx65 leave I pointing past the last register stored or loaded, =false turns it off"
+    )]
+    quirk_load_store_i: Option<Option<bool>>,
+    #[structopt(
+        long,
+        require_equals = true,
+        help = "Bxnn jumps to xnn + Vx instead of Bnnn jumping to nnn + V0, =false turns it off"
+    )]
+    quirk_jump_vx: Option<Option<bool>>,
+    #[structopt(
+        long,
+        help = "Enables XO-CHIP instructions: F002 and Fx3A for audio, 5xy2 and 5xy3 to save and load registers"
+    )]
+    xo_chip: bool,
+    #[structopt(
+        long,
+        help = "Pixels fade out over a few frames instead of disappearing, reducing flicker"
+    )]
+    persistence: bool,
+    #[structopt(long, help = "Pauses while the window doesn't have focus")]
+    pause_on_blur: bool,
+    #[structopt(
+        long,
+        help = "Shows the instructions and frames run each second in the window title"
+    )]
+    show_stats: bool,
+    #[structopt(
+        long,
+        help = "Four hex colors for pixels that are off, in plane 1, in plane 2, and in both, e.g. 0x000000,0xaf12e8,0xffffff,0xd789f4"
+    )]
+    palette: Option<color::Palette>,
+    #[structopt(
+        long,
+        help = "Uses the two most common colors in an image for pixels that are off and on, needs the image feature"
+    )]
+    palette_from_image: Option<String>,
+    #[structopt(
+        long,
+        parse(try_from_str = parse_brightness),
+        help = "Multiplies the brightness of lit pixels, from 0.0 to 2.0, defaults to 1.0"
+    )]
+    brightness: Option<f32>,
+    #[structopt(
+        long,
+        help = "Stops with an error when the PC runs past 0xFFF instead of wrapping around to 0x000"
+    )]
+    halt_on_pc_wrap: bool,
+    #[structopt(
+        long,
+        help = "Prints the ROM size, entry point, quirks, color, and speed before running"
+    )]
+    verbose: bool,
+    #[structopt(
+        long,
+        help = "Stops when a jump targets its own address, the idle loop many programs end with"
+    )]
+    detect_idle: bool,
+    #[structopt(
+        long,
+        help = "Warns about instructions that are probably mistakes, like writing to VF directly"
+    )]
+    strict: bool,
+    #[structopt(
+        long,
+        help = "Fx0A takes a key that's already held down, so holding a key repeats it"
+    )]
+    key_repeat: bool,
+    #[structopt(
+        long,
+        help = "Writes the registers, display, memory, and why it stopped to this file on exit"
+    )]
+    dump_on_exit: Option<String>,
+    #[structopt(
+        long,
+        help = "Saves the display as a PNG to this file when the program halts or reaches --max-cycles"
+    )]
+    screenshot_on_halt: Option<String>,
+    #[structopt(
+        long,
+        default_value = "8",
+        help = "How many pixels across each CHIP8 pixel is in screenshots"
+    )]
+    screenshot_scale: usize,
+    #[structopt(
+        long,
+        help = "Writes every key pressed and released, with the cycle it happened on, to this file on exit"
+    )]
+    record_input: Option<String>,
+    #[structopt(
+        long,
+        parse(try_from_str = parse_autosave),
+        help = "Saves the state every N frames, keeping the last three saves"
+    )]
+    autosave: Option<u64>,
+    #[structopt(
+        long,
+        default_value = "autosave",
+        help = "Autosaves are written to this path with .0.state to .2.state added"
+    )]
+    autosave_path: String,
+    #[structopt(
+        long,
+        help = "Carries on from a save state, like an autosave, once the program loads"
+    )]
+    load_state: Option<String>,
+    #[structopt(
+        long,
+        help = "Plays back keys written by --record-input instead of reading the keyboard"
+    )]
+    replay_input: Option<String>,
+    #[structopt(
+        long,
+        default_value = "minifb",
+        help = "Where to draw the display: minifb opens a window, terminal draws in the terminal, none runs without a display"
+    )]
+    display: Backend,
+    #[structopt(
+        long,
+        default_value = "nearest",
+        help = "How pixels are scaled up: nearest keeps them crisp, smooth blurs them, but no display supports smooth yet"
+    )]
+    scale_mode: ScaleMode,
+    #[structopt(
+        long,
+        default_value = "32",
+        parse(try_from_str = parse_scale),
+        help = "How many window pixels across each CHIP8 pixel is: 1, 2, 4, 8, 16, or 32"
+    )]
+    scale: usize,
+    #[structopt(
+        long,
+        default_value = "qwerty",
+        help = "Which keys are the keypad: qwerty uses 1234/QWER/ASDF/ZXCV, numeric uses 0-9 and A-F"
+    )]
+    keypad_layout: KeypadLayout,
+    #[structopt(
+        long,
+        help = "Starts with the lit and unlit colors swapped, press I to toggle it while running"
+    )]
+    invert: bool,
+    #[structopt(
+        long,
+        default_value = "none",
+        help = "Draws scanlines or a grid over the window for a retro look: scanlines, grid, or none"
+    )]
+    overlay: Overlay,
+    #[structopt(
+        long,
+        default_value = "20",
+        parse(try_from_str = parse_volume),
+        help = "How loud the beep is, from 0 to 100"
+    )]
+    volume: u8,
+    #[structopt(
+        long,
+        default_value = "square",
+        help = "The shape of the beep: square, sine, or triangle"
+    )]
+    waveform: Waveform,
+    #[structopt(long, default_value = "440", help = "The pitch of the beep in Hz")]
+    beep_hz: f32,
+    #[structopt(
+        long = "start",
+        default_value = "0x200",
+        parse(try_from_str = parse_address),
+        help = "Where the program is loaded and starts running, e.g. 0x600 for ETI-660 programs"
+    )]
+    start_address: usize,
+    #[structopt(
+        long,
+        default_value = "4096",
+        parse(try_from_str = parse_memory_size),
+        help = "How many bytes of memory the machine has, for variants with more or less than 4096"
+    )]
+    memory_size: usize,
+    #[structopt(
+        long,
+        parse(try_from_str = parse_key_name),
+        help = "The key that steps to the next instruction in debug mode, e.g. Space or N, defaults to Enter"
+    )]
+    step_key: Option<String>,
+    #[structopt(
+        long,
+        parse(try_from_str = parse_key_name),
+        help = "The key that exits in debug mode, defaults to Escape"
+    )]
+    quit_key: Option<String>,
+    #[structopt(
+        long,
+        parse(try_from_str = parse_key_name),
+        help = "The key that resumes normal execution in debug mode, defaults to Delete"
+    )]
+    resume_key: Option<String>,
+    #[structopt(
+        long,
+        parse(try_from_str = parse_key_name),
+        help = "The key that continues until the next breakpoint in debug mode, defaults to G"
+    )]
+    continue_key: Option<String>,
+    #[structopt(
+        long,
+        parse(try_from_str = parse_key_name),
+        help = "The key that runs until the next instruction that draws in debug mode, defaults to N"
+    )]
+    draw_key: Option<String>,
+    #[structopt(
+        long,
+        parse(try_from_str = parse_key_name),
+        help = "The key that saves all of memory to memory.bin and memory.txt in debug mode, defaults to O"
+    )]
+    save_key: Option<String>,
+    #[structopt(
+        long,
+        help = "Loads an 80 byte font file in place of the built in font"
+    )]
+    font: Option<String>,
+}
+
+/// Parses an address given in hex (0x2A6) or decimal
+fn parse_address(s: &str) -> Result<usize, std::num::ParseIntError> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+}
+
+/// Parses a memory size in hex or decimal, big enough to hold the font at 0x50 to 0x9F
+fn parse_memory_size(s: &str) -> Result<usize, String> {
+    match parse_address(s) {
+        Ok(size) if size >= 0xA0 => Ok(size),
+        Ok(size) => Err(format!(
+            "{} bytes is too small, memory has to be at least 160 bytes",
+            size
+        )),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// Checks a debug key's name, keeping the name so each display can read it as its own kind of key
+fn parse_key_name(s: &str) -> Result<String, String> {
+    parse_key(s).map(|_| s.to_string())
+}
+
+/// The debug keys named on the command line, or failing that in the config file, read with parse
+/// as the display's kind of key, and the defaults for the rest
+fn debug_keys<K>(names: [Option<&String>; 6], parse: fn(&str) -> Result<K, String>) -> DebugKeys<K>
+where
+    DebugKeys<K>: Default,
+{
+    let [step, quit, resume, run_to_breakpoint, run_until_draw, save_memory] = names.map(|name| {
+        // Names from the command line were already checked, so errors are from the config file
+        name.map(|name| {
+            parse(name).unwrap_or_else(|err| {
+                eprintln!("Error in config file: {}", err);
+                std::process::exit(1);
+            })
+        })
+    });
+    let defaults = DebugKeys::default();
+    DebugKeys {
+        step: step.unwrap_or(defaults.step),
+        quit: quit.unwrap_or(defaults.quit),
+        resume: resume.unwrap_or(defaults.resume),
+        run_to_breakpoint: run_to_breakpoint.unwrap_or(defaults.run_to_breakpoint),
+        run_until_draw: run_until_draw.unwrap_or(defaults.run_until_draw),
+        save_memory: save_memory.unwrap_or(defaults.save_memory),
+    }
+}
+
+/// Parses a window scale, which minifb only supports in powers of two up to 32
+fn parse_scale(s: &str) -> Result<usize, String> {
+    match s.parse() {
+        Ok(scale) if [1, 2, 4, 8, 16, 32].contains(&scale) => Ok(scale),
+        _ => Err(format!(
+            "invalid scale {}, expected 1, 2, 4, 8, 16, or 32",
+            s
+        )),
+    }
+}
+
+/// Parses a volume from 0 to 100
+fn parse_volume(s: &str) -> Result<u8, String> {
+    match s.parse() {
+        Ok(volume) if volume <= 100 => Ok(volume),
+        _ => Err(format!("invalid volume {}, expected 0 to 100", s)),
+    }
+}
+
+/// Parses a brightness multiplier from 0.0 to 2.0
+fn parse_brightness(s: &str) -> Result<f32, String> {
+    match s.parse() {
+        Ok(brightness) if (0.0..=2.0).contains(&brightness) => Ok(brightness),
+        _ => Err(format!("invalid brightness {}, expected 0.0 to 2.0", s)),
+    }
+}
+
+fn parse_autosave(s: &str) -> Result<u64, String> {
+    match s.parse() {
+        Ok(frames) if frames > 0 => Ok(frames),
+        _ => Err(format!(
+            "invalid autosave interval {}, expected 1 or more frames",
+            s
+        )),
+    }
+}
+
 #[derive(StructOpt)]
 #[structopt(rename_all = "kebab-case")]
 enum Command {
@@ -11,8 +428,24 @@ enum Command {
         help = "USAGE: load myChip8Binary.chip8 <optional-color>"
     )]
     Load {
-        filename: String,
-        color: Option<color::Color>,
+        #[structopt(
+            help = "The programs to run one after another, optionally followed by a color for the pixels: purple, green, red, blue, or #rrggbb. The built in demo runs without any"
+        )]
+        filenames: Vec<String>,
+        #[structopt(long, help = "Runs a small built in demo instead of a program")]
+        demo: bool,
+        #[structopt(
+            long,
+            help = "Lists the opcodes the emulator can't run instead of running the program"
+        )]
+        check: bool,
+        #[structopt(
+            long,
+            help = "Lists the quirks, whether they're on, and what they do, then exits"
+        )]
+        list_quirks: bool,
+        #[structopt(flatten)]
+        options: RunOptions,
     },
     #[structopt(
         about = "Loads and runs a program in debug mode.
@@ -20,31 +453,489 @@ enum Command {
         at each operation.
         ENTER -> Proceeds to next instruction
         ESC -> Exits the emulator
-        DELETE -> Resumes normal execution",
+        DELETE -> Resumes normal execution
+        G -> Continues until the next breakpoint
+        BACKSPACE -> Rewinds the last instruction
+        N -> Runs until the next instruction that draws
+        M -> Prints the memory around I or --dump-addr
+        O -> Saves all of memory to memory.bin and memory.txt
+        P -> Prints the sprite at I as pixels",
         help = "USAGE: debug myChip8Binary.chip8"
     )]
-    Debug { filename: String },
+    Debug {
+        filename: String,
+        #[structopt(flatten)]
+        options: RunOptions,
+    },
+    #[structopt(
+        about = "Assembles a program written with the mnemonics shown by --trace",
+        help = "USAGE: assemble myChip8Prog.asm myChip8Prog.chip8"
+    )]
+    Assemble { input: String, output: String },
+    #[structopt(
+        about = "Runs every .chip8 file in a directory headless and reports which ones halt",
+        help = "USAGE: test-roms myRoms/"
+    )]
+    TestRoms {
+        dir: String,
+        #[structopt(
+            long,
+            default_value = "1000000",
+            help = "How many instructions a ROM can run before it's stopped"
+        )]
+        max_cycles: u64,
+    },
+    #[structopt(
+        about = "Runs a replay file headless and prints the hash of the state it ends in, failing if it doesn't match the replay's hash",
+        help = "USAGE: replay myRun.replay"
+    )]
+    Replay { file: String },
+    #[structopt(
+        about = "Times how fast the emulator runs a program's instructions, without drawing or timers",
+        help = "USAGE: benchmark myChip8Prog.chip8"
+    )]
+    Benchmark {
+        rom: String,
+        #[structopt(
+            long,
+            default_value = "10000000",
+            help = "How many instructions to run"
+        )]
+        instructions: u64,
+    },
+    #[structopt(
+        about = "Runs the emulator's built-in test ROMs and prints which kinds of instructions pass",
+        help = "USAGE: selftest"
+    )]
+    Selftest,
+    #[structopt(
+        about = "Prints a save state's format version, PC, and memory size, failing if it can't be loaded",
+        help = "USAGE: validate-state autosave.0.state"
+    )]
+    ValidateState { file: String },
+}
+
+/// Reads the key presses written by --record-input
+fn read_input_log(path: &str) -> Result<Vec<input_log::InputEvent>, String> {
+    let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    input_log::parse(&text)
+}
+
+#[cfg(feature = "image")]
+fn read_image_palette(path: &str) -> Result<color::Palette, String> {
+    chip_8_emulator::image_palette::palette_from_image(path)
+}
+
+#[cfg(not(feature = "image"))]
+fn read_image_palette(_path: &str) -> Result<color::Palette, String> {
+    Err("built without the image feature, rebuild with --features image".to_string())
 }
 
-fn load(filename: String, color: color::Color) {
-    let mut chip8 = chip8::CHIP8::new();
-    chip8.color = color;
-    chip8.load_and_run(&filename);
+/// Reads an 80 byte font file
+fn read_font(path: &str) -> Result<[u8; 80], String> {
+    let bytes = std::fs::read(path).map_err(|err| err.to_string())?;
+    bytes
+        .as_slice()
+        .try_into()
+        .map_err(|_| format!("expected 80 bytes but the file has {}", bytes.len()))
 }
 
-fn debug(filename: String) {
-    let mut chip8 = chip8::CHIP8::new();
-    chip8.debug = true;
-    chip8.load_and_run(&filename);
+/// Creates a CHIP8 configured with the options shared by all run modes
+fn configure(options: &RunOptions, color: Option<color::Color>, debug: bool) -> chip8::CHIP8 {
+    // Read before the display is set up so errors are printed to a normal terminal
+    let font = options.font.as_ref().map(|path| {
+        read_font(path).unwrap_or_else(|err| {
+            eprintln!("Error loading font {}: {}", path, err);
+            std::process::exit(1);
+        })
+    });
+    let image_palette = options.palette_from_image.as_ref().map(|path| {
+        read_image_palette(path).unwrap_or_else(|err| {
+            eprintln!("Error reading colors from {}: {}", path, err);
+            std::process::exit(1);
+        })
+    });
+    let mut chip8_options = Options::default();
+    #[cfg(feature = "config")]
+    let config = Config::load().unwrap_or_else(|err| {
+        eprintln!("Error loading config file {}", err);
+        std::process::exit(1);
+    });
+    #[cfg(feature = "config")]
+    if let Err(err) = config.apply(&mut chip8_options) {
+        eprintln!("Error in config file: {}", err);
+        std::process::exit(1);
+    }
+    if let Some(color) = color {
+        chip8_options.color = color;
+    }
+    if let Some(palette) = options.palette.or(image_palette) {
+        chip8_options.palette = Some(palette);
+    }
+    if let Some(brightness) = options.brightness {
+        chip8_options.brightness = brightness;
+    }
+    if let Some(cycles_per_frame) = options.cycles_per_frame {
+        chip8_options.cycles_per_frame = cycles_per_frame;
+    }
+    let quirks = &mut chip8_options.quirks;
+    if let Some(platform) = options.platform {
+        *quirks = Quirks::for_platform(platform);
+    }
+    // A bare --quirk-clip turns the quirk on, --quirk-clip=false turns it off
+    let flag = |quirk: Option<Option<bool>>| quirk.map(|on| on.unwrap_or(true));
+    quirks.apply(&QuirkOverrides {
+        i_overflow: flag(options.quirk_i_overflow),
+        clip: flag(options.quirk_clip),
+        vblank: flag(options.quirk_vblank),
+        shift_vy: flag(options.quirk_shift_vy),
+        load_store_i: flag(options.quirk_load_store_i),
+        jump_vx: flag(options.quirk_jump_vx),
+    });
+    chip8_options.seed = options
+        .seed
+        .or(if options.deterministic { Some(0) } else { None });
+    chip8_options.debug = debug;
+    chip8_options.headless = matches!(options.display, Backend::None);
+    chip8_options.scale = options.scale;
+    chip8_options.memory_size = options.memory_size;
+    chip8_options.start_address = options.start_address;
+    chip8_options.trace = options.trace;
+    chip8_options.profile = options.profile;
+    chip8_options.coverage = options.coverage;
+    chip8_options.strict = options.strict;
+    chip8_options.key_repeat = options.key_repeat;
+    chip8_options.cycle_costs = options.cycle_costs;
+    chip8_options.max_cycles = options.max_cycles;
+    chip8_options.deterministic = options.deterministic;
+    chip8_options.xo_chip = options.xo_chip || options.platform == Some(Platform::XoChip);
+    chip8_options.audio.volume = options.volume;
+    chip8_options.audio.waveform = options.waveform;
+    chip8_options.audio.beep_hz = options.beep_hz;
+    if options.scale_mode == ScaleMode::Smooth && !chip8_options.headless {
+        eprintln!("Warning: this display always scales to the nearest pixel, ignoring --scale-mode smooth");
+    }
+    let flag_keys = [
+        &options.step_key,
+        &options.quit_key,
+        &options.resume_key,
+        &options.continue_key,
+        &options.draw_key,
+        &options.save_key,
+    ];
+    #[cfg(feature = "config")]
+    let config_keys = [
+        &config.keys.step,
+        &config.keys.quit,
+        &config.keys.resume,
+        &config.keys.run_to_breakpoint,
+        &config.keys.draw,
+        &config.keys.save,
+    ]
+    .map(Option::as_ref);
+    #[cfg(not(feature = "config"))]
+    let config_keys = [None; 6];
+    let key_names = std::array::from_fn(|i| flag_keys[i].as_ref().or(config_keys[i]));
+    let mut chip8 = match options.display {
+        Backend::Minifb => {
+            let frontend = MinifbFrontend::new(chip8::WIDTH, chip8::HEIGHT, options.scale)
+                .with_debug_keys(debug_keys(key_names, parse_key))
+                .with_keypad_layout(options.keypad_layout);
+            chip8::CHIP8::with_frontend_options(Box::new(frontend), chip8_options)
+        }
+        Backend::Terminal => {
+            let frontend = TerminalFrontend::new()
+                .with_debug_keys(debug_keys(key_names, parse_terminal_key))
+                .with_keypad_layout(options.keypad_layout);
+            chip8::CHIP8::with_frontend_options(Box::new(frontend), chip8_options)
+        }
+        Backend::None => chip8::CHIP8::with_options(chip8_options),
+    };
+    chip8.breakpoints.extend(&options.breakpoints);
+    chip8.watch_addr = options.watch_addr;
+    chip8.dump_addr = options.dump_addr;
+    chip8.rewind_depth = options.rewind_depth;
+    chip8.persistence = options.persistence;
+    chip8.pause_on_blur = options.pause_on_blur;
+    chip8.show_stats = options.show_stats;
+    chip8.halt_on_pc_wrap = options.halt_on_pc_wrap;
+    chip8.verbose = options.verbose;
+    chip8.detect_idle = options.detect_idle;
+    chip8.dump_on_exit = options.dump_on_exit.clone();
+    chip8.screenshot_on_halt = options.screenshot_on_halt.clone();
+    chip8.screenshot_scale = options.screenshot_scale;
+    chip8.record_input = options.record_input.clone();
+    chip8.autosave = options.autosave;
+    chip8.autosave_path = options.autosave_path.clone();
+    chip8.resume_from = options.load_state.clone();
+    if let Some(path) = &options.replay_input {
+        let events = read_input_log(path).unwrap_or_else(|err| {
+            eprintln!("Error loading input from {}: {}", path, err);
+            std::process::exit(1);
+        });
+        chip8.replay_input(events);
+    }
+    chip8.inverted = options.invert;
+    if matches!(options.display, Backend::Terminal) && options.overlay != Overlay::None {
+        eprintln!("Warning: the terminal is too small for an overlay, ignoring --overlay");
+    } else {
+        chip8.overlay = options.overlay;
+    }
+    if let Some(font) = font {
+        chip8.load_custom_fonts(&font);
+    }
+    chip8
+}
+
+/// Loads and runs the program, or the demo without one, then prints the profile summary and
+/// coverage report when enabled
+fn run(mut chip8: chip8::CHIP8, filenames: &[String]) {
+    let mut reports = String::new();
+    let result = match filenames {
+        [] => {
+            let result = chip8.run_demo();
+            reports.push_str(&reports_for(&chip8));
+            result
+        }
+        filenames => chip8.run_sequence_with(filenames, |chip8, file| {
+            let report = reports_for(chip8);
+            // Each program gets its own, so say which is which
+            if filenames.len() > 1 && !report.is_empty() {
+                reports.push_str(&format!("{}:\n", file));
+            }
+            reports.push_str(&report);
+        }),
+    };
+    drop(chip8); // Hands the terminal back before printing anything
+    eprint!("{}", reports);
+    if let Err(err) = result {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+/// Splits off the color that can follow the programs, e.g. `load game.chip8 green`
+fn split_color(mut filenames: Vec<String>) -> (Vec<String>, Option<color::Color>) {
+    let is_color =
+        |arg: &str| matches!(arg, "purple" | "green" | "red" | "blue") || arg.starts_with('#');
+    match filenames.last() {
+        Some(last) if is_color(last) => {
+            let color = filenames.pop().and_then(|color| color.parse().ok());
+            (filenames, color)
+        }
+        _ => (filenames, None),
+    }
+}
+
+/// The profile summary and coverage report for the program that just ran, when they're enabled
+fn reports_for(chip8: &chip8::CHIP8) -> String {
+    let mut reports = String::new();
+    if chip8.profile {
+        reports.push_str(&chip8.profile_summary());
+    }
+    if chip8.coverage {
+        reports.push_str(&chip8.coverage_report());
+    }
+    reports
+}
+
+fn load(filenames: Vec<String>, color: Option<color::Color>, options: RunOptions) {
+    // A recording covers one run from the start, so it can't be split between programs
+    if filenames.len() > 1 && (options.record_input.is_some() || options.replay_input.is_some()) {
+        eprintln!("Error: --record-input and --replay-input work with one program at a time");
+        std::process::exit(1);
+    }
+    if filenames.is_empty() && options.load_state.is_some() {
+        eprintln!("Error: --load-state needs the program the state was saved from");
+        std::process::exit(1);
+    }
+    let chip8 = configure(&options, color, false);
+    run(chip8, &filenames);
+}
+
+/// Prints the opcodes in the programs that can't be run, exiting with an error if there are any
+fn check(filenames: Vec<String>, mut options: RunOptions) {
+    options.display = Backend::None;
+    let mut chip8 = configure(&options, None, false);
+    let mut unknown_found = false;
+    let mut print_unknown = |chip8: &chip8::CHIP8| {
+        let unknown = chip8.validate_rom();
+        if unknown.is_empty() {
+            println!("No unknown opcodes found");
+        }
+        for (addr, opcode) in &unknown {
+            println!("{:03x}: {:04x}", addr, opcode);
+        }
+        unknown_found |= !unknown.is_empty();
+    };
+    if filenames.is_empty() {
+        chip8.load_bytes(chip8::DEMO_ROM).unwrap_or_else(|err| {
+            eprintln!("Error loading the demo: {}", err);
+            std::process::exit(1);
+        });
+        print_unknown(&chip8);
+    }
+    for filename in &filenames {
+        if filenames.len() > 1 {
+            println!("{}:", filename);
+        }
+        chip8.reset();
+        chip8.load_into_memory(filename);
+        print_unknown(&chip8);
+    }
+    if unknown_found {
+        std::process::exit(1);
+    }
+}
+
+/// Prints every quirk with whether the options turn it on
+fn list_quirks(mut options: RunOptions) {
+    options.display = Backend::None;
+    let chip8 = configure(&options, None, false);
+    for (flag, on, description) in chip8.quirks.list() {
+        println!(
+            "{:<20} {:<4} {}",
+            flag,
+            if on { "on" } else { "off" },
+            description
+        );
+    }
+}
+
+fn debug(filename: String, options: RunOptions) {
+    let chip8 = configure(&options, None, true);
+    run(chip8, &[filename]);
+}
+
+fn assemble(input: String, output: String) {
+    let result = std::fs::read_to_string(&input)
+        .map_err(|err| err.to_string())
+        .and_then(|source| assembler::assemble(&source).map_err(|err| err.to_string()))
+        .and_then(|program| std::fs::write(&output, program).map_err(|err| err.to_string()));
+    if let Err(err) = result {
+        eprintln!("Error assembling {}: {}", input, err);
+        std::process::exit(1);
+    }
+}
+
+/// Runs every ROM in the directory and prints a summary, exiting with an error if any failed
+fn test_roms(dir: String, max_cycles: u64) {
+    let path = std::path::Path::new(&dir);
+    let results = batch::run_directory(path, max_cycles).unwrap_or_else(|err| {
+        eprintln!("Error reading {}: {}", dir, err);
+        std::process::exit(1);
+    });
+    print!("{}", batch::summary(&results));
+    if results.iter().any(|rom| rom.result.is_err()) {
+        std::process::exit(1);
+    }
+}
+
+/// Runs the replay, exiting with an error if it ends in a different state than the replay expects
+fn run_replay(file: String) {
+    let result = std::fs::read_to_string(&file)
+        .map_err(|err| err.to_string())
+        .and_then(|text| replay::Replay::parse(&text))
+        .and_then(|replay| replay.run().map(|hash| (hash, replay.hash)));
+    let (hash, expected) = result.unwrap_or_else(|err| {
+        eprintln!("Error replaying {}: {}", file, err);
+        std::process::exit(1);
+    });
+    println!("{}", hash);
+    if let Some(expected) = expected.filter(|expected| *expected != hash) {
+        eprintln!("Error: the replay should end with hash {}", expected);
+        std::process::exit(1);
+    }
+}
+
+/// Prints how many instructions a second the emulator ran the program at, and the COSMAC VIP
+/// clock speed that would take
+fn run_benchmark(rom: String, instructions: u64) {
+    let result = std::fs::read(&rom)
+        .map_err(|err| err.to_string())
+        .and_then(|bytes| benchmark::run(&bytes, instructions).map_err(|err| err.to_string()));
+    let result = result.unwrap_or_else(|err| {
+        eprintln!("Error benchmarking {}: {}", rom, err);
+        std::process::exit(1);
+    });
+    println!(
+        "{} instructions in {:.3}s",
+        result.instructions,
+        result.elapsed.as_secs_f64()
+    );
+    println!(
+        "{:.0} instructions/s, {:.2} emulated MHz",
+        result.instructions_per_second(),
+        result.emulated_mhz()
+    );
+}
+
+/// Runs the bundled test ROMs, exiting with an error if any of them failed
+fn run_selftest() {
+    let results = selftest::run();
+    print!("{}", selftest::summary(&results));
+    if results.iter().any(|case| !case.passed) {
+        std::process::exit(1);
+    }
+}
+
+/// Prints what the save state's header says, exiting with an error if it's cut short, corrupt, or
+/// from a version that can't be loaded
+fn validate_state(file: String) {
+    let header = save_state::inspect_state(&file).unwrap_or_else(|err| {
+        eprintln!("Error reading {}: {}", file, err);
+        std::process::exit(1);
+    });
+    println!("Version: {}", header.version);
+    println!("PC: {:03x}", header.pc);
+    println!("Memory: {} bytes", header.memory_size);
+    println!("Display: {}x{}", header.width, header.height);
+}
+
+/// Logs to stderr at the level given, or the one in RUST_LOG, or info and up so hitting a
+/// breakpoint or watchpoint is shown
+fn init_logging(level: Option<log::LevelFilter>) {
+    let env = env_logger::Env::default().default_filter_or("info");
+    let mut builder = env_logger::Builder::from_env(env);
+    if let Some(level) = level {
+        builder.filter_level(level);
+    }
+    builder.init();
 }
 
 fn main() {
     let args = Command::from_args();
+    init_logging(match &args {
+        Command::Load { options, .. } | Command::Debug { options, .. } => options.log_level,
+        _ => None,
+    });
     match args {
-        Command::Load { filename, color } => match color {
-            Some(color) => load(filename, color),
-            None => load(filename, color::Color::Purple),
-        },
-        Command::Debug { filename } => debug(filename),
+        Command::Load {
+            list_quirks: true,
+            options,
+            ..
+        } => list_quirks(options),
+        Command::Load {
+            filenames,
+            check: true,
+            options,
+            ..
+        } => check(split_color(filenames).0, options),
+        Command::Load {
+            filenames,
+            demo,
+            options,
+            ..
+        } => {
+            let (filenames, color) = split_color(filenames);
+            load(if demo { Vec::new() } else { filenames }, color, options)
+        }
+        Command::Debug { filename, options } => debug(filename, options),
+        Command::Assemble { input, output } => assemble(input, output),
+        Command::TestRoms { dir, max_cycles } => test_roms(dir, max_cycles),
+        Command::Replay { file } => run_replay(file),
+        Command::Benchmark { rom, instructions } => run_benchmark(rom, instructions),
+        Command::Selftest => run_selftest(),
+        Command::ValidateState { file } => validate_state(file),
     }
 }
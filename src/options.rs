@@ -0,0 +1,75 @@
+use crate::audio::Audio;
+use crate::color::{Color, Palette};
+use crate::quirks::Quirks;
+
+/// Everything needed to set up a CHIP8 in one go, for CHIP8::with_options
+pub struct Options {
+    pub color: Color,
+    /// Replaces the palette made from color
+    pub palette: Option<Palette>,
+    /// Multiplies the brightness of lit pixels, from 0.0 to 2.0
+    pub brightness: f32,
+    pub quirks: Quirks,
+    /// Instructions run each 60Hz frame
+    pub cycles_per_frame: usize,
+    /// Seeds the random numbers used by Cxnn, seeded from entropy when None
+    pub seed: Option<u64>,
+    /// Starts in debug mode, waiting for a debug command before each instruction
+    pub debug: bool,
+    /// Runs without a display or keyboard
+    pub headless: bool,
+    /// Window pixels across each CHIP8 pixel: 1, 2, 4, 8, 16, or 32
+    pub scale: usize,
+    /// Bytes of memory, 4096 like the COSMAC VIP
+    pub memory_size: usize,
+    /// Where programs are loaded and start running
+    pub start_address: usize,
+    /// Logs the PC, opcode, and mnemonic of each instruction
+    pub trace: bool,
+    /// Counts the opcode classes run, for profile_summary
+    pub profile: bool,
+    /// Counts the opcode classes run, for coverage_report
+    pub coverage: bool,
+    /// Warns about instructions that are allowed but probably mistakes
+    pub strict: bool,
+    /// Fx0A takes a key that's already held down instead of waiting for a new press
+    pub key_repeat: bool,
+    /// Paces frames by COSMAC VIP machine cycles instead of instructions
+    pub cycle_costs: bool,
+    /// Stops with an error after this many instructions
+    pub max_cycles: Option<u64>,
+    /// Runs frames back to back instead of at 60Hz
+    pub deterministic: bool,
+    /// Enables the XO-CHIP instructions
+    pub xo_chip: bool,
+    /// The beep's volume, waveform, and pitch
+    pub audio: Audio,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            color: Color::Purple,
+            palette: None,
+            brightness: 1.0,
+            quirks: Quirks::default(),
+            cycles_per_frame: 10,
+            seed: None,
+            debug: false,
+            headless: false,
+            scale: 32,
+            memory_size: 4096,
+            start_address: 0x200,
+            trace: false,
+            profile: false,
+            coverage: false,
+            strict: false,
+            key_repeat: false,
+            cycle_costs: false,
+            max_cycles: None,
+            deterministic: false,
+            xo_chip: false,
+            audio: Audio::default(),
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use crate::color::scale;
+
+/// How many pixels across and down each CHIP8 pixel is drawn as, leaving room for the lines
+pub const OVERLAY_SCALE: usize = 4;
+
+/// How much of the color is left on the darkened lines
+const DARKEN: f32 = 0.5;
+
+/// Lines drawn over the display for a retro look
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Overlay {
+    None,
+    /// Darkens every other row, like a CRT
+    Scanlines,
+    /// Darkens the bottom row and right column of each pixel, separating them
+    Grid,
+}
+
+impl std::str::FromStr for Overlay {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Overlay::None),
+            "scanlines" => Ok(Overlay::Scanlines),
+            "grid" => Ok(Overlay::Grid),
+            _ => Err(format!(
+                "unknown overlay {}, expected scanlines, grid, or none",
+                s
+            )),
+        }
+    }
+}
+
+impl Overlay {
+    /// Scales a width * height frame up by OVERLAY_SCALE and draws the lines over it
+    /// Returns the new frame with its width and height
+    pub fn apply(&self, buffer: &[u32], width: usize, height: usize) -> (Vec<u32>, usize, usize) {
+        let (scaled_width, scaled_height) = (width * OVERLAY_SCALE, height * OVERLAY_SCALE);
+        let mut scaled = Vec::with_capacity(scaled_width * scaled_height);
+        for y in 0..scaled_height {
+            for x in 0..scaled_width {
+                let pixel = buffer[(y / OVERLAY_SCALE) * width + x / OVERLAY_SCALE];
+                let darken = match self {
+                    Overlay::None => false,
+                    Overlay::Scanlines => y % 2 == 1,
+                    Overlay::Grid => [x, y]
+                        .iter()
+                        .any(|i| i % OVERLAY_SCALE == OVERLAY_SCALE - 1),
+                };
+                scaled.push(if darken { scale(pixel, DARKEN) } else { pixel });
+            }
+        }
+        (scaled, scaled_width, scaled_height)
+    }
+}
+
+#[test]
+fn test_apply_overlay() {
+    let (red, green) = (0xff0000, 0x00ff00);
+    let (dark_red, dark_green) = (0x800000, 0x008000);
+    let dark_row = [
+        dark_red, dark_red, dark_red, dark_red, dark_green, dark_green, dark_green, dark_green,
+    ];
+    let row = |scaled: &[u32], y: usize| scaled[y * 8..(y + 1) * 8].to_vec();
+
+    let (scaled, width, height) = Overlay::Scanlines.apply(&[red, green], 2, 1);
+    assert_eq!((width, height), (8, 4));
+    assert_eq!(
+        row(&scaled, 0),
+        [red, red, red, red, green, green, green, green]
+    );
+    assert_eq!(row(&scaled, 1), dark_row);
+    assert_eq!(row(&scaled, 2), row(&scaled, 0));
+
+    let (scaled, _, _) = Overlay::Grid.apply(&[red, green], 2, 1);
+    assert_eq!(
+        row(&scaled, 0),
+        [red, red, red, dark_red, green, green, green, dark_green]
+    );
+    assert_eq!(row(&scaled, 2), row(&scaled, 0));
+    assert_eq!(row(&scaled, 3), dark_row);
+
+    let (scaled, _, _) = Overlay::None.apply(&[red, green], 2, 1);
+    assert!((0..4).all(|y| row(&scaled, y) == [red, red, red, red, green, green, green, green]));
+
+    assert_eq!("grid".parse(), Ok(Overlay::Grid));
+    assert!("crt".parse::<Overlay>().is_err());
+}
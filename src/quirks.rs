@@ -0,0 +1,202 @@
+/// The machines and interpreters with a bundle of quirks that can be picked by name
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Platform {
+    Chip8,
+    Schip,
+    XoChip,
+}
+
+impl std::str::FromStr for Platform {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chip8" => Ok(Platform::Chip8),
+            "schip" => Ok(Platform::Schip),
+            "xochip" => Ok(Platform::XoChip),
+            _ => Err(format!(
+                "unknown platform {}, expected chip8, schip, or xochip",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Platform {
+    /// The name --platform takes
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Platform::Chip8 => write!(f, "chip8"),
+            Platform::Schip => write!(f, "schip"),
+            Platform::XoChip => write!(f, "xochip"),
+        }
+    }
+}
+
+/// Behaviours that differ between CHIP8 interpreters
+/// Every quirk is off by default, use for_platform to match a particular machine
+#[derive(Debug, Default, PartialEq)]
+pub struct Quirks {
+    /// Fx1E sets VF when I + Vx overflows past 0xFFF, like the Amiga interpreter
+    pub i_add_overflow_flag: bool,
+    /// Dxyn drops sprite pixels past the right and bottom edges instead of wrapping them around
+    pub clip_sprites: bool,
+    /// Dxyn ends the frame, like the COSMAC VIP waiting for vblank, so at most one sprite is drawn each frame
+    pub draw_wait_vblank: bool,
+    /// 8xy6 and 8xyE shift Vy into Vx instead of shifting Vx in place, like the COSMAC VIP
+    pub shift_uses_vy: bool,
+    /// Fx55 and Fx65 leave I pointing past the last register stored or loaded, like the COSMAC VIP
+    pub load_store_increment_i: bool,
+    /// Bnnn jumps to nnn + Vx, where x is the top digit of nnn, instead of nnn + V0, like SCHIP
+    pub jump_uses_vx: bool,
+}
+
+/// Quirks turned on or off one at a time, named after their --quirk-* flags
+/// None leaves the quirk as the platform has it
+#[derive(Debug, Default, PartialEq)]
+pub struct QuirkOverrides {
+    pub i_overflow: Option<bool>,
+    pub clip: Option<bool>,
+    pub vblank: Option<bool>,
+    pub shift_vy: Option<bool>,
+    pub load_store_i: Option<bool>,
+    pub jump_vx: Option<bool>,
+}
+
+impl Quirks {
+    /// Turns each quirk the overrides give on or off, applied after for_platform so they win
+    pub fn apply(&mut self, overrides: &QuirkOverrides) {
+        for (quirk, value) in [
+            (&mut self.i_add_overflow_flag, overrides.i_overflow),
+            (&mut self.clip_sprites, overrides.clip),
+            (&mut self.draw_wait_vblank, overrides.vblank),
+            (&mut self.shift_uses_vy, overrides.shift_vy),
+            (&mut self.load_store_increment_i, overrides.load_store_i),
+            (&mut self.jump_uses_vx, overrides.jump_vx),
+        ] {
+            if let Some(value) = value {
+                *quirk = value;
+            }
+        }
+    }
+
+    /// The quirks the platform's programs expect
+    pub fn for_platform(platform: Platform) -> Quirks {
+        match platform {
+            Platform::Chip8 => Quirks {
+                i_add_overflow_flag: false,
+                clip_sprites: true,
+                draw_wait_vblank: true,
+                shift_uses_vy: true,
+                load_store_increment_i: true,
+                jump_uses_vx: false,
+            },
+            Platform::Schip => Quirks {
+                i_add_overflow_flag: false,
+                clip_sprites: true,
+                draw_wait_vblank: false,
+                shift_uses_vy: false,
+                load_store_increment_i: false,
+                jump_uses_vx: true,
+            },
+            Platform::XoChip => Quirks {
+                i_add_overflow_flag: false,
+                clip_sprites: false,
+                draw_wait_vblank: false,
+                shift_uses_vy: true,
+                load_store_increment_i: true,
+                jump_uses_vx: false,
+            },
+        }
+    }
+
+    /// Each quirk's command line flag, whether it's on, and what it does
+    pub fn list(&self) -> Vec<(&'static str, bool, &'static str)> {
+        vec![
+            (
+                "--quirk-i-overflow",
+                self.i_add_overflow_flag,
+                "Fx1E sets VF when I overflows past 0xFFF",
+            ),
+            (
+                "--quirk-clip",
+                self.clip_sprites,
+                "Dxyn clips sprites at the screen edges instead of wrapping them around",
+            ),
+            (
+                "--quirk-vblank",
+                self.draw_wait_vblank,
+                "Dxyn waits for the next 60Hz frame, so at most one sprite is drawn per frame",
+            ),
+            (
+                "--quirk-shift-vy",
+                self.shift_uses_vy,
+                "8xy6 and 8xyE shift Vy into Vx instead of shifting Vx in place",
+            ),
+            (
+                "--quirk-load-store-i",
+                self.load_store_increment_i,
+                "Fx55 and Fx65 leave I pointing past the last register stored or loaded",
+            ),
+            (
+                "--quirk-jump-vx",
+                self.jump_uses_vx,
+                "Bxnn jumps to xnn + Vx instead of Bnnn jumping to nnn + V0",
+            ),
+        ]
+    }
+}
+
+#[test]
+fn test_list() {
+    let quirks = Quirks {
+        clip_sprites: true,
+        ..Quirks::default()
+    };
+    let list = quirks.list();
+    let flags: Vec<&str> = list.iter().map(|&(flag, _, _)| flag).collect();
+    assert_eq!(
+        flags,
+        [
+            "--quirk-i-overflow",
+            "--quirk-clip",
+            "--quirk-vblank",
+            "--quirk-shift-vy",
+            "--quirk-load-store-i",
+            "--quirk-jump-vx"
+        ]
+    );
+    let on: Vec<bool> = list.iter().map(|&(_, on, _)| on).collect();
+    assert_eq!(on, [false, true, false, false, false, false]);
+}
+
+#[test]
+fn test_for_platform() {
+    let chip8 = Quirks::for_platform(Platform::Chip8);
+    assert!(chip8.clip_sprites && chip8.draw_wait_vblank);
+    assert!(chip8.shift_uses_vy && chip8.load_store_increment_i);
+    assert!(!chip8.jump_uses_vx && !chip8.i_add_overflow_flag);
+
+    let schip = Quirks::for_platform(Platform::Schip);
+    assert!(schip.clip_sprites && schip.jump_uses_vx);
+    assert!(!schip.draw_wait_vblank && !schip.shift_uses_vy && !schip.load_store_increment_i);
+
+    let xochip = Quirks::for_platform(Platform::XoChip);
+    assert!(xochip.shift_uses_vy && xochip.load_store_increment_i);
+    assert!(!xochip.clip_sprites && !xochip.draw_wait_vblank && !xochip.jump_uses_vx);
+
+    assert_eq!("schip".parse(), Ok(Platform::Schip));
+    assert!("chip48".parse::<Platform>().is_err());
+}
+
+#[test]
+fn test_apply_overrides() {
+    let mut quirks = Quirks::for_platform(Platform::Chip8);
+    quirks.apply(&QuirkOverrides {
+        clip: Some(false),
+        jump_vx: Some(true),
+        ..QuirkOverrides::default()
+    });
+    assert!(!quirks.clip_sprites); // Turned off though chip8 turns it on
+    assert!(quirks.jump_uses_vx);
+    assert!(quirks.draw_wait_vblank && quirks.shift_uses_vy); // Kept from chip8
+}
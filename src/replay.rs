@@ -0,0 +1,122 @@
+use crate::chip8::CHIP8;
+use crate::error::Error;
+use crate::input_log::{self, InputEvent};
+
+/// Everything needed to repeat a run exactly: the ROM, the random seed, the speed, how long to run,
+/// and the keys pressed along the way, with the hash of the state it should end in
+#[derive(Debug, PartialEq)]
+pub struct Replay {
+    pub rom: String,
+    pub seed: u64,
+    pub cycles_per_frame: usize,
+    pub cycles: u64,
+    pub hash: Option<String>,
+    pub input: Vec<InputEvent>,
+}
+
+impl Replay {
+    /// Parses a replay file: `name value` lines for the settings, then the input in the
+    /// `cycle key pressed` lines written by --record-input
+    pub fn parse(text: &str) -> Result<Replay, String> {
+        let mut rom = None;
+        let mut seed = 0;
+        let mut cycles_per_frame = 10;
+        let mut cycles = None;
+        let mut hash = None;
+        // Settings lines are blanked out so the input errors keep their line numbers
+        let mut input = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let mut fields = line.split_whitespace();
+            let (name, value) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(name), Some(value), None) if !name.starts_with('#') => (name, value),
+                _ => {
+                    input.push(line);
+                    continue;
+                }
+            };
+            let error = |_| format!("line {}: invalid {}", number + 1, name);
+            match name {
+                "rom" => rom = Some(value.to_string()),
+                "seed" => seed = value.parse().map_err(error)?,
+                "cycles-per-frame" => cycles_per_frame = value.parse().map_err(error)?,
+                "cycles" => cycles = Some(value.parse().map_err(error)?),
+                "hash" => hash = Some(value.to_string()),
+                _ => return Err(format!("line {}: unknown setting {}", number + 1, name)),
+            }
+            input.push("");
+        }
+        Ok(Replay {
+            rom: rom.ok_or("the replay has no rom")?,
+            seed,
+            cycles_per_frame,
+            cycles: cycles.ok_or("the replay has no cycles")?,
+            hash,
+            input: input_log::parse(&input.join("\n"))?,
+        })
+    }
+
+    /// Formats the replay the way parse reads it
+    pub fn format(&self) -> String {
+        let mut text = format!(
+            "rom {}\nseed {}\ncycles-per-frame {}\ncycles {}\n",
+            self.rom, self.seed, self.cycles_per_frame, self.cycles
+        );
+        if let Some(hash) = &self.hash {
+            text.push_str(&format!("hash {}\n", hash));
+        }
+        text.push_str(&input_log::format(&self.input));
+        text
+    }
+
+    /// Runs the ROM headless for the number of cycles, or until it halts, returning the hash of
+    /// the state it ended in
+    pub fn run(&self) -> Result<String, String> {
+        let rom = std::fs::read(&self.rom).map_err(|err| format!("{}: {}", self.rom, err))?;
+        let mut chip8 = CHIP8::headless();
+        chip8.deterministic = true;
+        chip8.set_seed(self.seed);
+        chip8.cycles_per_frame = self.cycles_per_frame;
+        chip8.max_cycles = Some(self.cycles);
+        chip8.replay_input(self.input.clone());
+        chip8.load_bytes(&rom).map_err(|err| err.to_string())?;
+        match chip8.run() {
+            Ok(()) | Err(Error::InstructionLimitReached { .. }) => Ok(chip8.snapshot().hash()),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+}
+
+#[test]
+fn test_replay() {
+    let text = std::fs::read_to_string("testbin/count_key_held.replay").unwrap();
+    let replay = Replay::parse(&text).unwrap();
+    assert_eq!(replay.cycles, 200);
+    assert_eq!(replay.input.len(), 2);
+    assert_eq!(replay.run(), Ok(replay.hash.clone().unwrap()));
+    assert_eq!(Replay::parse(&replay.format()), Ok(replay));
+
+    // Without the key the program ends up somewhere else
+    let mut without_input = Replay::parse(&text).unwrap();
+    without_input.input.clear();
+    assert_ne!(without_input.run(), Ok(without_input.hash.clone().unwrap()));
+}
+
+#[test]
+fn test_parse_replay_errors() {
+    assert_eq!(
+        Replay::parse("cycles 10"),
+        Err("the replay has no rom".to_string())
+    );
+    assert_eq!(
+        Replay::parse("rom a\nseed x"),
+        Err("line 2: invalid seed".to_string())
+    );
+    assert_eq!(
+        Replay::parse("rom a\nspeed 5"),
+        Err("line 2: unknown setting speed".to_string())
+    );
+    assert_eq!(
+        Replay::parse("rom a\ncycles 5\n\n1 16 1"),
+        Err("line 4: invalid key".to_string())
+    );
+}
@@ -0,0 +1,146 @@
+use crate::quirks::Platform;
+
+/// What's known about a recognized ROM
+#[derive(Debug, PartialEq)]
+pub struct RomInfo {
+    pub name: &'static str,
+    /// The platform whose quirks the ROM expects
+    pub platform: Platform,
+}
+
+/// ROMs recognized by the SHA-1 of their bytes, the public domain IBM logo and the examples that
+/// ship with the emulator
+/// Hash the exact file when adding one, e.g. with sha1sum, since any change to a ROM changes its hash
+const KNOWN_ROMS: [(&str, RomInfo); 4] = [
+    (
+        "1ba58656810b67fd131eb9af3e3987863bf26c90",
+        RomInfo {
+            name: "IBM Logo",
+            platform: Platform::Chip8,
+        },
+    ),
+    (
+        "2ccf80d3ba2b96a282f769c304a0d3b156a7c6ab",
+        RomInfo {
+            name: "Draw A example",
+            platform: Platform::Chip8,
+        },
+    ),
+    (
+        "7a893d9a261b03847308d4eeb03fae2ce57bb791",
+        RomInfo {
+            name: "A + A example",
+            platform: Platform::Chip8,
+        },
+    ),
+    (
+        "537a61aae76a1163123df74991ea14aee4f76d6e",
+        RomInfo {
+            name: "A and + example",
+            platform: Platform::Chip8,
+        },
+    ),
+];
+
+/// Looks the ROM up in the table of known ROMs by its SHA-1
+pub fn identify_rom(bytes: &[u8]) -> Option<&'static RomInfo> {
+    let hash = sha1_hex(bytes);
+    KNOWN_ROMS
+        .iter()
+        .find(|(known, _)| *known == hash)
+        .map(|(_, info)| info)
+}
+
+/// The SHA-1 digest of the bytes as 40 hex digits
+pub fn sha1_hex(bytes: &[u8]) -> String {
+    sha1(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// The SHA-1 digest of the bytes, following FIPS 180-4
+/// Only used to recognize ROMs, SHA-1 is too weak for anything security related
+pub fn sha1(bytes: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    // Pad with a 1 bit, zeros up to 56 bytes into the last block, then the length in bits
+    let mut message = bytes.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((bytes.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (h, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (bytes, word) in digest.chunks_mut(4).zip(h) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+#[test]
+fn test_sha1() {
+    let hex = |digest: [u8; 20]| -> String {
+        digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+    };
+    assert_eq!(hex(sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+    assert_eq!(
+        hex(sha1(b"abc")),
+        "a9993e364706816aba3e25717850c26c9cd0d89d"
+    );
+    // Long enough that the padding needs a second block
+    assert_eq!(
+        hex(sha1(
+            b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+        )),
+        "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+    );
+}
+
+#[test]
+fn test_identify_rom() {
+    let rom = std::fs::read("examples/draw_a.chip8").unwrap();
+    let info = identify_rom(&rom).unwrap();
+    assert_eq!(info.name, "Draw A example");
+    assert_eq!(info.platform, Platform::Chip8);
+
+    let rom = std::fs::read("testbin/ibm_logo.chip8").unwrap();
+    assert_eq!(identify_rom(&rom).unwrap().name, "IBM Logo");
+
+    assert_eq!(identify_rom(&[0x60, 0x05]), None);
+}
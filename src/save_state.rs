@@ -0,0 +1,277 @@
+use crate::chip8::State;
+use crate::grid::Grid;
+
+/// The first bytes of every save state
+const MAGIC: &[u8; 4] = b"CH8S";
+/// Where the stack pointer is, after the 16 byte header, I, the registers, and the stack
+const STACK_POINTER_OFFSET: usize = 16 + 2 + 16 + 32;
+/// Goes up whenever the layout changes, so old saves are rejected instead of read wrong
+pub const VERSION: u16 = 1;
+
+/// Encodes the state as the magic, version, sizes, registers, stack, timers, keys, display, and
+/// memory, with numbers big-endian and the display packed eight pixels to a byte
+pub fn encode(state: &State) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&VERSION.to_be_bytes());
+    bytes.extend_from_slice(&(state.memory.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(&(state.display.width() as u16).to_be_bytes());
+    bytes.extend_from_slice(&(state.display.height() as u16).to_be_bytes());
+    bytes.extend_from_slice(&(state.pc as u16).to_be_bytes());
+    bytes.extend_from_slice(&state.i.to_be_bytes());
+    bytes.extend_from_slice(&state.registers);
+    for addr in state.stack {
+        bytes.extend_from_slice(&addr.to_be_bytes());
+    }
+    bytes.extend_from_slice(&[
+        state.stack_pointer as u8,
+        state.delay_timer,
+        state.sound_timer,
+    ]);
+    let keys = (0..16)
+        .filter(|&key| state.keys[key])
+        .fold(0u16, |keys, key| keys | 1 << key);
+    bytes.extend_from_slice(&keys.to_be_bytes());
+    for pixels in state.display.cells().chunks(8) {
+        let lit = pixels.iter().enumerate().filter(|(_, &on)| on);
+        bytes.push(lit.fold(0, |byte, (bit, _)| byte | 0x80 >> bit));
+    }
+    bytes.extend_from_slice(&state.memory);
+    bytes
+}
+
+/// What a save state says about itself before the registers and memory
+#[derive(Debug, PartialEq)]
+pub struct StateHeader {
+    pub version: u16,
+    pub memory_size: usize,
+    pub width: usize,
+    pub height: usize,
+    pub pc: usize,
+}
+
+impl StateHeader {
+    /// How many bytes the whole save state takes: the header, I, the registers, the stack, the
+    /// stack pointer, timers, and keys, then the packed display and the memory
+    fn file_len(&self) -> usize {
+        let display = (self.width * self.height).div_ceil(8);
+        STACK_POINTER_OFFSET + 5 + display + self.memory_size
+    }
+}
+
+/// Reads the header of a save state file to check it can be loaded, without restoring it
+/// Files the wrong length, like a save cut off partway through, and bad stack pointers are errors
+pub fn inspect_state(path: &str) -> Result<StateHeader, String> {
+    let bytes = std::fs::read(path).map_err(|err| err.to_string())?;
+    let header = read_header(&mut Reader { bytes: &bytes })?;
+    if bytes.len() != header.file_len() {
+        return Err(format!(
+            "the save state is {} bytes instead of {}, it may have been cut off while saving",
+            bytes.len(),
+            header.file_len()
+        ));
+    }
+    check_stack_pointer(bytes[STACK_POINTER_OFFSET] as usize)?;
+    Ok(header)
+}
+
+/// A call can only go 16 levels deep, so anything past that is a corrupt save
+fn check_stack_pointer(stack_pointer: usize) -> Result<(), String> {
+    if stack_pointer > 16 {
+        return Err(format!(
+            "the stack pointer {} is past the 16 levels",
+            stack_pointer
+        ));
+    }
+    Ok(())
+}
+
+/// Reads the header, failing for anything that isn't a save state this version can load
+fn read_header(reader: &mut Reader) -> Result<StateHeader, String> {
+    if reader.take(4)? != MAGIC {
+        return Err("not a save state".to_string());
+    }
+    let version = reader.u16()?;
+    if version != VERSION {
+        return Err(format!(
+            "save state version {} isn't supported, only {}",
+            version, VERSION
+        ));
+    }
+    let header = StateHeader {
+        version,
+        memory_size: reader.u32()? as usize,
+        width: reader.u16()? as usize,
+        height: reader.u16()? as usize,
+        pc: reader.u16()? as usize,
+    };
+    if header.pc >= header.memory_size {
+        return Err(format!(
+            "the PC {:03x} is past the end of the {} bytes of memory",
+            header.pc, header.memory_size
+        ));
+    }
+    Ok(header)
+}
+
+/// Decodes a state written by encode
+pub fn decode(bytes: &[u8]) -> Result<State, String> {
+    let mut reader = Reader { bytes };
+    let StateHeader {
+        memory_size,
+        width,
+        height,
+        pc,
+        ..
+    } = read_header(&mut reader)?;
+    let i = reader.u16()?;
+    let mut registers = [0; 16];
+    registers.copy_from_slice(reader.take(16)?);
+    let mut stack = [0; 16];
+    for addr in stack.iter_mut() {
+        *addr = reader.u16()?;
+    }
+    let stack_pointer = reader.u8()? as usize;
+    check_stack_pointer(stack_pointer)?;
+    let (delay_timer, sound_timer) = (reader.u8()?, reader.u8()?);
+    let held = reader.u16()?;
+    let keys = std::array::from_fn(|key| held & 1 << key != 0);
+    let packed = reader.take((width * height).div_ceil(8))?;
+    let mut display = Grid::new(width, height);
+    for row in 0..height {
+        for col in 0..width {
+            let pixel = row * width + col;
+            display[row][col] = packed[pixel / 8] & 0x80 >> (pixel % 8) != 0;
+        }
+    }
+    let memory = reader.take(memory_size)?.to_vec();
+    if !reader.bytes.is_empty() {
+        return Err(format!(
+            "{} unexpected bytes after the memory",
+            reader.bytes.len()
+        ));
+    }
+    Ok(State {
+        registers,
+        i,
+        pc,
+        stack,
+        stack_pointer,
+        delay_timer,
+        sound_timer,
+        keys,
+        display,
+        memory,
+    })
+}
+
+/// Reads the fields off the front of a save state
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < len {
+            return Err("the save state is cut short".to_string());
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_be_bytes([self.u8()?, self.u8()?]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_be_bytes([
+            self.u8()?,
+            self.u8()?,
+            self.u8()?,
+            self.u8()?,
+        ]))
+    }
+}
+
+#[test]
+fn test_encode_and_decode() {
+    let mut chip8 = crate::chip8::CHIP8::headless();
+    chip8.deterministic = true;
+    chip8.load_and_run("testbin/draw.chip8").unwrap();
+    chip8.set_key(0xA, true).unwrap();
+    let state = chip8.snapshot();
+    let bytes = encode(&state);
+    assert_eq!(bytes[..6], [b'C', b'H', b'8', b'S', 0, 1]);
+    assert_eq!(decode(&bytes).unwrap().diff(&state), Vec::<String>::new());
+
+    assert_eq!(
+        decode(&bytes[..100]).unwrap_err(),
+        "the save state is cut short"
+    );
+    let mut newer = bytes.clone();
+    newer[5] = 2;
+    assert_eq!(
+        decode(&newer).unwrap_err(),
+        "save state version 2 isn't supported, only 1"
+    );
+    assert_eq!(decode(b"PNG").unwrap_err(), "the save state is cut short");
+    assert_eq!(decode(b"\x89PNG\r\n").unwrap_err(), "not a save state");
+}
+
+#[test]
+fn test_decode_tampered() {
+    let mut chip8 = crate::chip8::CHIP8::headless().with_memory_size(0x400);
+    chip8.load_into_memory("testbin/stack_math.chip8");
+    let bytes = encode(&chip8.snapshot());
+
+    // The PC is the last field of the header
+    let mut tampered = bytes.clone();
+    tampered[14..16].copy_from_slice(&0x400u16.to_be_bytes());
+    assert_eq!(
+        decode(&tampered).unwrap_err(),
+        "the PC 400 is past the end of the 1024 bytes of memory"
+    );
+    let mut tampered = bytes.clone();
+    tampered[STACK_POINTER_OFFSET] = 17;
+    assert_eq!(
+        decode(&tampered).unwrap_err(),
+        "the stack pointer 17 is past the 16 levels"
+    );
+    tampered[STACK_POINTER_OFFSET] = 16;
+    assert!(decode(&tampered).is_ok());
+}
+
+#[test]
+fn test_inspect_state() {
+    let path = std::env::temp_dir().join("chip8_test_inspect_state.state");
+    let path = path.to_str().unwrap();
+    let mut chip8 = crate::chip8::CHIP8::headless().with_memory_size(0x800);
+    chip8.load_into_memory("testbin/add_xnn.chip8");
+    chip8.save_state(path).unwrap();
+    let header = inspect_state(path).unwrap();
+    assert_eq!(header.version, VERSION);
+    assert_eq!((header.memory_size, header.pc), (0x800, 0x200));
+    assert_eq!((header.width, header.height), (64, 32));
+
+    // Cut off partway through the memory, as if the emulator was killed while saving
+    let bytes = std::fs::read(path).unwrap();
+    std::fs::write(path, &bytes[..bytes.len() - 100]).unwrap();
+    assert_eq!(
+        inspect_state(path).unwrap_err(),
+        format!(
+            "the save state is {} bytes instead of {}, it may have been cut off while saving",
+            bytes.len() - 100,
+            bytes.len()
+        )
+    );
+    let mut tampered = bytes.clone();
+    tampered[STACK_POINTER_OFFSET] = 200;
+    std::fs::write(path, &tampered).unwrap();
+    assert!(inspect_state(path).is_err());
+    std::fs::remove_file(path).unwrap();
+    assert!(inspect_state(path).is_err());
+}
@@ -0,0 +1,160 @@
+use crate::chip8::CHIP8;
+use crate::error::Error;
+
+/// A bundled test ROM and how to tell whether it ran correctly
+struct Case {
+    name: &'static str,
+    category: &'static str,
+    rom: &'static [u8],
+    check: fn(&CHIP8, &Result<(), Error>) -> bool,
+}
+
+/// Whether one of the bundled ROMs ran correctly
+pub struct CaseResult {
+    pub name: &'static str,
+    pub category: &'static str,
+    pub passed: bool,
+}
+
+/// Picked from the testbin fixtures the unit tests use, built into the binary so they work anywhere
+fn cases() -> Vec<Case> {
+    macro_rules! case {
+        // Most ROMs leave their answer in V0 or I
+        ($category:expr, $name:expr, V0 == $value:expr) => {
+            case!($category, $name, |chip8, result| result.is_ok()
+                && chip8.register(0) == $value)
+        };
+        ($category:expr, $name:expr, I == $value:expr) => {
+            case!($category, $name, |chip8, result| result.is_ok()
+                && chip8.index() == $value)
+        };
+        ($category:expr, $name:expr, $check:expr) => {
+            Case {
+                name: $name,
+                category: $category,
+                rom: include_bytes!(concat!("../testbin/", $name, ".chip8")),
+                check: $check,
+            }
+        };
+    }
+    vec![
+        case!("Flow", "jump_nnn_plus_v0", |chip8, result| {
+            result.is_ok() && chip8.register(1) == 5
+        }),
+        case!("Flow", "stack_math", |chip8, result| {
+            result.is_ok() && (chip8.register(0), chip8.register(1)) == (45, 10)
+        }),
+        case!("Flow", "stack_overflow", |_, result| {
+            *result == Err(Error::StackOverflow { pc: 0x200 })
+        }),
+        case!("Flow", "stack_underflow", |_, result| {
+            *result == Err(Error::StackUnderflow { pc: 0x200 })
+        }),
+        case!("Skips", "is_eq", V0 == 5),
+        case!("Skips", "not_eq", V0 == 6),
+        case!("Skips", "skip_not_eq_iseq", V0 == 6),
+        case!("Skips", "skip_not_eq_neq", V0 == 5),
+        case!("Skips", "xy_eq", V0 == 5),
+        case!("Skips", "xy_neq", V0 == 6),
+        case!("Skips", "xy_neq_eq", V0 == 6),
+        case!("Skips", "xy_neq_neq", V0 == 5),
+        case!("Registers", "set_registers", |chip8, result| {
+            result.is_ok() && (chip8.register(0), chip8.register(1)) == (5, 10)
+        }),
+        case!("Registers", "add_xnn", V0 == 10),
+        case!("Registers", "assign_xy", V0 == 6),
+        case!("Arithmetic", "or_xy", V0 == 255),
+        case!("Arithmetic", "and_xy", V0 == 0),
+        case!("Arithmetic", "xor_xy", V0 == 255),
+        case!("Arithmetic", "add_xy", V0 == 15),
+        case!("Arithmetic", "sub_xy", V0 == 5),
+        case!("Arithmetic", "sub_yx", V0 == 5),
+        case!("Arithmetic", "shift_right", V0 == 2),
+        case!("Arithmetic", "shift_left", V0 == 10),
+        case!("Memory", "set_16bit_register", I == 10),
+        case!("Memory", "add_ix", |chip8, result| {
+            result.is_ok() && chip8.register(0) == 5 && chip8.index() == 5
+        }),
+        case!("Memory", "set_i_to_sprite", I == 130),
+        case!("Display", "clear_screen", |chip8, result| {
+            result.is_ok() && !chip8.framebuffer()[0][0]
+        }),
+        case!("Display", "draw", |chip8, result| {
+            let row = [false, false, true, true, true, true, false, false];
+            result.is_ok() && chip8.framebuffer()[0][..8] == row
+        }),
+        case!("Timers", "timers", |chip8, result| {
+            result.is_ok() && (0..3).map(|x| chip8.register(x)).eq([5, 5, 10])
+        }),
+    ]
+}
+
+/// Runs every bundled ROM headless, in category order
+/// The instruction limit stops a broken build from looping forever
+pub fn run() -> Vec<CaseResult> {
+    cases()
+        .into_iter()
+        .map(|case| {
+            let mut chip8 = CHIP8::headless();
+            chip8.deterministic = true;
+            chip8.non_blocking_keys = true;
+            chip8.max_cycles = Some(10_000);
+            let result = chip8.load_bytes(case.rom).and_then(|_| chip8.run());
+            CaseResult {
+                name: case.name,
+                category: case.category,
+                passed: (case.check)(&chip8, &result),
+            }
+        })
+        .collect()
+}
+
+/// A line for each category saying how many of its ROMs passed, naming any that failed
+pub fn summary(results: &[CaseResult]) -> String {
+    let mut categories: Vec<&str> = Vec::new();
+    for result in results {
+        if !categories.contains(&result.category) {
+            categories.push(result.category);
+        }
+    }
+    let mut summary = String::new();
+    for category in categories {
+        let cases: Vec<&CaseResult> = results.iter().filter(|r| r.category == category).collect();
+        let failed: Vec<&str> = cases.iter().filter(|r| !r.passed).map(|r| r.name).collect();
+        let outcome = if failed.is_empty() { "pass" } else { "FAIL" };
+        let passed = cases.len() - failed.len();
+        summary.push_str(&format!(
+            "{:<12} {} {}/{}",
+            category,
+            outcome,
+            passed,
+            cases.len()
+        ));
+        if !failed.is_empty() {
+            summary.push_str(&format!(" ({})", failed.join(", ")));
+        }
+        summary.push('\n');
+    }
+    summary
+}
+
+#[test]
+fn test_selftest() {
+    let results = run();
+    let failed: Vec<&str> = results
+        .iter()
+        .filter(|r| !r.passed)
+        .map(|r| r.name)
+        .collect();
+    assert!(failed.is_empty(), "failed: {:?}", failed);
+    let text = summary(&results);
+    assert!(text.lines().all(|line| line.contains(" pass ")));
+    assert!(text.starts_with("Flow         pass 4/4\n"));
+
+    let results = [CaseResult {
+        name: "add_xy",
+        category: "Arithmetic",
+        passed: false,
+    }];
+    assert_eq!(summary(&results), "Arithmetic   FAIL 0/1 (add_xy)\n");
+}
@@ -0,0 +1,62 @@
+use crate::chip8::{StepResult, CHIP8};
+use wasm_bindgen::prelude::*;
+
+/// A CHIP8 driven from JavaScript
+/// JS runs the loop, renders the framebuffer, and forwards key presses
+#[wasm_bindgen]
+pub struct WasmChip8 {
+    chip8: CHIP8,
+}
+
+impl Default for WasmChip8 {
+    fn default() -> Self {
+        WasmChip8::new()
+    }
+}
+
+#[wasm_bindgen]
+impl WasmChip8 {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WasmChip8 {
+        let mut chip8 = CHIP8::headless();
+        // Blocking would freeze the page, so Fx0A waits across steps for set_key instead
+        chip8.non_blocking_keys = true;
+        WasmChip8 { chip8 }
+    }
+
+    pub fn load_bytes(&mut self, rom: &[u8]) -> Result<(), JsValue> {
+        self.chip8
+            .load_bytes(rom)
+            .map_err(|err| JsValue::from_str(&err.to_string()))
+    }
+
+    /// Executes a single instruction
+    /// returns false once the program has halted
+    pub fn step(&mut self) -> Result<bool, JsValue> {
+        match self.chip8.step() {
+            Ok(StepResult::Continue) => Ok(true),
+            Ok(StepResult::Halted(_)) => Ok(false),
+            Err(err) => Err(JsValue::from_str(&err.to_string())),
+        }
+    }
+
+    /// The display as one byte per pixel, row by row: 1 when lit and 0 when not
+    pub fn framebuffer(&self) -> Vec<u8> {
+        self.chip8
+            .framebuffer_flat()
+            .into_iter()
+            .map(|pixel| pixel as u8)
+            .collect()
+    }
+
+    /// Whether anything was drawn since the last call
+    pub fn take_draw_flag(&mut self) -> bool {
+        self.chip8.take_draw_flag()
+    }
+
+    pub fn set_key(&mut self, idx: usize, pressed: bool) -> Result<(), JsValue> {
+        self.chip8
+            .set_key(idx, pressed)
+            .map_err(|err| JsValue::from_str(&err.to_string()))
+    }
+}